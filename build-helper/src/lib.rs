@@ -0,0 +1,376 @@
+// 各プラグインの build.rs から使う共通処理です。
+// Cargo.toml の `[package.metadata.jk_plugin]` と Justfile を読み、内容が食い違っていればビルドを止めます。
+// AE の SDK がある環境では、読み込んだ値から PiPL を生成します。
+//
+//   [package.metadata.jk_plugin]
+//   plugin_name = "JK Color Key Fs"                       # ファイル名 (Justfile の PluginName と同じ)
+//   identifier = "com.adobe.AfterEffects.colorkey-fs"     # com.adobe.AfterEffects.<パッケージ名>
+//   effect_name = "JK Color Key F's"                      # エフェクト名とマッチ名 (省略時は plugin_name)
+//
+// effect_name は元の F's Plugins のプロジェクトを開けるようにマッチ名を同じにするためのもので、
+// ファイル名にアポストロフィを入れないよう plugin_name では F's を Fs と書きます。
+// それ以外の違いは食い違いとしてビルドを止めます (plugin_name == effect_name からアポストロフィを除いたもの)。
+// エフェクト名は環境変数 JK_EFFECT_NAME でプラグインのコードに渡すので、lib.rs には書きません。
+//   category = "JK Plugins F's"                           # 省略時は DEFAULT_CATEGORY
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[cfg(any(windows, target_os = "macos"))]
+mod pipl_gen;
+
+pub const DEFAULT_CATEGORY: &str = "JK Plugins F's";
+pub const IDENTIFIER_PREFIX: &str = "com.adobe.AfterEffects.";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PluginMetadata {
+    pub package_name: String,
+    pub version: Version,
+    pub plugin_name: String,
+    pub identifier: String,
+    pub effect_name: String,
+    pub category: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MetadataError {
+    Io(String),
+    Toml(String),
+    Missing(&'static str),
+    BadVersion(String),
+    Mismatch {
+        what: &'static str,
+        expected: String,
+        found: String,
+    },
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::Io(e) => write!(f, "{e}"),
+            MetadataError::Toml(e) => write!(f, "Cargo.toml: {e}"),
+            MetadataError::Missing(key) => write!(f, "Cargo.toml: `{key}` is missing"),
+            MetadataError::BadVersion(v) => {
+                write!(f, "Cargo.toml: version `{v}` is not MAJOR.MINOR.PATCH")
+            }
+            MetadataError::Mismatch {
+                what,
+                expected,
+                found,
+            } => write!(f, "{what} is `{found}`, expected `{expected}`"),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+impl Version {
+    pub fn parse(s: &str) -> Result<Self, MetadataError> {
+        let bad = || MetadataError::BadVersion(s.to_string());
+        // 1.2.3-alpha のような後ろの部分は PiPL には入れない
+        let core = s.split(['-', '+']).next().unwrap_or(s);
+        let mut it = core.split('.').map(|p| p.parse::<u32>().map_err(|_| bad()));
+        let version = Version {
+            major: it.next().ok_or_else(bad)??,
+            minor: it.next().ok_or_else(bad)??,
+            patch: it.next().ok_or_else(bad)??,
+        };
+        if it.next().is_some() {
+            return Err(bad());
+        }
+        Ok(version)
+    }
+}
+
+impl PluginMetadata {
+    /// Cargo.toml の中身から読み込みます。値どうしの整合性も確認します。
+    pub fn parse(cargo_toml: &str) -> Result<Self, MetadataError> {
+        let table: toml::Table = cargo_toml
+            .parse()
+            .map_err(|e: toml::de::Error| MetadataError::Toml(e.to_string()))?;
+        let package = table
+            .get("package")
+            .and_then(|v| v.as_table())
+            .ok_or(MetadataError::Missing("package"))?;
+        let jk = package
+            .get("metadata")
+            .and_then(|v| v.get("jk_plugin"))
+            .and_then(|v| v.as_table())
+            .ok_or(MetadataError::Missing("package.metadata.jk_plugin"))?;
+
+        let string = |t: &toml::Table, key: &'static str, full: &'static str| {
+            t.get(key)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .ok_or(MetadataError::Missing(full))
+        };
+        let optional = |key: &str| jk.get(key).and_then(|v| v.as_str()).map(str::to_string);
+
+        let plugin_name = string(jk, "plugin_name", "package.metadata.jk_plugin.plugin_name")?;
+        let meta = PluginMetadata {
+            package_name: string(package, "name", "package.name")?,
+            version: Version::parse(&string(package, "version", "package.version")?)?,
+            identifier: string(jk, "identifier", "package.metadata.jk_plugin.identifier")?,
+            effect_name: optional("effect_name").unwrap_or_else(|| plugin_name.clone()),
+            category: optional("category").unwrap_or_else(|| DEFAULT_CATEGORY.to_string()),
+            plugin_name,
+        };
+        meta.validate()?;
+        Ok(meta)
+    }
+
+    /// identifier がパッケージ名から作った値になっているか、
+    /// plugin_name が effect_name からアポストロフィを除いたものになっているか確認します。
+    pub fn validate(&self) -> Result<(), MetadataError> {
+        check(
+            "package.metadata.jk_plugin.identifier",
+            &format!("{IDENTIFIER_PREFIX}{}", self.package_name),
+            &self.identifier,
+        )?;
+        if self.plugin_name.is_empty() {
+            return Err(MetadataError::Missing(
+                "package.metadata.jk_plugin.plugin_name",
+            ));
+        }
+        if self.effect_name.is_empty() {
+            return Err(MetadataError::Missing(
+                "package.metadata.jk_plugin.effect_name",
+            ));
+        }
+        check(
+            "package.metadata.jk_plugin.plugin_name",
+            &file_name(&self.effect_name),
+            &self.plugin_name,
+        )
+    }
+
+    /// Justfile の BuildName / PluginName が Cargo.toml と同じか確認します。
+    pub fn check_justfile(&self, justfile: &str) -> Result<(), MetadataError> {
+        if let Some(build_name) = just_variable(justfile, "BuildName") {
+            check("Justfile BuildName", &self.package_name, &build_name)?;
+        }
+        if let Some(plugin_name) = just_variable(justfile, "PluginName") {
+            check("Justfile PluginName", &self.plugin_name, &plugin_name)?;
+        }
+        Ok(())
+    }
+
+    /// `dir` の Cargo.toml を読み、Justfile があればそれとも照合します。
+    pub fn load(dir: &Path) -> Result<Self, MetadataError> {
+        let read = |name: &str| {
+            fs::read_to_string(dir.join(name))
+                .map_err(|e| MetadataError::Io(format!("{}: {e}", dir.join(name).display())))
+        };
+        let meta = Self::parse(&read("Cargo.toml")?)?;
+        if dir.join("Justfile").exists() {
+            meta.check_justfile(&read("Justfile")?)?;
+        }
+        Ok(meta)
+    }
+}
+
+// エフェクト名からファイル名に使う名前を作ります (JK Color Key F's => JK Color Key Fs)
+fn file_name(effect_name: &str) -> String {
+    effect_name.replace('\'', "")
+}
+
+fn check(what: &'static str, expected: &str, found: &str) -> Result<(), MetadataError> {
+    if expected != found {
+        return Err(MetadataError::Mismatch {
+            what,
+            expected: expected.to_string(),
+            found: found.to_string(),
+        });
+    }
+    Ok(())
+}
+
+// `Name := "value"` の value を返します。文字列リテラル以外 (式) のときは None
+fn just_variable(justfile: &str, name: &str) -> Option<String> {
+    justfile.lines().find_map(|line| {
+        let (lhs, rhs) = line.split_once(":=")?;
+        if lhs.trim() != name {
+            return None;
+        }
+        let rhs = rhs.trim();
+        let value = rhs.strip_prefix('"')?.strip_suffix('"')?;
+        Some(value.to_string())
+    })
+}
+
+/// build.rs から呼び出します。メタデータを確認し、AE の SDK がある環境では PiPL を生成します。
+pub fn build() {
+    let dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+    let dir = Path::new(&dir);
+    println!(
+        "cargo:rerun-if-changed={}",
+        dir.join("Cargo.toml").display()
+    );
+    println!("cargo:rerun-if-changed={}", dir.join("Justfile").display());
+
+    let meta = match PluginMetadata::load(dir) {
+        Ok(meta) => meta,
+        Err(e) => panic!("jk_plugin metadata error: {e}"),
+    };
+    // プラグインのコードでは env!("JK_EFFECT_NAME") で使う
+    println!("cargo:rustc-env=JK_EFFECT_NAME={}", meta.effect_name);
+
+    #[cfg(any(windows, target_os = "macos"))]
+    pipl_gen::plugin_build(&meta);
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let _ = meta;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CARGO_TOML: &str = r#"
+[package]
+name = "colorkey-fs"
+version = "0.2.3"
+edition = "2021"
+
+[package.metadata.jk_plugin]
+plugin_name = "JK Color Key Fs"
+identifier = "com.adobe.AfterEffects.colorkey-fs"
+effect_name = "JK Color Key F's"
+"#;
+
+    const JUSTFILE: &str = r#"BuildName        := "colorkey-fs"
+PluginName       := "JK Color Key Fs"
+BundleIdentifier := "com.adobe.AfterEffects.{{BuildName}}"
+BinaryName       := replace(lowercase(BuildName), "-", "_")
+"#;
+
+    #[test]
+    fn parses_metadata() {
+        let meta = PluginMetadata::parse(CARGO_TOML).unwrap();
+        assert_eq!(
+            meta,
+            PluginMetadata {
+                package_name: "colorkey-fs".into(),
+                version: Version {
+                    major: 0,
+                    minor: 2,
+                    patch: 3
+                },
+                plugin_name: "JK Color Key Fs".into(),
+                identifier: "com.adobe.AfterEffects.colorkey-fs".into(),
+                effect_name: "JK Color Key F's".into(),
+                category: DEFAULT_CATEGORY.into(),
+            }
+        );
+        meta.check_justfile(JUSTFILE).unwrap();
+    }
+
+    #[test]
+    fn effect_name_defaults_to_plugin_name() {
+        let toml = CARGO_TOML.replace("effect_name = \"JK Color Key F's\"\n", "");
+        let meta = PluginMetadata::parse(&toml).unwrap();
+        assert_eq!(meta.effect_name, "JK Color Key Fs");
+    }
+
+    #[test]
+    fn rejects_effect_name_drift() {
+        let toml = CARGO_TOML.replace("JK Color Key F's", "JK Colour Key F's");
+        assert_eq!(
+            PluginMetadata::parse(&toml),
+            Err(MetadataError::Mismatch {
+                what: "package.metadata.jk_plugin.plugin_name",
+                expected: "JK Colour Key Fs".into(),
+                found: "JK Color Key Fs".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_identifier_mismatch() {
+        let toml = CARGO_TOML.replace(
+            "identifier = \"com.adobe.AfterEffects.colorkey-fs\"",
+            "identifier = \"com.adobe.AfterEffects.colorchange-fs\"",
+        );
+        assert!(matches!(
+            PluginMetadata::parse(&toml),
+            Err(MetadataError::Mismatch {
+                what: "package.metadata.jk_plugin.identifier",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_justfile_mismatch() {
+        let meta = PluginMetadata::parse(CARGO_TOML).unwrap();
+        let justfile = JUSTFILE.replace("JK Color Key Fs", "JK Color Key F's");
+        assert_eq!(
+            meta.check_justfile(&justfile),
+            Err(MetadataError::Mismatch {
+                what: "Justfile PluginName",
+                expected: "JK Color Key Fs".into(),
+                found: "JK Color Key F's".into(),
+            })
+        );
+        let justfile = JUSTFILE.replace("\"colorkey-fs\"", "\"colorkey\"");
+        assert!(meta.check_justfile(&justfile).is_err());
+    }
+
+    #[test]
+    fn reports_missing_keys() {
+        let toml = CARGO_TOML.replace("plugin_name = \"JK Color Key Fs\"\n", "");
+        assert_eq!(
+            PluginMetadata::parse(&toml),
+            Err(MetadataError::Missing(
+                "package.metadata.jk_plugin.plugin_name"
+            ))
+        );
+        assert_eq!(
+            PluginMetadata::parse("[package]\nname = \"a\"\nversion = \"0.1.0\"\n"),
+            Err(MetadataError::Missing("package.metadata.jk_plugin"))
+        );
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(
+            Version::parse("1.20.3-beta.1"),
+            Ok(Version {
+                major: 1,
+                minor: 20,
+                patch: 3
+            })
+        );
+        assert!(Version::parse("1.2").is_err());
+        assert!(Version::parse("1.2.3.4").is_err());
+        assert!(Version::parse("1.x.3").is_err());
+    }
+
+    // リポジトリにある全プラグインのメタデータが食い違っていないこと
+    #[test]
+    fn workspace_plugins_are_consistent() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let mut found = 0;
+        for entry in fs::read_dir(&root).unwrap().flatten() {
+            let cargo_toml = entry.path().join("Cargo.toml");
+            let Ok(text) = fs::read_to_string(&cargo_toml) else {
+                continue;
+            };
+            if !text.contains("[package.metadata.jk_plugin]") {
+                continue;
+            }
+            PluginMetadata::load(&entry.path())
+                .unwrap_or_else(|e| panic!("{}: {e}", entry.path().display()));
+            found += 1;
+        }
+        assert!(found > 0, "no plugin crates found");
+    }
+}
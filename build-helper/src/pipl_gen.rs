@@ -0,0 +1,57 @@
+// PluginMetadata から PiPL を生成します。フラグは全プラグイン共通です。
+
+use pipl::*;
+
+use crate::PluginMetadata;
+
+const PF_PLUG_IN_VERSION: u16 = 13;
+const PF_PLUG_IN_SUBVERS: u16 = 28;
+
+// pipl のプロパティは &'static str を受け取るので、ビルドスクリプトの間だけ残れば良い文字列をリークさせる
+fn leak(s: &str) -> &'static str {
+    Box::leak(s.to_string().into_boxed_str())
+}
+
+#[rustfmt::skip]
+pub fn plugin_build(meta: &PluginMetadata) {
+    let name = leak(&meta.effect_name);
+    let category = leak(&meta.category);
+
+    pipl::plugin_build(vec![
+        Property::Kind(PIPLType::AEEffect),
+        Property::Name(name),
+        Property::Category(category),
+
+        #[cfg(target_os = "windows")]
+        Property::CodeWin64X86("EffectMain"),
+        #[cfg(target_os = "macos")]
+        Property::CodeMacIntel64("EffectMain"),
+        #[cfg(target_os = "macos")]
+        Property::CodeMacARM64("EffectMain"),
+
+        Property::AE_PiPL_Version { major: 2, minor: 0 },
+        Property::AE_Effect_Spec_Version { major: PF_PLUG_IN_VERSION, minor: PF_PLUG_IN_SUBVERS },
+        Property::AE_Effect_Version {
+            version: meta.version.major,
+            subversion: meta.version.minor,
+            bugversion: meta.version.patch,
+            stage: Stage::Develop,
+            build: 1,
+        },
+        Property::AE_Effect_Info_Flags(0),
+        Property::AE_Effect_Global_OutFlags(
+            OutFlags::PixIndependent |
+            OutFlags::NonParamVary |
+            OutFlags::DeepColorAware
+        ),
+        Property::AE_Effect_Global_OutFlags_2(
+            OutFlags2::FloatColorAware |
+            OutFlags2::SupportsSmartRender |
+            OutFlags2::SupportsThreadedRendering |
+            OutFlags2::SupportsGetFlattenedSequenceData
+        ),
+        Property::AE_Effect_Match_Name(name),
+        Property::AE_Reserved_Info(8),
+        Property::AE_Effect_Support_URL("https://www.adobe.com"),
+    ]);
+}
@@ -1,88 +1,163 @@
+// After Effects SDK が無い環境(Linux)ではプラグイン本体はビルドしない
+#![cfg(any(windows, target_os = "macos"))]
+
 use after_effects::{self as ae};
 
-use libs::utils::{conv_16_to_8, conv_32_to_8, conv_8_to_16, conv_8_to_32, round_byte_fp_long};
+use libs::host::FsEffect;
+use libs::kernels::colorchange::{ColorChange, MatchMode, PairOverride, Transfer};
+use libs::list;
+use libs::palette::{Palette, PaletteEdit, PaletteEntry, PaletteError, PaletteFormat};
+use libs::slider::{self, percent_to_alpha8};
+use libs::ColorMetric;
+use libs::Rgba8;
+
+libs::param_group! {
+    /// 変更前と変更後の色の組
+    struct ColorPair / PairParam {
+        /// 個別のオンオフ
+        target: checkbox = Target("Target"),
+        /// 色の選択
+        src: color = SrcColor("Source Color"),
+        /// 変更後の色
+        dst: color = DstColor("Destination Color"),
+    }
+}
 
-#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
-enum Params {
-    Level,
-    Mode1,
-    Target0,
-    SrcColor0,
-    DstColor0,
-    Target1,
-    SrcColor1,
-    DstColor1,
-    Target2,
-    SrcColor2,
-    DstColor2,
-    Target3,
-    SrcColor3,
-    DstColor3,
-    Target4,
-    SrcColor4,
-    DstColor4,
-    Target5,
-    SrcColor5,
-    DstColor5,
-    Target6,
-    SrcColor6,
-    DstColor6,
-    Target7,
-    SrcColor7,
-    DstColor7,
+libs::param_group! {
+    /// 組ごとに全体の設定を上書きする値
+    struct PairTolerance / ToleranceParam {
+        /// オンのときだけ下の許容誤差とぼかし幅を使う
+        custom: checkbox = Custom("Custom Tolerance"),
+        tolerance: percent = Tolerance("Tolerance"),
+        softness: percent = PairSoftness("Pair Softness"),
+        /// 色の距離の測り方 (先頭は全体の設定)
+        metric: metric = PairMetric("Pair Metric"),
+    }
+}
+
+// 上書きしない初期値 (古いプロジェクトもこの値で読み込まれる)
+const TOLERANCE_DEFAULT: PairTolerance = PairTolerance {
+    custom: false,
+    tolerance: 0.0,
+    softness: 0.0,
+    metric: None,
+};
+
+libs::param_group! {
+    /// 組ごとのアルファの比較と置き換え
+    struct PairAlpha / AlphaParam {
+        /// オンのとき、色に加えてアルファも Source Alpha と比べる
+        match_alpha: checkbox = MatchAlpha("Match Alpha"),
+        src_alpha: percent = SrcAlpha("Source Alpha"),
+        /// オンのとき、置き換えた色のアルファを Destination Alpha にする
+        set_alpha: checkbox = SetAlpha("Set Alpha"),
+        dst_alpha: percent = DstAlpha("Destination Alpha"),
+    }
 }
 
-impl Params {
-    pub fn from_index(index: usize) -> Option<Self> {
-        use Params::*;
-        match index {
-            0 => Some(Level),
-            1 => Some(Mode1),
-            2 => Some(Target0),
-            3 => Some(SrcColor0),
-            4 => Some(DstColor0),
-            5 => Some(Target1),
-            6 => Some(SrcColor1),
-            7 => Some(DstColor1),
-            8 => Some(Target2),
-            9 => Some(SrcColor2),
-            10 => Some(DstColor2),
-            11 => Some(Target3),
-            12 => Some(SrcColor3),
-            13 => Some(DstColor3),
-            14 => Some(Target4),
-            15 => Some(SrcColor4),
-            16 => Some(DstColor4),
-            17 => Some(Target5),
-            18 => Some(SrcColor5),
-            19 => Some(DstColor5),
-            20 => Some(Target6),
-            21 => Some(SrcColor6),
-            22 => Some(DstColor6),
-            23 => Some(Target7),
-            24 => Some(SrcColor7),
-            25 => Some(DstColor7),
-            _ => None,
+const ALPHA_DEFAULT: PairAlpha = PairAlpha {
+    match_alpha: false,
+    src_alpha: 100.0,
+    set_alpha: false,
+    dst_alpha: 100.0,
+};
+
+impl PairTolerance {
+    fn to_override(self, alpha: PairAlpha) -> PairOverride {
+        let custom = |v: f64| self.custom.then_some(slider::percent(v));
+        PairOverride {
+            tolerance: custom(self.tolerance),
+            softness: custom(self.softness),
+            metric: self.metric,
+            src_alpha: alpha
+                .match_alpha
+                .then(|| percent_to_alpha8(alpha.src_alpha)),
+            dst_alpha: alpha.set_alpha.then(|| percent_to_alpha8(alpha.dst_alpha)),
         }
     }
 }
 
+const fn pair(src: (u8, u8, u8), dst: (u8, u8, u8)) -> ColorPair {
+    ColorPair {
+        target: false,
+        src: ae::Pixel8 {
+            red: src.0,
+            green: src.1,
+            blue: src.2,
+            alpha: 255,
+        },
+        dst: ae::Pixel8 {
+            red: dst.0,
+            green: dst.1,
+            blue: dst.2,
+            alpha: 255,
+        },
+    }
+}
+
+// 組の数と初期値。ここを増やせばパラメータも増える
+const PAIR_DEFAULTS: [ColorPair; 8] = [
+    pair((255, 255, 255), (128, 128, 128)),
+    pair((255, 0, 0), (128, 0, 0)),
+    pair((0, 255, 0), (0, 128, 0)),
+    pair((0, 0, 255), (0, 0, 128)),
+    // 黄色
+    pair((255, 255, 0), (128, 128, 0)),
+    // 水色
+    pair((0, 255, 255), (0, 128, 128)),
+    // マゼンタ
+    pair((255, 0, 255), (128, 0, 128)),
+    // 黒→白
+    pair((0, 0, 0), (255, 255, 255)),
+];
+
+const WHITE: ae::Pixel8 = ae::Pixel8 {
+    red: 255,
+    green: 255,
+    blue: 255,
+    alpha: 255,
+};
+
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+enum Params {
+    Level,
+    Mode1,
+    Pair(usize, PairParam),
+    Metric,
+    Softness,
+    // ここから下は上限の無いパレット (シーケンスデータに保存) の編集用
+    PaletteIndex,
+    PaletteSrc,
+    PaletteDst,
+    PaletteAdd,
+    PaletteReplace,
+    PaletteRemove,
+    PaletteUp,
+    PaletteDown,
+    PaletteImport,
+    PaletteExport,
+    MatchMode,
+    // 古いプロジェクトの並びを変えないよう、後から足したものは末尾に置く
+    PairTolerance(usize, ToleranceParam),
+    PreviewMatches,
+    Transfer,
+    Unpremultiply,
+    PairAlpha(usize, AlphaParam),
+}
+
 #[derive(Default)]
 struct Plugin {}
 
-ae::define_effect!(Plugin, (), Params);
+libs::fs_effect!(Plugin, Params);
 
-impl AdobePluginGlobal for Plugin {
-    fn can_load(_host_name: &str, _host_version: &str) -> bool {
-        true
-    }
+impl FsEffect for Plugin {
+    type Params = Params;
+    type Kernel = ColorChange;
+    type Sequence = Palette;
+
+    const ABOUT: &'static str = "fs-rs colorchange";
 
-    fn params_setup(
-        &self,
-        params: &mut ae::Parameters<Params>,
-        in_data: InData,
-        _: OutData,
-    ) -> Result<(), Error> {
+    fn params_setup(&self, params: &mut ae::Parameters<Params>) -> Result<(), Error> {
         params.add(
             Params::Level,
             "level",
@@ -107,583 +182,270 @@ impl AdobePluginGlobal for Plugin {
             }),
         )?;
 
-        // 1つ目
-        // 個別のオンオフ
-        params.add(
-            Params::Target0,
-            "Target0",
-            ae::CheckBoxDef::setup(|f| {
-                f.set_default(false);
-                f.set_value(f.default());
-            }),
-        )?;
+        for (i, defaults) in PAIR_DEFAULTS.iter().enumerate() {
+            ColorPair::add(params, i, defaults, Params::Pair)?;
+        }
 
-        // 色の選択
+        // 色の距離の測り方
         params.add(
-            Params::SrcColor0,
-            "Source Color0",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 255,
-                    green: 255,
-                    blue: 255,
-                    alpha: 255,
-                });
+            Params::Metric,
+            "Color Metric",
+            ae::PopupDef::setup(|f| {
+                f.set_options(&ColorMetric::LABELS);
+                f.set_default(1);
                 f.set_value(f.default());
             }),
         )?;
 
-        // 変更後の色
+        // 許容誤差の外側で少しずつ置き換える幅
         params.add(
-            Params::DstColor0,
-            "Destination Color0",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 128,
-                    green: 128,
-                    blue: 128,
-                    alpha: 255,
-                });
+            Params::Softness,
+            "Softness",
+            ae::FloatSliderDef::setup(|f| {
+                f.set_default(0.0);
+                f.set_precision(1);
+                f.set_valid_min(0.0);
+                f.set_valid_max(100.0);
+                f.set_slider_min(0.0);
+                f.set_slider_max(100.0);
                 f.set_value(f.default());
             }),
         )?;
 
-        // 2つ目
-        // 個別のオンオフ
+        // パレットの何番目を編集するか (1 始まり)
         params.add(
-            Params::Target1,
-            "Target1",
-            ae::CheckBoxDef::setup(|f| {
-                f.set_default(false);
+            Params::PaletteIndex,
+            "Palette Entry",
+            ae::FloatSliderDef::setup(|f| {
+                f.set_default(1.0);
+                f.set_precision(0);
+                f.set_valid_min(1.0);
+                f.set_valid_max(list::MAX_INDEX);
+                f.set_slider_min(1.0);
+                f.set_slider_max(100.0);
                 f.set_value(f.default());
             }),
         )?;
-
-        // 色の選択
         params.add(
-            Params::SrcColor1,
-            "Source Color1",
+            Params::PaletteSrc,
+            "Palette Source",
             ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 255,
-                    green: 0,
-                    blue: 0,
-                    alpha: 255,
-                });
+                f.set_default(WHITE);
                 f.set_value(f.default());
             }),
         )?;
-
-        // 変更後の色
         params.add(
-            Params::DstColor1,
-            "Destination Color1",
+            Params::PaletteDst,
+            "Palette Destination",
             ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 128,
-                    green: 0,
-                    blue: 0,
-                    alpha: 255,
-                });
+                f.set_default(WHITE);
                 f.set_value(f.default());
             }),
         )?;
-
-        // 3つ目
-        // 個別のオンオフ
+        add_button(params, Params::PaletteAdd, "Add to Palette", "Add")?;
+        add_button(params, Params::PaletteReplace, "Replace Entry", "Replace")?;
+        add_button(params, Params::PaletteRemove, "Remove Entry", "Remove")?;
+        add_button(params, Params::PaletteUp, "Move Entry Up", "Up")?;
+        add_button(params, Params::PaletteDown, "Move Entry Down", "Down")?;
+        add_button(params, Params::PaletteImport, "Import Palette", "Import...")?;
+        add_button(params, Params::PaletteExport, "Export Palette", "Export...")?;
+
+        // 複数のペアが一致したときの扱い
         params.add(
-            Params::Target2,
-            "Target2",
-            ae::CheckBoxDef::setup(|f| {
-                f.set_default(false);
-                f.set_value(f.default());
-            }),
-        )?;
-
-        // 色の選択
-        params.add(
-            Params::SrcColor2,
-            "Source Color2",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 0,
-                    green: 255,
-                    blue: 0,
-                    alpha: 255,
-                });
+            Params::MatchMode,
+            "Match Mode",
+            ae::PopupDef::setup(|f| {
+                f.set_options(&MatchMode::LABELS);
+                f.set_default(1);
                 f.set_value(f.default());
             }),
         )?;
 
-        // 変更後の色
-        params.add(
-            Params::DstColor2,
-            "Destination Color2",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 0,
-                    green: 128,
-                    blue: 0,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
+        for i in 0..PAIR_DEFAULTS.len() {
+            PairTolerance::add(params, i, &TOLERANCE_DEFAULT, Params::PairTolerance)?;
+        }
 
-        // 4つ目
-        // 個別のオンオフ
+        // 置き換える代わりに、どのペアがどのピクセルに一致したかを色で表示する
         params.add(
-            Params::Target3,
-            "Target3",
+            Params::PreviewMatches,
+            "Preview Matches",
             ae::CheckBoxDef::setup(|f| {
                 f.set_default(false);
                 f.set_value(f.default());
             }),
         )?;
 
-        // 色の選択
-        params.add(
-            Params::SrcColor3,
-            "Source Color3",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 0,
-                    green: 0,
-                    blue: 255,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
-        // 変更後の色
+        // 変更後の色で塗りつぶすか、元の陰影を残して置き換えるか
         params.add(
-            Params::DstColor3,
-            "Destination Color3",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 0,
-                    green: 0,
-                    blue: 128,
-                    alpha: 255,
-                });
+            Params::Transfer,
+            "Transfer",
+            ae::PopupDef::setup(|f| {
+                f.set_options(&Transfer::LABELS);
+                f.set_default(1);
                 f.set_value(f.default());
             }),
         )?;
 
-        // 5つ目（黄色）
+        // 半透明の縁も不透明な部分と同じ色として比べる
         params.add(
-            Params::Target4,
-            "Target4",
+            Params::Unpremultiply,
+            "Unpremultiply",
             ae::CheckBoxDef::setup(|f| {
                 f.set_default(false);
                 f.set_value(f.default());
             }),
         )?;
-        params.add(
-            Params::SrcColor4,
-            "Source Color4",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 255,
-                    green: 255,
-                    blue: 0,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
-        params.add(
-            Params::DstColor4,
-            "Destination Color4",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 128,
-                    green: 128,
-                    blue: 0,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
 
-        // 6つ目（水色）
-        params.add(
-            Params::Target5,
-            "Target5",
-            ae::CheckBoxDef::setup(|f| {
-                f.set_default(false);
-                f.set_value(f.default());
-            }),
-        )?;
-        params.add(
-            Params::SrcColor5,
-            "Source Color5",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 0,
-                    green: 255,
-                    blue: 255,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
-        params.add(
-            Params::DstColor5,
-            "Destination Color5",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 0,
-                    green: 128,
-                    blue: 128,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
-
-        // 7つ目（マゼンタ）
-        params.add(
-            Params::Target6,
-            "Target6",
-            ae::CheckBoxDef::setup(|f| {
-                f.set_default(false);
-                f.set_value(f.default());
-            }),
-        )?;
-        params.add(
-            Params::SrcColor6,
-            "Source Color6",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 255,
-                    green: 0,
-                    blue: 255,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
-        params.add(
-            Params::DstColor6,
-            "Destination Color6",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 128,
-                    green: 0,
-                    blue: 128,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
-
-        // 8つ目（黒→白）
-        params.add(
-            Params::Target7,
-            "Target7",
-            ae::CheckBoxDef::setup(|f| {
-                f.set_default(false);
-                f.set_value(f.default());
-            }),
-        )?;
-        params.add(
-            Params::SrcColor7,
-            "Source Color7",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 0,
-                    green: 0,
-                    blue: 0,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
-        params.add(
-            Params::DstColor7,
-            "Destination Color7",
-            ae::ColorDef::setup(|f| {
-                f.set_default(Pixel8 {
-                    red: 255,
-                    green: 255,
-                    blue: 255,
-                    alpha: 255,
-                });
-                f.set_value(f.default());
-            }),
-        )?;
+        for i in 0..PAIR_DEFAULTS.len() {
+            PairAlpha::add(params, i, &ALPHA_DEFAULT, Params::PairAlpha)?;
+        }
 
         Ok(())
     }
 
-    fn handle_command(
-        &mut self,
-        cmd: ae::Command,
-        in_data: InData,
-        mut out_data: OutData,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        match cmd {
-            ae::Command::About => {
-                self.about(&mut out_data);
-            }
-            ae::Command::GlobalSetup => {
-                self.global_setup(&in_data)?;
-            }
-            ae::Command::Render {
-                in_layer,
-                out_layer,
-            } => {
-                self.legacy_render(&in_data, in_layer, out_layer, params)?;
-            }
-            ae::Command::SmartPreRender { extra } => {
-                self.smart_pre_render(&in_data, extra, params)?;
-            }
-            ae::Command::SmartRender { extra } => {
-                self.smart_render(&in_data, extra, params)?;
-            }
-            _ => {}
-        }
-        Ok(())
+    fn kernel(params: &ae::Parameters<Params>, palette: &Palette) -> Result<ColorChange, Error> {
+        let level = params.get(Params::Level)?.as_float_slider()?.value();
+        // ビット深度によらない正規化した許容誤差
+        let tolerance = slider::percent(level);
+        let metric = ColorMetric::from_popup(params.get(Params::Metric)?.as_popup()?.value());
+        let softness = params.get(Params::Softness)?.as_float_slider()?.value();
+        let mode = MatchMode::from_popup(params.get(Params::MatchMode)?.as_popup()?.value());
+        let preview = params.get(Params::PreviewMatches)?.as_checkbox()?.value();
+        let transfer = Transfer::from_popup(params.get(Params::Transfer)?.as_popup()?.value());
+        let unpremultiply = params.get(Params::Unpremultiply)?.as_checkbox()?.value();
+
+        // 固定の8組の後にパレットを並べる
+        let (mut pairs, mut overrides, mut slots) = Plugin::collect_enabled_color_pairs(params)?;
+        pairs.extend(palette.enabled_pairs());
+        overrides.extend(palette.enabled_overrides());
+        // パレットの組は固定の8組に続く番号にする
+        slots.extend(palette.enabled_indices().map(|i| PAIR_DEFAULTS.len() + i));
+
+        Ok(ColorChange::new(tolerance, metric, pairs)
+            .with_softness(slider::percent(softness))
+            .with_mode(mode)
+            .with_transfer(transfer)
+            .with_pair_overrides(overrides)
+            .with_preview(preview)
+            .with_preview_slots(slots)
+            .with_unpremultiply(unpremultiply))
     }
-}
 
-impl Plugin {
-    fn about(&mut self, out_data: &mut OutData) {
-        out_data.set_return_msg("fs-rs colorchange");
+    fn enabled(params: &ae::Parameters<Params>) -> Result<bool, Error> {
+        Ok(params.get(Params::Mode1)?.as_checkbox()?.value())
     }
 
-    fn global_setup(&mut self, in_data: &InData) -> Result<(), ae::Error> {
-        win_dbg_logger::DEBUGGER_LOGGER.set_force_log_without_debugger(true);
-        log::info!("GlobalSetup");
-        // For Premiere - declare supported pixel formats
-        if in_data.is_premiere() {
-            let suite = ae::pf::suites::PixelFormat::new()?;
-
-            // Add the pixel formats we support in order of preference.
-            suite.clear_supported_pixel_formats(in_data.effect_ref())?;
-            let formats = [
-                ae::pr::PixelFormat::Bgra4444_8u,
-                ae::pr::PixelFormat::Bgra4444_16u,
-                ae::pr::PixelFormat::Bgra4444_32f,
-            ];
-            for x in formats {
-                suite.add_supported_pixel_format(in_data.effect_ref(), x)?;
+    fn user_changed_param(
+        params: &ae::Parameters<Params>,
+        palette: &mut Palette,
+        param: Params,
+    ) -> Result<bool, Error> {
+        match param {
+            Params::PaletteImport => return Ok(Plugin::import_palette(palette)),
+            Params::PaletteExport => {
+                Plugin::export_palette(palette);
+                return Ok(false);
             }
+            _ => {}
         }
-        Ok(())
-    }
 
-    fn legacy_render(
-        &mut self,
-        in_data: &InData,
-        in_layer: ae::Layer,
-        out_layer: ae::Layer,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        if !in_data.is_premiere() {
-            // We don't support non-SmartFX unless it's Premiere
-            return Err(Error::BadCallbackParameter);
+        let index = params.get(Params::PaletteIndex)?.as_float_slider()?.value() as usize;
+        let index = index.saturating_sub(1);
+        let mut entry = PaletteEntry::new(
+            params.get(Params::PaletteSrc)?.as_color()?.value().into(),
+            params.get(Params::PaletteDst)?.as_color()?.value().into(),
+        );
+        // 色を置き換えても、読み込んだ組ごとの設定は残す
+        if let (Params::PaletteReplace, Some(old)) = (param, palette.entries.get(index)) {
+            entry.overrides = old.overrides;
         }
-
-        self.do_render(in_data, in_layer, out_layer, params)?;
-
-        Ok(())
+        let edit = match param {
+            Params::PaletteAdd => PaletteEdit::Add(entry),
+            Params::PaletteReplace => PaletteEdit::Replace(index, entry),
+            Params::PaletteRemove => PaletteEdit::Remove(index),
+            Params::PaletteUp => PaletteEdit::MoveUp(index),
+            Params::PaletteDown => PaletteEdit::MoveDown(index),
+            _ => return Ok(false),
+        };
+        Ok(palette.apply(edit).changed())
     }
+}
 
-    fn smart_pre_render(
-        &mut self,
-        in_data: &InData,
-        mut extra: ae::PreRenderExtra,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        let req = extra.output_request();
-
-        if let Ok(in_result) = extra.callbacks().checkout_layer(
-            0,
-            0,
-            &req,
-            in_data.current_time(),
-            in_data.time_step(),
-            in_data.time_scale(),
-        ) {
-            let _ = extra.union_result_rect(in_result.result_rect.into());
-            let _ = extra.union_max_result_rect(in_result.max_result_rect.into());
+impl Plugin {
+    // ファイルを選んでパレットを置き換える。置き換えたら true
+    fn import_palette(palette: &mut Palette) -> bool {
+        let Some(path) = palette_dialog().pick_file() else {
+            return false;
+        };
+        match Palette::load(&path) {
+            Ok(loaded) => {
+                *palette = loaded;
+                true
+            }
+            Err(e) => {
+                show_error(&e);
+                false
+            }
         }
-        Ok(())
     }
 
-    fn smart_render(
-        &mut self,
-        in_data: &InData,
-        extra: ae::SmartRenderExtra,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        let cb = extra.callbacks();
-        let Some(input_world) = cb.checkout_layer_pixels(0)? else {
-            return Ok(());
+    fn export_palette(palette: &Palette) {
+        let Some(path) = palette_dialog().set_file_name("palette.ase").save_file() else {
+            return;
         };
-
-        if let Ok(Some(output_world)) = cb.checkout_output() {
-            self.do_render(in_data, input_world, output_world, params)?;
+        if let Err(e) = palette.save(&path) {
+            show_error(&e);
         }
-
-        cb.checkin_layer_pixels(0)?;
-        Ok(())
     }
-    // ペア作り
-    fn collect_enabled_color_pairs(
-        params: &ae::Parameters<Params>,
-    ) -> Result<Vec<(Pixel8, Pixel8)>, Error> {
-        let mut pairs = Vec::new();
-        //ここは、それぞれTarget0, SrcColor0, DstColor0から3つ飛ばしでパラメータを取得できるため、+1,+2をする必要が無い。
-        for i in 0..8 {
-            let target = match Params::from_index(Params::Target0 as usize + i * 3) {
-                Some(p) => p,
-                None => continue,
-            };
-            let src = match Params::from_index(Params::SrcColor0 as usize + i * 3) {
-                Some(p) => p,
-                None => continue,
-            };
-            let dst = match Params::from_index(Params::DstColor0 as usize + i * 3) {
-                Some(p) => p,
-                None => continue,
-            };
-
-            if params.get(target)?.as_checkbox()?.value() {
-                let src_color = params.get(src)?.as_color()?.value();
-                let dst_color = params.get(dst)?.as_color()?.value();
-                pairs.push((src_color, dst_color));
+
+    // ペア作り。ペアと同じ順番で、組ごとの上書きと組の番号 (0 始まり。確認表示の色に使う) も返す
+    fn collect_enabled_color_pairs(params: &ae::Parameters<Params>) -> Result<EnabledPairs, Error> {
+        let pairs = ColorPair::get_all(params, PAIR_DEFAULTS.len(), Params::Pair)?;
+        let tolerances =
+            PairTolerance::get_all(params, PAIR_DEFAULTS.len(), Params::PairTolerance)?;
+        let alphas = PairAlpha::get_all(params, PAIR_DEFAULTS.len(), Params::PairAlpha)?;
+        let (mut colors, mut overrides, mut slots): EnabledPairs = Default::default();
+        for (slot, ((p, t), a)) in pairs.iter().zip(tolerances).zip(alphas).enumerate() {
+            if p.target {
+                colors.push((p.src.into(), p.dst.into()));
+                overrides.push(t.to_override(a));
+                slots.push(slot);
             }
         }
-
-        Ok(pairs)
+        Ok((colors, overrides, slots))
     }
+}
 
-    fn do_render(
-        &self,
-        in_data: &ae::InData,
-        in_layer: ae::Layer,
-        mut out_layer: ae::Layer,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), Error> {
-        let level = params.get(Params::Level)?.as_float_slider()?.value();
-        let level = (MAX_CHANNEL8 as f64 * level / 100.0) as u8; // Convert to 0-255 range
-                                                                 // 0.0 - 100.0 => 0 - 255
-                                                                 // let level = (MAX_CHANNEL8 as f64 * level / 100.0) as u8; // Convert to 0-255 range
-                                                                 // いどう
-                                                                 // let src_color = params.get(Params::SrcColor0)?.as_color()?.value();
-                                                                 // let dst_color = params.get(Params::DstColor0)?.as_color()?.value();
-
-        let color_pairs = Plugin::collect_enabled_color_pairs(params)?;
-
-        let progress_final = out_layer.height() as _;
-        ae::pf::suites::WorldTransform::new()?.copy_hq(
-            in_data.effect_ref(),
-            &in_layer,
-            &mut out_layer,
-            None,
-            None,
-        )?;
-        in_layer.iterate_with(
-            &mut out_layer,
-            0,
-            progress_final,
-            None,
-            |_x: i32,
-             _y: i32,
-             pixel: ae::GenericPixel,
-             out_pixel: ae::GenericPixelMut|
-             -> Result<(), Error> {
-                match (pixel, out_pixel) {
-                    (ae::GenericPixel::Pixel8(pixel), ae::GenericPixelMut::Pixel8(out_pixel)) => {
-                        for (src_color, dst_color) in &color_pairs {
-                            if level == 0 {
-                                if pixel.red == src_color.red
-                                    && pixel.green == src_color.green
-                                    && pixel.blue == src_color.blue
-                                {
-                                    out_pixel.red = dst_color.red;
-                                    out_pixel.green = dst_color.green;
-                                    out_pixel.blue = dst_color.blue;
-                                }
-                            } else if pixel.red.abs_diff(src_color.red) <= level
-                                && pixel.green.abs_diff(src_color.green) <= level
-                                && pixel.blue.abs_diff(src_color.blue) <= level
-                            {
-                                out_pixel.red = dst_color.red;
-                                out_pixel.green = dst_color.green;
-                                out_pixel.blue = dst_color.blue;
-                            }
-
-                            // if matched {
-                            //     out_pixel.red = dst_color.red;
-                            //     out_pixel.green = dst_color.green;
-                            //     out_pixel.blue = dst_color.blue;
-                            //     break; // 最初にマッチした色だけ置き換えたい場合
-                            // }
-                        }
-                    }
-
-                    (ae::GenericPixel::Pixel16(pixel), ae::GenericPixelMut::Pixel16(out_pixel)) => {
-                        let p = &conv_16_to_8(pixel);
-                        for (src_color, dst_color) in &color_pairs {
-                            let d = conv_8_to_16(&dst_color);
-
-                            if level == 0 {
-                                if p.red == src_color.red
-                                    && p.green == src_color.green
-                                    && p.blue == src_color.blue
-                                {
-                                    out_pixel.red = d.red;
-                                    out_pixel.green = d.green;
-                                    out_pixel.blue = d.blue;
-                                }
-                            } else if p.red.abs_diff(src_color.red) <= level
-                                && p.green.abs_diff(src_color.green) <= level
-                                && p.blue.abs_diff(src_color.blue) <= level
-                            {
-                                out_pixel.red = d.red;
-                                out_pixel.green = d.green;
-                                out_pixel.blue = d.blue;
-                            }
-                        }
-                    }
-                    (
-                        ae::GenericPixel::PixelF32(pixel),
-                        ae::GenericPixelMut::PixelF32(out_pixel),
-                    ) => {
-                        let p = &conv_32_to_8(pixel);
-                        for (src_color, dst_color) in &color_pairs {
-                            let d = conv_8_to_32(&dst_color);
-
-                            if level == 0 {
-                                if p.red == src_color.red
-                                    && p.green == src_color.green
-                                    && p.blue == src_color.blue
-                                {
-                                    out_pixel.red = d.red;
-                                    out_pixel.green = d.green;
-                                    out_pixel.blue = d.blue;
-                                }
-                            } else if p.red.abs_diff(src_color.red) <= level
-                                && p.green.abs_diff(src_color.green) <= level
-                                && p.blue.abs_diff(src_color.blue) <= level
-                            {
-                                out_pixel.red = d.red;
-                                out_pixel.green = d.green;
-                                out_pixel.blue = d.blue;
-                            }
-                        }
-                    }
-                    _ => return Err(Error::BadCallbackParameter),
-                }
-
-                Ok(())
-            },
-        )?;
-        Ok(())
-    }
+// 有効な組の (変更前の色, 変更後の色)、組ごとの上書き、組の番号
+type EnabledPairs = (Vec<(Rgba8, Rgba8)>, Vec<PairOverride>, Vec<usize>);
+
+// 押されたときに user_changed_param が呼ばれるボタンを追加します。
+fn add_button(
+    params: &mut ae::Parameters<Params>,
+    id: Params,
+    name: &str,
+    label: &str,
+) -> Result<(), Error> {
+    params.add_with_flags(
+        id,
+        name,
+        ae::ButtonDef::setup(|f| {
+            f.set_label(label);
+        }),
+        ae::ParamFlag::SUPERVISE,
+        ae::ParamUIFlags::empty(),
+    )
+}
+
+fn palette_dialog() -> rfd::FileDialog {
+    let extensions = PaletteFormat::ALL.map(PaletteFormat::extension);
+    rfd::FileDialog::new().add_filter("Palette", &extensions[..])
+}
+
+fn show_error(e: &PaletteError) {
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Error)
+        .set_title(env!("JK_EFFECT_NAME"))
+        .set_description(e.to_string())
+        .show();
 }
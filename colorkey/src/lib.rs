@@ -1,30 +1,81 @@
+// After Effects SDK が無い環境(Linux)ではプラグイン本体はビルドしない
+#![cfg(any(windows, target_os = "macos"))]
+
 use after_effects::{self as ae};
 
-use libs::utils::{conv_16_to_8, conv_32_to_8, conv_8_to_16, conv_8_to_32, round_byte_fp_long};
+use libs::host::FsEffect;
+use libs::kernels::colorkey::{ColorKey, KeyColor, Keyer, ViewMode};
+use libs::key_list::{KeyEdit, KeyList};
+use libs::list;
+use libs::slider;
+use libs::{ColorMetric, Rgba8};
+
+libs::param_group! {
+    /// Key Color に加えて抜く色
+    struct ExtraKey / KeyParam {
+        /// 個別のオンオフ
+        enabled: checkbox = Enabled("Use Key"),
+        color: color = Color("Key Color"),
+        /// オンのときだけ下の許容誤差を使う (キーヤーでは Inner Tolerance の代わり)
+        custom: checkbox = Custom("Custom Tolerance"),
+        tolerance: percent = Tolerance("Key Tolerance"),
+    }
+}
+
+// 追加の色は "Key Color2" から始まる。これより多い色は Key List (シーケンスデータ) に追加する
+const EXTRA_KEYS: std::ops::RangeInclusive<usize> = 2..=4;
+
+const EXTRA_KEY_DEFAULT: ExtraKey = ExtraKey {
+    enabled: false,
+    color: ae::Pixel8 {
+        red: 0xFF,
+        green: 0xFF,
+        blue: 0xFF,
+        alpha: 0xFF,
+    },
+    custom: false,
+    tolerance: 0.0,
+};
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
 enum Params {
     Threshold,
     KeyColor,
     BackColor,
+    Metric,
+    Softness,
+    SoftKey,
+    InnerTolerance,
+    OuterTolerance,
+    Despill,
+    MatteChoke,
+    MatteSoftness,
+    ExtraKey(usize, KeyParam),
+    View,
+    Unpremultiply,
+    // ここから下は上限の無いキー色の一覧 (シーケンスデータに保存) の編集用
+    KeyListIndex,
+    KeyListColor,
+    KeyListCustom,
+    KeyListTolerance,
+    KeyListAdd,
+    KeyListReplace,
+    KeyListRemove,
 }
 
 #[derive(Default)]
 struct Plugin {}
 
-ae::define_effect!(Plugin, (), Params);
+libs::fs_effect!(Plugin, Params);
 
-impl AdobePluginGlobal for Plugin {
-    fn can_load(_host_name: &str, _host_version: &str) -> bool {
-        true
-    }
+impl FsEffect for Plugin {
+    type Params = Params;
+    type Kernel = ColorKey;
+    type Sequence = KeyList;
+
+    const ABOUT: &'static str = "fs-rs colorkey";
 
-    fn params_setup(
-        &self,
-        params: &mut ae::Parameters<Params>,
-        in_data: InData,
-        _: OutData,
-    ) -> Result<(), Error> {
+    fn params_setup(&self, params: &mut ae::Parameters<Params>) -> Result<(), Error> {
         params.add(
             Params::Threshold,
             "threshold",
@@ -67,232 +118,235 @@ impl AdobePluginGlobal for Plugin {
             }),
         )?;
 
-        Ok(())
-    }
-
-    fn handle_command(
-        &mut self,
-        cmd: ae::Command,
-        in_data: InData,
-        mut out_data: OutData,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        match cmd {
-            ae::Command::About => {
-                self.about(&mut out_data);
-            }
-            ae::Command::GlobalSetup => {
-                self.global_setup(&in_data)?;
-            }
-            ae::Command::Render {
-                in_layer,
-                out_layer,
-            } => {
-                self.legacy_render(&in_data, in_layer, out_layer, params)?;
-            }
-            ae::Command::SmartPreRender { extra } => {
-                self.smart_pre_render(&in_data, extra, params)?;
-            }
-            ae::Command::SmartRender { extra } => {
-                self.smart_render(&in_data, extra, params)?;
-            }
-            _ => {}
-        }
-        Ok(())
-    }
-}
+        // 色の距離の測り方
+        params.add(
+            Params::Metric,
+            "Color Metric",
+            ae::PopupDef::setup(|f| {
+                f.set_options(&ColorMetric::LABELS);
+                f.set_default(1);
+                f.set_value(f.default());
+            }),
+        )?;
 
-impl Plugin {
-    fn about(&mut self, out_data: &mut OutData) {
-        out_data.set_return_msg("fs-rs colorkey");
-    }
+        // 許容誤差の外側で少しずつ透明にする幅
+        params.add(
+            Params::Softness,
+            "Softness",
+            ae::FloatSliderDef::setup(|f| {
+                f.set_default(0.0);
+                f.set_precision(1);
+                f.set_valid_min(0.0);
+                f.set_valid_max(100.0);
+                f.set_slider_min(0.0);
+                f.set_slider_max(100.0);
+                f.set_value(f.default());
+            }),
+        )?;
 
-    fn global_setup(&mut self, in_data: &InData) -> Result<(), ae::Error> {
-        win_dbg_logger::DEBUGGER_LOGGER.set_force_log_without_debugger(true);
-        log::info!("GlobalSetup");
-        // For Premiere - declare supported pixel formats
-        if in_data.is_premiere() {
-            let suite = ae::pf::suites::PixelFormat::new()?;
+        // キーヤー。オフのときは今までと同じ 2値 (+ Softness) のキー
+        params.add(
+            Params::SoftKey,
+            "Soft Key",
+            ae::CheckBoxDef::setup(|f| {
+                f.set_default(false);
+                f.set_value(f.default());
+            }),
+        )?;
+        add_slider(params, Params::InnerTolerance, "Inner Tolerance", 0.0, 0.0)?;
+        add_slider(params, Params::OuterTolerance, "Outer Tolerance", 0.0, 10.0)?;
+        add_slider(params, Params::Despill, "Despill", 0.0, 100.0)?;
+        add_slider(params, Params::MatteChoke, "Matte Choke", -100.0, 0.0)?;
+        add_slider(params, Params::MatteSoftness, "Matte Softness", 0.0, 0.0)?;
 
-            // Add the pixel formats we support in order of preference.
-            suite.clear_supported_pixel_formats(in_data.effect_ref())?;
-            let formats = [
-                ae::pr::PixelFormat::Bgra4444_8u,
-                ae::pr::PixelFormat::Bgra4444_16u,
-                ae::pr::PixelFormat::Bgra4444_32f,
-            ];
-            for x in formats {
-                suite.add_supported_pixel_format(in_data.effect_ref(), x)?;
-            }
+        for i in EXTRA_KEYS {
+            ExtraKey::add(params, i, &EXTRA_KEY_DEFAULT, Params::ExtraKey)?;
         }
-        Ok(())
-    }
 
-    fn legacy_render(
-        &mut self,
-        in_data: &InData,
-        in_layer: ae::Layer,
-        out_layer: ae::Layer,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        if !in_data.is_premiere() {
-            // We don't support non-SmartFX unless it's Premiere
-            return Err(Error::BadCallbackParameter);
-        }
+        // 許容誤差の調整用に、結果の代わりにマットや抜いた範囲を表示する
+        params.add(
+            Params::View,
+            "View",
+            ae::PopupDef::setup(|f| {
+                f.set_options(&ViewMode::LABELS);
+                f.set_default(1);
+                f.set_value(f.default());
+            }),
+        )?;
+
+        // プリマルチプライされた入力を、色をアルファで割ってから抜く
+        params.add(
+            Params::Unpremultiply,
+            "Unpremultiply",
+            ae::CheckBoxDef::setup(|f| {
+                f.set_default(false);
+                f.set_value(f.default());
+            }),
+        )?;
 
-        self.do_render(in_data, in_layer, out_layer, params)?;
+        // キー色の一覧の何番目を編集するか (1 始まり)
+        params.add(
+            Params::KeyListIndex,
+            "Key List Entry",
+            ae::FloatSliderDef::setup(|f| {
+                f.set_default(1.0);
+                f.set_precision(0);
+                f.set_valid_min(1.0);
+                f.set_valid_max(list::MAX_INDEX);
+                f.set_slider_min(1.0);
+                f.set_slider_max(100.0);
+                f.set_value(f.default());
+            }),
+        )?;
+        params.add(
+            Params::KeyListColor,
+            "Key List Color",
+            ae::ColorDef::setup(|f| {
+                f.set_default(EXTRA_KEY_DEFAULT.color);
+                f.set_value(f.default());
+            }),
+        )?;
+        params.add(
+            Params::KeyListCustom,
+            "Key List Custom Tolerance",
+            ae::CheckBoxDef::setup(|f| {
+                f.set_default(false);
+                f.set_value(f.default());
+            }),
+        )?;
+        add_slider(
+            params,
+            Params::KeyListTolerance,
+            "Key List Tolerance",
+            0.0,
+            0.0,
+        )?;
+        add_button(params, Params::KeyListAdd, "Add to Key List", "Add")?;
+        add_button(params, Params::KeyListReplace, "Replace Key", "Replace")?;
+        add_button(params, Params::KeyListRemove, "Remove Key", "Remove")?;
 
         Ok(())
     }
 
-    fn smart_pre_render(
-        &mut self,
-        in_data: &InData,
-        mut extra: ae::PreRenderExtra,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        let req = extra.output_request();
-
-        if let Ok(in_result) = extra.callbacks().checkout_layer(
-            0,
-            0,
-            &req,
-            in_data.current_time(),
-            in_data.time_step(),
-            in_data.time_scale(),
-        ) {
-            let _ = extra.union_result_rect(in_result.result_rect.into());
-            let _ = extra.union_max_result_rect(in_result.max_result_rect.into());
-        }
-        Ok(())
+    fn kernel(params: &ae::Parameters<Params>, key_list: &KeyList) -> Result<ColorKey, Error> {
+        let threshold = params.get(Params::Threshold)?.as_float_slider()?.value();
+        // ビット深度によらない正規化した許容誤差
+        let tolerance = slider::percent(threshold);
+        let key_color = params.get(Params::KeyColor)?.as_color()?.value();
+        let back_color = params.get(Params::BackColor)?.as_color()?.value();
+        let metric = ColorMetric::from_popup(params.get(Params::Metric)?.as_popup()?.value());
+        let softness = params.get(Params::Softness)?.as_float_slider()?.value();
+        let view = params.get(Params::View)?.as_popup()?.value();
+        let unpremultiply = params.get(Params::Unpremultiply)?.as_checkbox()?.value();
+        Ok(
+            ColorKey::new(tolerance, metric, key_color.into(), back_color.into())
+                .with_softness(slider::percent(softness))
+                .with_keys(&keys(params, key_color.into(), key_list)?)
+                .with_keyer(keyer(params)?)
+                .with_view(ViewMode::from_popup(view))
+                .with_unpremultiply(unpremultiply),
+        )
     }
 
-    fn smart_render(
-        &mut self,
-        in_data: &InData,
-        extra: ae::SmartRenderExtra,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        let cb = extra.callbacks();
-        let Some(input_world) = cb.checkout_layer_pixels(0)? else {
-            return Ok(());
+    fn user_changed_param(
+        params: &ae::Parameters<Params>,
+        key_list: &mut KeyList,
+        param: Params,
+    ) -> Result<bool, Error> {
+        let index = params.get(Params::KeyListIndex)?.as_float_slider()?.value() as usize;
+        let index = index.saturating_sub(1);
+        let custom = params.get(Params::KeyListCustom)?.as_checkbox()?.value();
+        let tolerance = params
+            .get(Params::KeyListTolerance)?
+            .as_float_slider()?
+            .value();
+        let key = KeyColor {
+            color: params.get(Params::KeyListColor)?.as_color()?.value().into(),
+            tolerance: custom.then_some(slider::percent(tolerance)),
         };
-
-        if let Ok(Some(output_world)) = cb.checkout_output() {
-            self.do_render(in_data, input_world, output_world, params)?;
-        }
-
-        cb.checkin_layer_pixels(0)?;
-        Ok(())
+        let edit = match param {
+            Params::KeyListAdd => KeyEdit::Add(key),
+            Params::KeyListReplace => KeyEdit::Replace(index, key),
+            Params::KeyListRemove => KeyEdit::Remove(index),
+            _ => return Ok(false),
+        };
+        Ok(key_list.apply(edit).changed())
     }
+}
 
-    fn do_render(
-        &self,
-        in_data: &ae::InData,
-        in_layer: ae::Layer,
-        mut out_layer: ae::Layer,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), Error> {
-        let threshold = params.get(Params::Threshold)?.as_float_slider()?.value();
-        let threshold = (MAX_CHANNEL8 as f64 * threshold / 100.0) as u8;
-        let key_color = params.get(Params::KeyColor)?.as_color()?.value();
-        let back_color = params.get(Params::BackColor)?.as_color()?.value();
+// min - 100.0 のスライダー
+fn add_slider(
+    params: &mut ae::Parameters<Params>,
+    id: Params,
+    name: &str,
+    min: f64,
+    default: f64,
+) -> Result<(), Error> {
+    params.add(
+        id,
+        name,
+        ae::FloatSliderDef::setup(|f| {
+            f.set_default(default);
+            f.set_precision(1);
+            f.set_valid_min(min as f32);
+            f.set_valid_max(100.0);
+            f.set_slider_min(min as f32);
+            f.set_slider_max(100.0);
+            f.set_value(f.default());
+        }),
+    )
+}
 
-        let progress_final = out_layer.height() as _;
-        ae::pf::suites::WorldTransform::new()?.copy_hq(
-            in_data.effect_ref(),
-            &in_layer,
-            &mut out_layer,
-            None,
-            None,
-        )?;
-        in_layer.iterate_with(
-            &mut out_layer,
-            0,
-            progress_final,
-            None,
-            |_x: i32,
-             _y: i32,
-             pixel: ae::GenericPixel,
-             out_pixel: ae::GenericPixelMut|
-             -> Result<(), Error> {
-                match (pixel, out_pixel) {
-                    (ae::GenericPixel::Pixel8(pixel), ae::GenericPixelMut::Pixel8(out_pixel)) => {
-                        if threshold == 0 {
-                            if pixel.red == key_color.red
-                                && pixel.green == key_color.green
-                                && pixel.blue == key_color.blue
-                            {
-                                out_pixel.red = back_color.red;
-                                out_pixel.green = back_color.green;
-                                out_pixel.blue = back_color.blue;
-                                out_pixel.alpha = 0x00;
-                            }
-                        } else if pixel.red.abs_diff(key_color.red) <= threshold
-                            && pixel.green.abs_diff(key_color.green) <= threshold
-                            && pixel.blue.abs_diff(key_color.blue) <= threshold
-                        {
-                            out_pixel.red = back_color.red;
-                            out_pixel.green = back_color.green;
-                            out_pixel.blue = back_color.blue;
-                            out_pixel.alpha = 0x00;
-                        }
-                    }
-                    (ae::GenericPixel::Pixel16(pixel), ae::GenericPixelMut::Pixel16(out_pixel)) => {
-                        let p = &conv_16_to_8(pixel);
-                        let b = conv_8_to_16(&back_color);
-                        if threshold == 0 {
-                            if p.red == key_color.red
-                                && p.green == key_color.green
-                                && p.blue == key_color.blue
-                            {
-                                out_pixel.red = b.red;
-                                out_pixel.green = b.green;
-                                out_pixel.blue = b.blue;
-                                out_pixel.alpha = 0x00;
-                            }
-                        } else if p.red.abs_diff(key_color.red) <= threshold
-                            && p.green.abs_diff(key_color.green) <= threshold
-                            && p.blue.abs_diff(key_color.blue) <= threshold
-                        {
-                            out_pixel.red = b.red;
-                            out_pixel.green = b.green;
-                            out_pixel.blue = b.blue;
-                            out_pixel.alpha = 0x00;
-                        }
-                    }
-                    (
-                        ae::GenericPixel::PixelF32(pixel),
-                        ae::GenericPixelMut::PixelF32(out_pixel),
-                    ) => {
-                        let p = &conv_32_to_8(pixel);
-                        let b = conv_8_to_32(&back_color);
-                        if threshold == 0 {
-                            if p.red == key_color.red
-                                && p.green == key_color.green
-                                && p.blue == key_color.blue
-                            {
-                                out_pixel.red = b.red;
-                                out_pixel.green = b.green;
-                                out_pixel.blue = b.blue;
-                                out_pixel.alpha = 0.0;
-                            }
-                        } else if p.red.abs_diff(key_color.red) <= threshold
-                            && p.green.abs_diff(key_color.green) <= threshold
-                            && p.blue.abs_diff(key_color.blue) <= threshold
-                        {
-                            out_pixel.red = b.red;
-                            out_pixel.green = b.green;
-                            out_pixel.blue = b.blue;
-                            out_pixel.alpha = 0.0;
-                        }
-                    }
-                    _ => return Err(Error::BadCallbackParameter),
-                }
+// 押されたときに user_changed_param が呼ばれるボタンを追加します。
+fn add_button(
+    params: &mut ae::Parameters<Params>,
+    id: Params,
+    name: &str,
+    label: &str,
+) -> Result<(), Error> {
+    params.add_with_flags(
+        id,
+        name,
+        ae::ButtonDef::setup(|f| {
+            f.set_label(label);
+        }),
+        ae::ParamFlag::SUPERVISE,
+        ae::ParamUIFlags::empty(),
+    )
+}
 
-                Ok(())
-            },
-        )?;
-        Ok(())
+// Key Color と、オンになっている追加の色、キー色の一覧
+fn keys(
+    params: &ae::Parameters<Params>,
+    key_color: Rgba8,
+    key_list: &KeyList,
+) -> Result<Vec<KeyColor>, Error> {
+    let mut keys = vec![KeyColor::new(key_color)];
+    for i in EXTRA_KEYS {
+        let key = ExtraKey::get(params, i, Params::ExtraKey)?;
+        if key.enabled {
+            keys.push(KeyColor {
+                color: key.color.into(),
+                tolerance: key.custom.then_some(slider::percent(key.tolerance)),
+            });
+        }
+    }
+    keys.extend_from_slice(&key_list.keys);
+    Ok(keys)
+}
+
+fn keyer(params: &ae::Parameters<Params>) -> Result<Option<Keyer>, Error> {
+    if !params.get(Params::SoftKey)?.as_checkbox()?.value() {
+        return Ok(None);
     }
+    let value = |id| -> Result<f32, Error> {
+        Ok(slider::percent(params.get(id)?.as_float_slider()?.value()))
+    };
+    Ok(Some(Keyer {
+        inner: value(Params::InnerTolerance)?,
+        outer: value(Params::OuterTolerance)?,
+        despill: value(Params::Despill)?,
+        choke: value(Params::MatteChoke)?,
+        softness: value(Params::MatteSoftness)?,
+    }))
 }
@@ -1,227 +1,62 @@
-use after_effects::{self as ae, MAX_CHANNEL8};
+// After Effects SDK が無い環境(Linux)ではプラグイン本体はビルドしない
+#![cfg(any(windows, target_os = "macos"))]
 
-use libs::utils::{
-    conv_16_to_8, conv_32_to_8, conv_8_to_16, conv_8_to_32, round_byte_long, round_fp_short,
-    round_short,
-};
+use after_effects::{self as ae};
+
+use libs::host::FsEffect;
+use libs::kernels::createalpha::{AlphaMode, CreateAlpha};
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
-enum Params {}
+enum Params {
+    Mode,
+    Background,
+}
 
 #[derive(Default)]
 struct Plugin {}
 
-ae::define_effect!(Plugin, (), Params);
-
-impl AdobePluginGlobal for Plugin {
-    fn can_load(_host_name: &str, _host_version: &str) -> bool {
-        true
-    }
-
-    fn params_setup(
-        &self,
-        params: &mut ae::Parameters<Params>,
-        in_data: InData,
-        _: OutData,
-    ) -> Result<(), Error> {
-        Ok(())
-    }
-
-    fn handle_command(
-        &mut self,
-        cmd: ae::Command,
-        in_data: InData,
-        mut out_data: OutData,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        match cmd {
-            ae::Command::About => {
-                self.about(&mut out_data);
-            }
-            ae::Command::GlobalSetup => {
-                self.global_setup(&in_data)?;
-            }
-            ae::Command::Render {
-                in_layer,
-                out_layer,
-            } => {
-                self.legacy_render(&in_data, in_layer, out_layer, params)?;
-            }
-            ae::Command::SmartPreRender { extra } => {
-                self.smart_pre_render(&in_data, extra, params)?;
-            }
-            ae::Command::SmartRender { extra } => {
-                self.smart_render(&in_data, extra, params)?;
-            }
-            _ => {}
-        }
-        Ok(())
-    }
-}
-
-impl Plugin {
-    fn about(&mut self, out_data: &mut OutData) {
-        out_data.set_return_msg("fs-rs createalpha");
-    }
-
-    fn global_setup(&mut self, in_data: &InData) -> Result<(), ae::Error> {
-        win_dbg_logger::DEBUGGER_LOGGER.set_force_log_without_debugger(true);
-        log::info!("GlobalSetup");
-        // For Premiere - declare supported pixel formats
-        if in_data.is_premiere() {
-            let suite = ae::pf::suites::PixelFormat::new()?;
-
-            // Add the pixel formats we support in order of preference.
-            suite.clear_supported_pixel_formats(in_data.effect_ref())?;
-            let formats = [
-                ae::pr::PixelFormat::Bgra4444_8u,
-                ae::pr::PixelFormat::Bgra4444_16u,
-                ae::pr::PixelFormat::Bgra4444_32f,
-            ];
-            for x in formats {
-                suite.add_supported_pixel_format(in_data.effect_ref(), x)?;
-            }
-        }
-        Ok(())
-    }
-
-    fn legacy_render(
-        &mut self,
-        in_data: &InData,
-        in_layer: ae::Layer,
-        out_layer: ae::Layer,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        if !in_data.is_premiere() {
-            // We don't support non-SmartFX unless it's Premiere
-            return Err(Error::BadCallbackParameter);
-        }
-
-        self.do_render(in_data, in_layer, out_layer, params)?;
-
-        Ok(())
-    }
-
-    fn smart_pre_render(
-        &mut self,
-        in_data: &InData,
-        mut extra: ae::PreRenderExtra,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        let req = extra.output_request();
-
-        if let Ok(in_result) = extra.callbacks().checkout_layer(
-            0,
-            0,
-            &req,
-            in_data.current_time(),
-            in_data.time_step(),
-            in_data.time_scale(),
-        ) {
-            let _ = extra.union_result_rect(in_result.result_rect.into());
-            let _ = extra.union_max_result_rect(in_result.max_result_rect.into());
-        }
-        Ok(())
-    }
-
-    fn smart_render(
-        &mut self,
-        in_data: &InData,
-        extra: ae::SmartRenderExtra,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        let cb = extra.callbacks();
-        let Some(input_world) = cb.checkout_layer_pixels(0)? else {
-            return Ok(());
-        };
+libs::fs_effect!(Plugin, Params);
+
+impl FsEffect for Plugin {
+    type Params = Params;
+    type Kernel = CreateAlpha;
+    type Sequence = ();
+
+    const ABOUT: &'static str = "fs-rs createalpha";
+
+    fn params_setup(&self, params: &mut ae::Parameters<Params>) -> Result<(), Error> {
+        // アルファの作り方。既定は元の実装と同じ黒背景
+        params.add(
+            Params::Mode,
+            "Mode",
+            ae::PopupDef::setup(|f| {
+                f.set_options(&AlphaMode::LABELS);
+                f.set_default(1);
+                f.set_value(f.default());
+            }),
+        )?;
 
-        if let Ok(Some(output_world)) = cb.checkout_output() {
-            self.do_render(in_data, input_world, output_world, params)?;
-        }
+        // Unmultiply Color で取り除く背景色
+        params.add(
+            Params::Background,
+            "Background Color",
+            ae::ColorDef::setup(|f| {
+                f.set_default(Pixel8 {
+                    red: 0xFF,
+                    green: 0xFF,
+                    blue: 0xFF,
+                    alpha: 0xFF,
+                });
+                f.set_value(f.default());
+            }),
+        )?;
 
-        cb.checkin_layer_pixels(0)?;
         Ok(())
     }
 
-    fn do_render(
-        &self,
-        in_data: &ae::InData,
-        in_layer: ae::Layer,
-        mut out_layer: ae::Layer,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), Error> {
-        let progress_final = out_layer.height() as _;
-        ae::pf::suites::WorldTransform::new()?.copy_hq(
-            in_data.effect_ref(),
-            &in_layer,
-            &mut out_layer,
-            None,
-            None,
-        )?;
-        in_layer.iterate_with(
-            &mut out_layer,
-            0,
-            progress_final,
-            None,
-            |_x: i32,
-             _y: i32,
-             pixel: ae::GenericPixel,
-             out_pixel: ae::GenericPixelMut|
-             -> Result<(), Error> {
-                match (pixel, out_pixel) {
-                    (ae::GenericPixel::Pixel8(pixel), ae::GenericPixelMut::Pixel8(out_pixel)) => {
-                        if out_pixel.alpha < MAX_CHANNEL8 as u8 {
-                            out_pixel.red = round_byte_long(
-                                (out_pixel.red as u32 * out_pixel.alpha as u32
-                                    / MAX_CHANNEL8 as u32) as i32,
-                            );
-                            out_pixel.green = round_byte_long(
-                                (out_pixel.green as u32 * out_pixel.alpha as u32
-                                    / MAX_CHANNEL8 as u32) as i32,
-                            );
-                            out_pixel.blue = round_byte_long(
-                                (out_pixel.blue as u32 * out_pixel.alpha as u32
-                                    / MAX_CHANNEL8 as u32) as i32,
-                            );
-                        }
-                        out_pixel.alpha = out_pixel.red.max(out_pixel.green).max(out_pixel.blue);
-                    }
-                    (ae::GenericPixel::Pixel16(pixel), ae::GenericPixelMut::Pixel16(out_pixel)) => {
-                        if out_pixel.alpha < MAX_CHANNEL16 as u16 {
-                            out_pixel.red = round_short(
-                                (out_pixel.red * out_pixel.alpha / MAX_CHANNEL16 as u16) as i32,
-                            );
-                            out_pixel.green = round_short(
-                                (out_pixel.green * out_pixel.alpha / MAX_CHANNEL16 as u16) as i32,
-                            );
-                            out_pixel.blue = round_short(
-                                (out_pixel.blue * out_pixel.alpha / MAX_CHANNEL16 as u16) as i32,
-                            );
-                        }
-                        out_pixel.alpha = out_pixel.red.max(out_pixel.green).max(out_pixel.blue);
-                    }
-                    (
-                        ae::GenericPixel::PixelF32(pixel),
-                        ae::GenericPixelMut::PixelF32(out_pixel),
-                    ) => {
-                        if out_pixel.alpha < 1.0 {
-                            out_pixel.red = round_fp_short(out_pixel.red * out_pixel.alpha);
-                            out_pixel.green = round_fp_short(out_pixel.green * out_pixel.alpha);
-                            out_pixel.blue = round_fp_short(out_pixel.blue * out_pixel.alpha);
-                        }
-                        out_pixel.alpha = out_pixel.red.max(out_pixel.green).max(out_pixel.blue);
-                    }
-                    _ => return Err(Error::BadCallbackParameter),
-                }
-
-                Ok(())
-            },
-        )?;
-        ae::pf::suites::FillMatte::new()?.premultiply(
-            in_data.effect_ref(),
-            &mut out_layer,
-            false,
-        )?;
-        Ok(())
+    fn kernel(params: &ae::Parameters<Params>, _sequence: &()) -> Result<CreateAlpha, Error> {
+        let mode = AlphaMode::from_popup(params.get(Params::Mode)?.as_popup()?.value());
+        let background = params.get(Params::Background)?.as_color()?.value();
+        Ok(CreateAlpha::new(mode, background.into()))
     }
 }
@@ -0,0 +1,88 @@
+// image クレートの画像と libs の Image を相互に変換します。
+// 16bit は AE と同じ 0 - 32768 の範囲に変換してからカーネルに渡します。
+
+use std::path::Path;
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use libs::pixel::MAX_CHANNEL16;
+use libs::{Image, Rgba16, Rgba8};
+
+pub enum Frame {
+    Eight(Image<Rgba8>),
+    Sixteen(Image<Rgba16>),
+}
+
+pub fn load(path: &Path) -> Result<Frame, image::ImageError> {
+    let img = image::open(path)?;
+    let frame = match img {
+        DynamicImage::ImageLuma16(_)
+        | DynamicImage::ImageLumaA16(_)
+        | DynamicImage::ImageRgb16(_)
+        | DynamicImage::ImageRgba16(_)
+        | DynamicImage::ImageRgb32F(_)
+        | DynamicImage::ImageRgba32F(_) => {
+            let buf = img.to_rgba16();
+            let (width, height) = buf.dimensions();
+            let data = buf
+                .pixels()
+                .map(|p| {
+                    Rgba16::new(
+                        from_u16_full(p[0]),
+                        from_u16_full(p[1]),
+                        from_u16_full(p[2]),
+                        from_u16_full(p[3]),
+                    )
+                })
+                .collect();
+            Frame::Sixteen(
+                Image::from_vec(width as usize, height as usize, width as usize, data).unwrap(),
+            )
+        }
+        _ => {
+            let buf = img.to_rgba8();
+            let (width, height) = buf.dimensions();
+            let data = buf
+                .pixels()
+                .map(|p| Rgba8::new(p[0], p[1], p[2], p[3]))
+                .collect();
+            Frame::Eight(
+                Image::from_vec(width as usize, height as usize, width as usize, data).unwrap(),
+            )
+        }
+    };
+    Ok(frame)
+}
+
+pub fn save(path: &Path, frame: &Frame) -> Result<(), image::ImageError> {
+    match frame {
+        Frame::Eight(img) => {
+            let buf = ImageBuffer::from_fn(img.width() as u32, img.height() as u32, |x, y| {
+                let p = img.get(x as usize, y as usize).copied().unwrap_or_default();
+                Rgba([p.red, p.green, p.blue, p.alpha])
+            });
+            DynamicImage::ImageRgba8(buf).save(path)
+        }
+        Frame::Sixteen(img) => {
+            let buf = ImageBuffer::from_fn(img.width() as u32, img.height() as u32, |x, y| {
+                let p = img.get(x as usize, y as usize).copied().unwrap_or_default();
+                Rgba([
+                    to_u16_full(p.red),
+                    to_u16_full(p.green),
+                    to_u16_full(p.blue),
+                    to_u16_full(p.alpha),
+                ])
+            });
+            DynamicImage::ImageRgba16(buf).save(path)
+        }
+    }
+}
+
+// 0 - 65535 => 0 - 32768
+fn from_u16_full(v: u16) -> u16 {
+    ((v as u32 * MAX_CHANNEL16 + 32767) / 65535) as u16
+}
+
+// 0 - 32768 => 0 - 65535
+fn to_u16_full(v: u16) -> u16 {
+    ((v.min(MAX_CHANNEL16 as u16) as u32 * 65535 + MAX_CHANNEL16 / 2) / MAX_CHANNEL16) as u16
+}
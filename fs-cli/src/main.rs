@@ -0,0 +1,508 @@
+// fs-rs のエフェクトを After Effects なしで画像ファイルに適用するコマンドです。
+//
+//   fs-cli colorchange --level 5 --pair ffffff:808080 --pair ff0000:800000 in.png out.png
+//   fs-cli colorchange --level 5 --palette cel.ase in.png out.png
+//   fs-cli colorchange --level 5 --pair ffffff:808080:2 --pair ff0000:800000::10:oklab in.png out.png
+//   fs-cli createalpha in.tif out.tif
+//   fs-cli createalpha --mode white scan.png out.png
+
+mod io;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use libs::kernels::colorchange::{ColorChange, MatchMode, PairOverride, Transfer};
+use libs::kernels::colorkey::{ColorKey, KeyColor, Keyer, ViewMode};
+use libs::kernels::createalpha::{AlphaMode, CreateAlpha};
+use libs::kernels::pixelselector::PixelSelector;
+use libs::kernels::PixelKernel;
+use libs::palette::Palette;
+use libs::slider;
+use libs::{ColorMetric, Rgba8};
+
+use crate::io::Frame;
+
+#[derive(Parser)]
+#[command(
+    name = "fs-cli",
+    version,
+    about = "Apply fs-rs effects to PNG/TIFF files"
+)]
+struct Cli {
+    #[command(subcommand)]
+    effect: Effect,
+}
+
+#[derive(Subcommand)]
+enum Effect {
+    /// JK Color Change F's
+    Colorchange {
+        /// 0.0 - 100.0
+        #[arg(long, default_value_t = 0.0)]
+        level: f64,
+        /// 変更前と変更後の色 (例: ffffff:808080)。複数指定できます。
+        /// SRC:DST:LEVEL:SOFTNESS:METRIC でその組だけの設定にできます (空欄は全体の設定)。
+        /// RRGGBBAA で書くと、SRC はアルファも比べ、DST はアルファも置き換えます
+        #[arg(long = "pair", value_parser = parse_pair)]
+        pairs: Vec<(Rgba8, Rgba8, PairOverride)>,
+        /// 変更前と変更後の色の組を読み込むファイル (.ase/.aco/.gpl/.csv/.json)。--pair の後に追加されます
+        #[arg(long)]
+        palette: Option<PathBuf>,
+        /// 複数のペアが一致したときの扱い (last, first, nearest, chained)
+        #[arg(long, default_value = "last")]
+        mode: MatchMode,
+        /// 置き換え方 (replace, rgb-offset, luminance, lab, oklab)。replace 以外は元の陰影を残します
+        #[arg(long, default_value = "replace")]
+        transfer: Transfer,
+        /// 置き換える代わりに、一致したピクセルをペアごとの色で塗り、それ以外を暗くします
+        #[arg(long)]
+        preview: bool,
+        #[command(flatten)]
+        alpha: Alpha,
+        #[command(flatten)]
+        matching: Matching,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// JK Color Change Simple F's
+    Colorchangesimple {
+        /// 0.0 - 100.0
+        #[arg(long, default_value_t = 0.0)]
+        level: f64,
+        /// RRGGBBAA で書くとアルファも比べます
+        #[arg(long, value_parser = parse_color_alpha, default_value = "ffffff")]
+        src: (Rgba8, Option<u8>),
+        /// RRGGBBAA で書くとアルファも置き換えます
+        #[arg(long, value_parser = parse_color_alpha, default_value = "000000")]
+        dst: (Rgba8, Option<u8>),
+        #[command(flatten)]
+        alpha: Alpha,
+        #[command(flatten)]
+        matching: Matching,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// JK Color Key F's
+    Colorkey {
+        /// 0.0 - 100.0
+        #[arg(long, default_value_t = 0.0)]
+        threshold: f64,
+        /// 抜く色。複数指定できます。
+        /// COLOR:TOLERANCE でその色だけの許容誤差にできます (0.0 - 100.0、--soft では --inner の代わり)
+        #[arg(long = "key", value_parser = parse_key, default_value = "ffffff")]
+        keys: Vec<KeyColor>,
+        #[arg(long, value_parser = parse_color, default_value = "000000")]
+        back: Rgba8,
+        #[command(flatten)]
+        keyer: KeyerArgs,
+        /// 表示 (composite, matte, status, overlay)。composite 以外は許容誤差の調整用です
+        #[arg(long, default_value = "composite")]
+        view: ViewMode,
+        #[command(flatten)]
+        alpha: Alpha,
+        #[command(flatten)]
+        matching: Matching,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// JK Create Alpha F's
+    Createalpha {
+        /// アルファの作り方 (black, white, color, luma601, luma709)
+        #[arg(long, default_value = "black")]
+        mode: AlphaMode,
+        /// --mode color で取り除く背景色
+        #[arg(long, value_parser = parse_color, default_value = "ffffff")]
+        background: Rgba8,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// JK Pixel Selector F's
+    Pixelselector {
+        /// RRGGBBAA で書くとアルファも比べます
+        #[arg(long, value_parser = parse_color_alpha, default_value = "00ff00")]
+        target: (Rgba8, Option<u8>),
+        /// 0.0 - 255.0 (プラグインの Threshold と同じ 8bit の差)。--softness も同じ単位です
+        #[arg(long, default_value_t = 0.0)]
+        threshold: f64,
+        #[arg(long)]
+        invert: bool,
+        #[command(flatten)]
+        alpha: Alpha,
+        #[command(flatten)]
+        matching: Matching,
+        #[command(flatten)]
+        files: Files,
+    },
+}
+
+#[derive(Args)]
+struct Matching {
+    /// 色の距離の測り方 (rgb-box, rgb, hsv, hsl, lab76, lab2000, oklab)
+    #[arg(long, default_value = "rgb-box")]
+    metric: ColorMetric,
+    /// 許容誤差の外側で少しずつ効果を弱める幅 (level/threshold と同じ単位)
+    #[arg(long, default_value_t = 0.0)]
+    softness: f64,
+}
+
+#[derive(Args)]
+struct KeyerArgs {
+    /// 2値のキーの代わりに、キー色からの距離でアルファを作るキーヤーを使います
+    #[arg(long)]
+    soft: bool,
+    /// --soft: これより近い色は完全に透明 (0.0 - 100.0)
+    #[arg(long, default_value_t = 0.0)]
+    inner: f64,
+    /// --soft: これより遠い色は不透明 (0.0 - 100.0)
+    #[arg(long, default_value_t = 10.0)]
+    outer: f64,
+    /// --soft: 残った色からキー色の色味を取り除く強さ (0.0 - 100.0)
+    #[arg(long, default_value_t = 100.0)]
+    despill: f64,
+    /// --soft: マットを縮める (正) / 広げる (負) 量 (-100.0 - 100.0)
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    choke: f64,
+    /// --soft: マットの変化の両端を滑らかにする量 (0.0 - 100.0)
+    #[arg(long, default_value_t = 0.0)]
+    matte_softness: f64,
+}
+
+impl KeyerArgs {
+    fn keyer(&self) -> Option<Keyer> {
+        self.soft.then(|| Keyer {
+            inner: slider::percent(self.inner),
+            outer: slider::percent(self.outer),
+            despill: slider::percent(self.despill),
+            choke: slider::percent(self.choke),
+            softness: slider::percent(self.matte_softness),
+        })
+    }
+}
+
+#[derive(Args)]
+struct Alpha {
+    /// 入力をプリマルチプライされた色として扱い、アルファで割ってから比べます
+    #[arg(long)]
+    unpremultiply: bool,
+}
+
+#[derive(Args)]
+struct Files {
+    /// 入力画像 (PNG/TIFF, 8/16bit)
+    input: PathBuf,
+    /// 出力画像。拡張子で形式を決めます
+    output: PathBuf,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.effect) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("fs-cli: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(effect: Effect) -> Result<(), Box<dyn std::error::Error>> {
+    match effect {
+        Effect::Colorchange {
+            level,
+            pairs: pair_args,
+            palette,
+            mode,
+            transfer,
+            preview,
+            alpha,
+            matching,
+            files,
+        } => {
+            let (mut pairs, mut overrides): (Vec<_>, Vec<_>) = pair_args
+                .into_iter()
+                .map(|(src, dst, o)| ((src, dst), o))
+                .unzip();
+            // 確認表示の色は --pair の順番、パレットの組はその後のパレットの中での順番で決める
+            let mut slots: Vec<usize> = (0..pairs.len()).collect();
+            if let Some(path) = palette {
+                let palette = Palette::load(&path)?;
+                let offset = pairs.len();
+                pairs.extend(palette.enabled_pairs());
+                overrides.extend(palette.enabled_overrides());
+                slots.extend(palette.enabled_indices().map(|i| offset + i));
+            }
+            apply(
+                &ColorChange::new(slider::percent(level), matching.metric, pairs)
+                    .with_softness(slider::percent(matching.softness))
+                    .with_mode(mode)
+                    .with_transfer(transfer)
+                    .with_pair_overrides(overrides)
+                    .with_preview(preview)
+                    .with_preview_slots(slots)
+                    .with_unpremultiply(alpha.unpremultiply),
+                &files,
+            )
+        }
+        Effect::Colorchangesimple {
+            level,
+            src: (src, src_alpha),
+            dst: (dst, dst_alpha),
+            alpha,
+            matching,
+            files,
+        } => apply(
+            &ColorChange::new(slider::percent(level), matching.metric, vec![(src, dst)])
+                .with_softness(slider::percent(matching.softness))
+                .with_pair_overrides(vec![PairOverride {
+                    src_alpha,
+                    dst_alpha,
+                    ..PairOverride::default()
+                }])
+                .with_unpremultiply(alpha.unpremultiply),
+            &files,
+        ),
+        Effect::Colorkey {
+            threshold,
+            keys,
+            back,
+            keyer,
+            view,
+            alpha,
+            matching,
+            files,
+        } => apply(
+            &ColorKey::new(
+                slider::percent(threshold),
+                matching.metric,
+                keys[0].color,
+                back,
+            )
+            .with_softness(slider::percent(matching.softness))
+            .with_keys(&keys)
+            .with_keyer(keyer.keyer())
+            .with_view(view)
+            .with_unpremultiply(alpha.unpremultiply),
+            &files,
+        ),
+        Effect::Createalpha {
+            mode,
+            background,
+            files,
+        } => apply(&CreateAlpha::new(mode, background), &files),
+        Effect::Pixelselector {
+            target: (target, target_alpha),
+            threshold,
+            invert,
+            alpha,
+            matching,
+            files,
+        } => apply(
+            &PixelSelector::new(
+                invert,
+                Some(target),
+                slider::level8(threshold),
+                matching.metric,
+            )
+            .with_softness(slider::level8(matching.softness))
+            .with_target_alpha(target_alpha)
+            .with_unpremultiply(alpha.unpremultiply),
+            &files,
+        ),
+    }
+}
+
+fn apply<K: PixelKernel>(kernel: &K, files: &Files) -> Result<(), Box<dyn std::error::Error>> {
+    let frame = match io::load(&files.input)? {
+        Frame::Eight(img) => Frame::Eight(kernel.process_image(&img)),
+        Frame::Sixteen(img) => Frame::Sixteen(kernel.process_image(&img)),
+    };
+    io::save(&files.output, &frame)?;
+    Ok(())
+}
+
+fn parse_color(s: &str) -> Result<Rgba8, String> {
+    Rgba8::from_hex(s).ok_or_else(|| format!("expected RRGGBB, got `{s}`"))
+}
+
+// RRGGBB または RRGGBBAA。アルファは書いたときだけ返す
+fn parse_color_alpha(s: &str) -> Result<(Rgba8, Option<u8>), String> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 8 || !hex.is_ascii() {
+        return Ok((parse_color(s)?, None));
+    }
+    let (rgb, alpha) = hex.split_at(6);
+    let color = Rgba8::from_hex(rgb);
+    let alpha = u8::from_str_radix(alpha, 16).ok();
+    match (color, alpha) {
+        (Some(color), Some(alpha)) => Ok((color, Some(alpha))),
+        _ => Err(format!("expected RRGGBBAA, got `{s}`")),
+    }
+}
+
+// COLOR[:TOLERANCE]。TOLERANCE は 0.0 - 100.0
+fn parse_key(s: &str) -> Result<KeyColor, String> {
+    let (color, tolerance) = match s.split_once(':') {
+        Some((color, tolerance)) => {
+            let tolerance = tolerance
+                .parse::<f64>()
+                .map_err(|_| format!("expected a number, got `{tolerance}`"))?;
+            (color, Some(slider::percent(tolerance)))
+        }
+        None => (s, None),
+    };
+    Ok(KeyColor {
+        color: parse_color(color)?,
+        tolerance,
+    })
+}
+
+// SRC:DST[:LEVEL[:SOFTNESS[:METRIC]]]。LEVEL と SOFTNESS は 0.0 - 100.0 で、空欄は全体の設定
+fn parse_pair(s: &str) -> Result<(Rgba8, Rgba8, PairOverride), String> {
+    let fields: Vec<&str> = s.split(':').collect();
+    let [src, dst, rest @ ..] = fields.as_slice() else {
+        return Err(format!("expected SRC:DST, got `{s}`"));
+    };
+    if rest.len() > 3 {
+        return Err(format!(
+            "expected SRC:DST[:LEVEL[:SOFTNESS[:METRIC]]], got `{s}`"
+        ));
+    }
+    let field = |i: usize| rest.get(i).copied().filter(|f| !f.is_empty());
+    let percent = |i: usize| {
+        field(i)
+            .map(|f| {
+                f.parse::<f64>()
+                    .map(slider::percent)
+                    .map_err(|_| format!("expected a number, got `{f}`"))
+            })
+            .transpose()
+    };
+    let (src, src_alpha) = parse_color_alpha(src)?;
+    let (dst, dst_alpha) = parse_color_alpha(dst)?;
+    let overrides = PairOverride {
+        tolerance: percent(0)?,
+        softness: percent(1)?,
+        metric: field(2).map(str::parse::<ColorMetric>).transpose()?,
+        src_alpha,
+        dst_alpha,
+    };
+    Ok((src, dst, overrides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use libs::Image;
+
+    fn parse(args: &[&str]) -> Effect {
+        Cli::try_parse_from(["fs-cli"].iter().chain(args))
+            .unwrap()
+            .effect
+    }
+
+    #[test]
+    fn command_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_pixelselector_flags() {
+        let Effect::Pixelselector {
+            target,
+            threshold,
+            invert,
+            matching,
+            files,
+            ..
+        } = parse(&[
+            "pixelselector",
+            "--target",
+            "00ff0080",
+            "--threshold",
+            "12.5",
+            "--metric",
+            "oklab",
+            "--invert",
+            "in.png",
+            "out.png",
+        ])
+        else {
+            panic!("expected pixelselector");
+        };
+        assert_eq!(target, (Rgba8::new(0, 255, 0, 255), Some(0x80)));
+        assert_eq!(threshold, 12.5);
+        assert!(invert);
+        assert_eq!(matching.metric, ColorMetric::Oklab);
+        assert_eq!(files.input, PathBuf::from("in.png"));
+        assert_eq!(files.output, PathBuf::from("out.png"));
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        for args in [
+            &["colorchange", "--pair", "ffffff", "in.png", "out.png"][..],
+            &[
+                "colorchange",
+                "--pair",
+                "ffffff:000000:x",
+                "in.png",
+                "out.png",
+            ],
+            &["colorkey", "--key", "ffffff:abc", "in.png", "out.png"],
+            &["createalpha", "--mode", "gray", "in.png", "out.png"],
+            &["pixelselector", "--target", "00ff0", "in.png", "out.png"],
+            &["pixelselector", "in.png"],
+        ] {
+            assert!(
+                Cli::try_parse_from(["fs-cli"].iter().chain(args)).is_err(),
+                "{args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_pair_overrides() {
+        let (src, dst, o) = parse_pair("ffffff80:808080::10:lab2000").unwrap();
+        assert_eq!(src, Rgba8::new(255, 255, 255, 255));
+        assert_eq!(dst, Rgba8::new(128, 128, 128, 255));
+        assert_eq!(o.src_alpha, Some(0x80));
+        assert_eq!(o.dst_alpha, None);
+        assert_eq!(o.tolerance, None);
+        assert_eq!(o.softness, Some(0.1));
+        assert_eq!(o.metric, Some(ColorMetric::LabDe2000));
+
+        let key = parse_key("00ff00:25").unwrap();
+        assert_eq!(key.color, Rgba8::new(0, 255, 0, 255));
+        assert_eq!(key.tolerance, Some(0.25));
+    }
+
+    // --threshold はプラグインと同じ 8bit の差なので、20 離れた色は 30 で選ばれ、40 離れた色は選ばれない
+    #[test]
+    fn pixelselector_threshold_is_level8() {
+        let dir = std::env::temp_dir().join(format!("fs-cli-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("in.png"), dir.join("out.png"));
+        let near = Rgba8::new(20, 255, 0, 255);
+        let far = Rgba8::new(40, 255, 0, 255);
+        let image = Image::from_vec(2, 1, 2, vec![near, far]).unwrap();
+        io::save(&input, &Frame::Eight(image)).unwrap();
+
+        let result = run(parse(&[
+            "pixelselector",
+            "--threshold",
+            "30",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+        ]))
+        .and_then(|()| Ok(io::load(&output)?));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let Frame::Eight(out) = result.unwrap() else {
+            panic!("expected an 8-bit image");
+        };
+        let out: Vec<Rgba8> = out.pixels().copied().collect();
+        assert_eq!(out[0], near);
+        assert_eq!(out[1].alpha, 0);
+    }
+}
@@ -0,0 +1,78 @@
+// ペアの数を増やしたときの ColorChange の処理時間を測ります。
+// 索引が効いていれば、ペアが数百になってもほとんど遅くなりません。
+//
+//   cargo bench -p libs --bench colorchange
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use libs::kernels::colorchange::{ColorChange, PairOverride};
+use libs::{ColorMetric, Image, Renderer, Rgba8};
+
+const WIDTH: usize = 1920;
+const HEIGHT: usize = 1080;
+
+fn color(i: u32) -> Rgba8 {
+    let h = i.wrapping_mul(2654435761);
+    Rgba8::new((h >> 24) as u8, (h >> 16) as u8, (h >> 8) as u8, 255)
+}
+
+fn frame() -> Image<Rgba8> {
+    let mut img = Image::new(WIDTH, HEIGHT);
+    for y in 0..HEIGHT {
+        for (x, p) in img.row_mut(y).iter_mut().enumerate() {
+            *p = color((x / 4 + y / 4 * WIDTH) as u32 % 1024);
+        }
+    }
+    img
+}
+
+fn pairs(n: u32) -> Vec<(Rgba8, Rgba8)> {
+    (0..n)
+        .map(|i| (color(i), Rgba8::new(0, 0, 0, 255)))
+        .collect()
+}
+
+fn colorchange(c: &mut Criterion) {
+    let src = frame();
+    let mut dst = src.clone();
+    // 並列化の影響を除くため 1 スレッドで測る
+    let renderer = Renderer::new().with_threads(1);
+
+    for (name, tolerance, metric) in [
+        ("exact", 0.0, ColorMetric::RgbBox),
+        ("rgb_box", 0.02, ColorMetric::RgbBox),
+        ("oklab", 0.02, ColorMetric::Oklab),
+    ] {
+        let mut group = c.benchmark_group(format!("colorchange_{name}_1080p"));
+        group.sample_size(10);
+        group.throughput(Throughput::Elements((WIDTH * HEIGHT) as u64));
+        for n in [8, 64, 256, 512] {
+            let kernel = ColorChange::new(tolerance, metric, pairs(n));
+            group.bench_with_input(BenchmarkId::new("pairs", n), &kernel, |b, k| {
+                b.iter(|| renderer.render(k, &src.view(), dst.view_mut()))
+            });
+        }
+        group.finish();
+    }
+
+    // ペアごとに測り方を変えて、索引が測り方の数だけに分かれる場合
+    let mut group = c.benchmark_group("colorchange_mixed_1080p");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((WIDTH * HEIGHT) as u64));
+    for n in [8, 64, 256, 512] {
+        let overrides = (0..n as usize)
+            .map(|i| PairOverride {
+                metric: Some(ColorMetric::ALL[i % ColorMetric::ALL.len()]),
+                ..PairOverride::default()
+            })
+            .collect();
+        let kernel =
+            ColorChange::new(0.02, ColorMetric::RgbBox, pairs(n)).with_pair_overrides(overrides);
+        group.bench_with_input(BenchmarkId::new("pairs", n), &kernel, |b, k| {
+            b.iter(|| renderer.render(k, &src.view(), dst.view_mut()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, colorchange);
+criterion_main!(benches);
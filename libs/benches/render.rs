@@ -0,0 +1,68 @@
+// 4K フレームで 1 スレッドと全スレッドのレンダリング時間を比べます。
+//
+//   cargo bench -p libs --bench render
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use libs::kernels::colorchange::ColorChange;
+use libs::kernels::createalpha::CreateAlpha;
+use libs::kernels::PixelKernel;
+use libs::{ColorMetric, Image, Pixel, Renderer, Rgba16, Rgba8};
+
+const WIDTH: usize = 3840;
+const HEIGHT: usize = 2160;
+
+// 同じ色ばかりにならないようにグラデーションを作る
+fn frame<P: Pixel>() -> Image<P> {
+    let mut img = Image::new(WIDTH, HEIGHT);
+    for y in 0..HEIGHT {
+        for (x, p) in img.row_mut(y).iter_mut().enumerate() {
+            *p = P::from_rgba8(Rgba8::new(
+                (x * 255 / WIDTH) as u8,
+                (y * 255 / HEIGHT) as u8,
+                ((x + y) % 256) as u8,
+                255,
+            ));
+        }
+    }
+    img
+}
+
+fn colorchange() -> ColorChange {
+    let pairs = (0..8u8)
+        .map(|i| {
+            (
+                Rgba8::new(i * 32, 255 - i * 32, i * 16, 255),
+                Rgba8::new(128, 128, 128, 255),
+            )
+        })
+        .collect();
+    ColorChange::new(0.05, ColorMetric::RgbBox, pairs).with_softness(0.05)
+}
+
+fn bench_kernel<K: PixelKernel, P: Pixel>(c: &mut Criterion, name: &str, kernel: &K) {
+    let src = frame::<P>();
+    let mut dst = src.clone();
+    let all = Renderer::new();
+
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((WIDTH * HEIGHT) as u64));
+    let mut counts = vec![1, all.threads()];
+    counts.dedup();
+    for threads in counts {
+        let renderer = all.with_threads(threads);
+        group.bench_with_input(BenchmarkId::new("threads", threads), &renderer, |b, r| {
+            b.iter(|| r.render(kernel, &src.view(), dst.view_mut()))
+        });
+    }
+    group.finish();
+}
+
+fn render(c: &mut Criterion) {
+    bench_kernel::<_, Rgba8>(c, "colorchange_8bit_4k", &colorchange());
+    bench_kernel::<_, Rgba16>(c, "colorchange_16bit_4k", &colorchange());
+    bench_kernel::<_, Rgba8>(c, "createalpha_8bit_4k", &CreateAlpha::default());
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
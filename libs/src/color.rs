@@ -0,0 +1,374 @@
+// 色空間の変換です。入力は 0.0 - 1.0 に正規化した sRGB として扱います。
+// ref: http://www.brucelindbloom.com/index.html?Math.html
+// ref: https://bottosson.github.io/posts/oklab/
+
+// 係数は参照元の桁数のまま書いている
+#![allow(clippy::excessive_precision)]
+
+// D65
+const WHITE_X: f32 = 0.95047;
+const WHITE_Y: f32 = 1.0;
+const WHITE_Z: f32 = 1.08883;
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Rec. 709 の係数で、ガンマのかかった値のまま重み付けした明るさ (luma) を返します。
+pub fn luma(r: f32, g: f32, b: f32) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// (h, s, v) を返します。h は 0.0 - 1.0 (1周)。
+pub fn rgb_to_hsv(r: f32, g: f32, b: f32) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let d = max - min;
+    let s = if max > 0.0 { d / max } else { 0.0 };
+    [hue(r, g, b, max, d), s, max]
+}
+
+/// (h, s, l) を返します。h は 0.0 - 1.0 (1周)。
+pub fn rgb_to_hsl(r: f32, g: f32, b: f32) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let d = max - min;
+    let l = (max + min) / 2.0;
+    let s = if d > 0.0 {
+        d / (1.0 - (2.0 * l - 1.0).abs()).max(f32::EPSILON)
+    } else {
+        0.0
+    };
+    [hue(r, g, b, max, d), s, l]
+}
+
+fn hue(r: f32, g: f32, b: f32, max: f32, d: f32) -> f32 {
+    if d <= 0.0 {
+        return 0.0;
+    }
+    let h = if max == r {
+        (g - b) / d
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    (h / 6.0).rem_euclid(1.0)
+}
+
+pub fn rgb_to_xyz(r: f32, g: f32, b: f32) -> [f32; 3] {
+    let r = srgb_to_linear(r);
+    let g = srgb_to_linear(g);
+    let b = srgb_to_linear(b);
+    [
+        0.4124564 * r + 0.3575761 * g + 0.1804375 * b,
+        0.2126729 * r + 0.7151522 * g + 0.0721750 * b,
+        0.0193339 * r + 0.1191920 * g + 0.9503041 * b,
+    ]
+}
+
+pub fn xyz_to_rgb(x: f32, y: f32, z: f32) -> [f32; 3] {
+    let r = 3.2404542 * x - 1.5371385 * y - 0.4985314 * z;
+    let g = -0.9692660 * x + 1.8760108 * y + 0.0415560 * z;
+    let b = 0.0556434 * x - 0.2040259 * y + 1.0572252 * z;
+    [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)]
+}
+
+const LAB_E: f32 = 216.0 / 24389.0;
+const LAB_K: f32 = 24389.0 / 27.0;
+
+/// CIE L*a*b* (L は 0 - 100) を返します。
+pub fn rgb_to_lab(r: f32, g: f32, b: f32) -> [f32; 3] {
+    let [x, y, z] = rgb_to_xyz(r, g, b);
+    let f = |t: f32| {
+        if t > LAB_E {
+            t.cbrt()
+        } else {
+            (LAB_K * t + 16.0) / 116.0
+        }
+    };
+    let fx = f(x / WHITE_X);
+    let fy = f(y / WHITE_Y);
+    let fz = f(z / WHITE_Z);
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+pub fn lab_to_rgb(l: f32, a: f32, b: f32) -> [f32; 3] {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+    let inv = |f: f32| {
+        let f3 = f * f * f;
+        if f3 > LAB_E {
+            f3
+        } else {
+            (116.0 * f - 16.0) / LAB_K
+        }
+    };
+    let y = if l > LAB_K * LAB_E {
+        fy * fy * fy
+    } else {
+        l / LAB_K
+    };
+    xyz_to_rgb(inv(fx) * WHITE_X, y * WHITE_Y, inv(fz) * WHITE_Z)
+}
+
+/// OKLab (L は 0 - 1) を返します。
+pub fn rgb_to_oklab(r: f32, g: f32, b: f32) -> [f32; 3] {
+    let r = srgb_to_linear(r);
+    let g = srgb_to_linear(g);
+    let b = srgb_to_linear(b);
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+pub fn oklab_to_rgb(l: f32, a: f32, b: f32) -> [f32; 3] {
+    let l_ = l + 0.3963377774 * a + 0.2158037573 * b;
+    let m_ = l - 0.1055613458 * a - 0.0638541728 * b;
+    let s_ = l - 0.0894841775 * a - 1.2914855480 * b;
+    let l = l_ * l_ * l_;
+    let m = m_ * m_ * m_;
+    let s = s_ * s_ * s_;
+    [
+        linear_to_srgb(4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s),
+        linear_to_srgb(-1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s),
+        linear_to_srgb(-0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s),
+    ]
+}
+
+/// CIEDE2000 の色差です。
+/// 途中の計算は f64 で行います。
+pub fn delta_e2000(lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
+    use std::f64::consts::PI;
+
+    let [l1, a1, b1] = lab1.map(f64::from);
+    let [l2, a2, b2] = lab2.map(f64::from);
+    let c1 = a1.hypot(b1);
+    let c2 = a2.hypot(b2);
+    let c_bar = (c1 + c2) / 2.0;
+    let c_bar7 = c_bar.powi(7);
+    let g = 0.5 * (1.0 - (c_bar7 / (c_bar7 + 25f64.powi(7))).sqrt());
+    let a1p = a1 * (1.0 + g);
+    let a2p = a2 * (1.0 + g);
+    let c1p = a1p.hypot(b1);
+    let c2p = a2p.hypot(b2);
+    let h1p = if c1p == 0.0 {
+        0.0
+    } else {
+        b1.atan2(a1p).rem_euclid(2.0 * PI)
+    };
+    let h2p = if c2p == 0.0 {
+        0.0
+    } else {
+        b2.atan2(a2p).rem_euclid(2.0 * PI)
+    };
+
+    let dl = l2 - l1;
+    let dc = c2p - c1p;
+    let dh = if c1p * c2p == 0.0 {
+        0.0
+    } else {
+        let d = h2p - h1p;
+        if d > PI {
+            d - 2.0 * PI
+        } else if d < -PI {
+            d + 2.0 * PI
+        } else {
+            d
+        }
+    };
+    let dh_big = 2.0 * (c1p * c2p).sqrt() * (dh / 2.0).sin();
+
+    let l_bar = (l1 + l2) / 2.0;
+    let c_bar_p = (c1p + c2p) / 2.0;
+    let h_bar_p = if c1p * c2p == 0.0 {
+        h1p + h2p
+    } else if (h1p - h2p).abs() <= PI + 1.0e-9 {
+        // 色相がちょうど反対 (差が π) のときは、丸めの向きによらずこちらにする (Sharma の表の 10 と 14)
+        (h1p + h2p) / 2.0
+    } else if h1p + h2p < 2.0 * PI {
+        (h1p + h2p + 2.0 * PI) / 2.0
+    } else {
+        (h1p + h2p - 2.0 * PI) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (h_bar_p - PI / 6.0).cos()
+        + 0.24 * (2.0 * h_bar_p).cos()
+        + 0.32 * (3.0 * h_bar_p + PI / 30.0).cos()
+        - 0.20 * (4.0 * h_bar_p - 63.0 * PI / 180.0).cos();
+    let d_theta = (30.0 * PI / 180.0) * (-((h_bar_p * 180.0 / PI - 275.0) / 25.0).powi(2)).exp();
+    let c_bar_p7 = c_bar_p.powi(7);
+    let rc = 2.0 * (c_bar_p7 / (c_bar_p7 + 25f64.powi(7))).sqrt();
+    let sl = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+    let sc = 1.0 + 0.045 * c_bar_p;
+    let sh = 1.0 + 0.015 * c_bar_p * t;
+    let rt = -(2.0 * d_theta).sin() * rc;
+
+    let l_term = dl / sl;
+    let c_term = dc / sc;
+    let h_term = dh_big / sh;
+    (l_term * l_term + c_term * c_term + h_term * h_term + rt * c_term * h_term).sqrt() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3], tolerance: f32) {
+        for i in 0..3 {
+            assert!(
+                (actual[i] - expected[i]).abs() <= tolerance,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    // Sharma, Wu, Dalal: "The CIEDE2000 Color-Difference Formula: Implementation Notes,
+    // Supplementary Test Data, and Mathematical Observations" (2005) の表 1
+    #[rustfmt::skip]
+    const SHARMA: [([f32; 3], [f32; 3], f32); 34] = [
+        ([50.0000, 2.6772, -79.7751], [50.0000, 0.0000, -82.7485], 2.0425),
+        ([50.0000, 3.1571, -77.2803], [50.0000, 0.0000, -82.7485], 2.8615),
+        ([50.0000, 2.8361, -74.0200], [50.0000, 0.0000, -82.7485], 3.4412),
+        ([50.0000, -1.3802, -84.2814], [50.0000, 0.0000, -82.7485], 1.0000),
+        ([50.0000, -1.1848, -84.8006], [50.0000, 0.0000, -82.7485], 1.0000),
+        ([50.0000, -0.9009, -85.5211], [50.0000, 0.0000, -82.7485], 1.0000),
+        ([50.0000, 0.0000, 0.0000], [50.0000, -1.0000, 2.0000], 2.3669),
+        ([50.0000, -1.0000, 2.0000], [50.0000, 0.0000, 0.0000], 2.3669),
+        ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0009], 7.1792),
+        ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0010], 7.1792),
+        ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0011], 7.2195),
+        ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0012], 7.2195),
+        ([50.0000, -0.0010, 2.4900], [50.0000, 0.0009, -2.4900], 4.8045),
+        ([50.0000, -0.0010, 2.4900], [50.0000, 0.0010, -2.4900], 4.8045),
+        ([50.0000, -0.0010, 2.4900], [50.0000, 0.0011, -2.4900], 4.7461),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 0.0000, -2.5000], 4.3065),
+        ([50.0000, 2.5000, 0.0000], [73.0000, 25.0000, -18.0000], 27.1492),
+        ([50.0000, 2.5000, 0.0000], [61.0000, -5.0000, 29.0000], 22.8977),
+        ([50.0000, 2.5000, 0.0000], [56.0000, -27.0000, -3.0000], 31.9030),
+        ([50.0000, 2.5000, 0.0000], [58.0000, 24.0000, 15.0000], 19.4535),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 3.1736, 0.5854], 1.0000),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 3.2972, 0.0000], 1.0000),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 1.8634, 0.5757], 1.0000),
+        ([50.0000, 2.5000, 0.0000], [50.0000, 3.2592, 0.3350], 1.0000),
+        ([60.2574, -34.0099, 36.2677], [60.4626, -34.1751, 39.4387], 1.2644),
+        ([63.0109, -31.0961, -5.8663], [62.8187, -29.7946, -4.0864], 1.2630),
+        ([61.2901, 3.7196, -5.3901], [61.4292, 2.2480, -4.9620], 1.8731),
+        ([35.0831, -44.1164, 3.7933], [35.0232, -40.0716, 1.5901], 1.8645),
+        ([22.7233, 20.0904, -46.6940], [23.0331, 14.9730, -42.5619], 2.0373),
+        ([36.4612, 47.8580, 18.3852], [36.2715, 50.5065, 21.2231], 1.4146),
+        ([90.8027, -2.0831, 1.4410], [91.1528, -1.6435, 0.0447], 1.4441),
+        ([90.9257, -0.5406, -0.9208], [88.6381, -0.8985, -0.7239], 1.5381),
+        ([6.7747, -0.2908, -2.4247], [5.8714, -0.0985, -2.2286], 0.6377),
+        ([2.0776, 0.0795, -1.1350], [0.9033, -0.0636, -0.5514], 0.9082),
+    ];
+
+    #[test]
+    fn delta_e2000_matches_sharma() {
+        for (lab1, lab2, expected) in SHARMA {
+            // 表は小数点以下 4 桁
+            let d = delta_e2000(lab1, lab2);
+            assert!(
+                (d - expected).abs() < 1.0e-3,
+                "{lab1:?} {lab2:?}: {d} != {expected}"
+            );
+            // 入れ替えても同じ
+            assert!((delta_e2000(lab2, lab1) - d).abs() < 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn srgb_to_lab_reference() {
+        // D65 の sRGB の原色 (Lindbloom の計算機と同じ白色点)
+        assert_close(rgb_to_lab(1.0, 1.0, 1.0), [100.0, 0.0, 0.0], 1.0e-2);
+        assert_close(rgb_to_lab(0.0, 0.0, 0.0), [0.0, 0.0, 0.0], 1.0e-4);
+        assert_close(
+            rgb_to_lab(1.0, 0.0, 0.0),
+            [53.2408, 80.0925, 67.2032],
+            1.0e-2,
+        );
+        assert_close(
+            rgb_to_lab(0.0, 1.0, 0.0),
+            [87.7347, -86.1827, 83.1793],
+            1.0e-2,
+        );
+        assert_close(
+            rgb_to_lab(0.0, 0.0, 1.0),
+            [32.2970, 79.1875, -107.8602],
+            1.0e-2,
+        );
+        // 18% グレーの反射率 (L = 50 付近)
+        assert_close(
+            rgb_to_lab(0.466327, 0.466327, 0.466327),
+            [50.0, 0.0, 0.0],
+            1.0e-2,
+        );
+    }
+
+    #[test]
+    fn srgb_to_oklab_reference() {
+        // https://bottosson.github.io/posts/oklab/ の行列で計算した sRGB の原色
+        assert_close(rgb_to_oklab(1.0, 1.0, 1.0), [1.0, 0.0, 0.0], 1.0e-4);
+        assert_close(rgb_to_oklab(0.0, 0.0, 0.0), [0.0, 0.0, 0.0], 1.0e-6);
+        assert_close(
+            rgb_to_oklab(1.0, 0.0, 0.0),
+            [0.62796, 0.22486, 0.12585],
+            1.0e-4,
+        );
+        assert_close(
+            rgb_to_oklab(0.0, 1.0, 0.0),
+            [0.86644, -0.23389, 0.17950],
+            1.0e-4,
+        );
+        assert_close(
+            rgb_to_oklab(0.0, 0.0, 1.0),
+            [0.45201, -0.03246, -0.31153],
+            1.0e-4,
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        for c in [
+            [0.2, 0.5, 0.8],
+            [1.0, 0.0, 0.0],
+            [0.9, 0.9, 0.1],
+            [0.01, 0.02, 0.03],
+        ] {
+            let [r, g, b] = c;
+            let [l, a, bb] = rgb_to_lab(r, g, b);
+            assert_close(lab_to_rgb(l, a, bb), c, 1.0e-4);
+            let [l, a, bb] = rgb_to_oklab(r, g, b);
+            assert_close(oklab_to_rgb(l, a, bb), c, 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn hsv_and_hsl_reference() {
+        assert_close(rgb_to_hsv(1.0, 0.0, 0.0), [0.0, 1.0, 1.0], 1.0e-6);
+        assert_close(rgb_to_hsv(0.0, 0.5, 0.5), [0.5, 1.0, 0.5], 1.0e-6);
+        assert_close(rgb_to_hsv(0.5, 0.25, 0.5), [5.0 / 6.0, 0.5, 0.5], 1.0e-6);
+        assert_close(rgb_to_hsl(1.0, 0.0, 0.0), [0.0, 1.0, 0.5], 1.0e-6);
+        assert_close(rgb_to_hsl(0.0, 0.5, 0.5), [0.5, 1.0, 0.25], 1.0e-6);
+        assert_close(rgb_to_hsl(0.75, 0.75, 0.75), [0.0, 0.0, 0.75], 1.0e-6);
+        assert_close(rgb_to_hsl(0.5, 1.0, 0.5), [1.0 / 3.0, 1.0, 0.75], 1.0e-6);
+    }
+}
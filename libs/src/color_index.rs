@@ -0,0 +1,104 @@
+// 多数の基準色の中から、ピクセルに一致しうる色だけを素早く取り出すための索引です。
+// 色の数が数百になっても、1ピクセルあたりの比較回数がほぼ変わらないようにします。
+//
+// - 許容誤差が 0 のときは 8bit の RGB をキーにしたハッシュで同じ色だけを引きます。
+// - 箱型・ユークリッド距離のときは、一辺が許容誤差の格子に色を振り分けて近傍だけを調べます。
+// - それ以外 (ΔE2000, HSV/HSL) は範囲を絞れないので全部の色を候補にします。
+//
+// どの場合も候補は元の順番 (添字の昇順) で返すので、先の色・後の色を優先する処理をそのまま使えます。
+// 候補が本当に一致するかは呼び出し側で ColorMetric::weight などを使って確かめます。
+
+use std::collections::HashMap;
+
+use crate::metric::{ColorMetric, MetricColor};
+use crate::pixel::{Channel, Pixel, Rgba8};
+
+// 整数への丸め誤差や 8bit から各ビット深度への変換の差を吸収するための余裕
+const MARGIN: f32 = 1.0e-3;
+
+pub struct ColorIndex {
+    all: Vec<usize>,
+    kind: Kind,
+}
+
+enum Kind {
+    /// RGB を詰めた値 => その色を持つ添字 (昇順)
+    Exact(HashMap<u32, Vec<usize>>),
+    /// セル => そのセルと周囲 26 セルにある色の添字 (昇順)
+    Grid {
+        cell: f32,
+        cells: HashMap<[i32; 3], Vec<usize>>,
+    },
+    All,
+}
+
+impl ColorIndex {
+    /// `reach` は一致とみなす最大の距離 (正規化した単位。許容誤差 + ぼかし幅) です。
+    pub fn new(metric: ColorMetric, keys: &[MetricColor], reach: f32) -> Self {
+        let all = (0..keys.len()).collect();
+        let kind = if reach <= 0.0 {
+            let mut map: HashMap<u32, Vec<usize>> = HashMap::new();
+            for (i, key) in keys.iter().enumerate() {
+                map.entry(pack(key.color)).or_default().push(i);
+            }
+            Kind::Exact(map)
+        } else if let Some(scale) = grid_scale(metric) {
+            let cell = (reach + MARGIN) * scale;
+            let mut cells: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+            for (i, key) in keys.iter().enumerate() {
+                let [x, y, z] = cell_of(key.coords(), cell);
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        for dz in -1..=1 {
+                            cells.entry([x + dx, y + dy, z + dz]).or_default().push(i);
+                        }
+                    }
+                }
+            }
+            Kind::Grid { cell, cells }
+        } else {
+            Kind::All
+        };
+        Self { all, kind }
+    }
+
+    /// `pixel` に一致しうる色の添字を昇順で返します。
+    pub fn candidates<P: Pixel>(&self, metric: ColorMetric, pixel: &P) -> &[usize] {
+        match &self.kind {
+            Kind::Exact(map) => map
+                .get(&pack(nearest_rgba8(pixel)))
+                .map_or(&[], Vec::as_slice),
+            Kind::Grid { cell, cells } => cells
+                .get(&cell_of(metric.coords(pixel), *cell))
+                .map_or(&[], Vec::as_slice),
+            Kind::All => &self.all,
+        }
+    }
+}
+
+// 座標の1単位が正規化した距離のいくつになるか。格子を使えない距離は None
+fn grid_scale(metric: ColorMetric) -> Option<f32> {
+    match metric {
+        ColorMetric::RgbBox | ColorMetric::RgbEuclidean | ColorMetric::Oklab => Some(1.0),
+        ColorMetric::LabDe76 => Some(100.0),
+        ColorMetric::LabDe2000 | ColorMetric::Hsv | ColorMetric::Hsl => None,
+    }
+}
+
+fn cell_of(c: [f32; 3], cell: f32) -> [i32; 3] {
+    [
+        (c[0] / cell).floor() as i32,
+        (c[1] / cell).floor() as i32,
+        (c[2] / cell).floor() as i32,
+    ]
+}
+
+// 一番近い 8bit の色。to_rgba8 は F's と同じ丸め (32bit は +0.5 してから丸める) なので使わない
+fn nearest_rgba8<P: Pixel>(p: &P) -> Rgba8 {
+    let to8 = |c: P::Channel| (c.to_f32() * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgba8::new(to8(p.red()), to8(p.green()), to8(p.blue()), 0)
+}
+
+fn pack(c: Rgba8) -> u32 {
+    (c.red as u32) << 16 | (c.green as u32) << 8 | c.blue as u32
+}
@@ -0,0 +1,79 @@
+// 8/16bit のチャンネルを整数のまま計算する関数です。
+// 丸めは AE SDK のサンプルや F's Plugins のマクロと同じで、途中の計算は u32 で行うので
+// 16bit の最大値 (と壊れた値 65535) でも桁あふれしません。
+//
+//   #define INT_MULT(a, b, t)  ( (t) = (a) * (b) + 0x80, ( ( ( (t) >> 8 ) + (t) ) >> 8 ) )
+//   #define INT_BLEND(a, b, alpha, tmp)  ( INT_MULT( (a) - (b), alpha, tmp ) + (b) )
+//   #define FS_CONVERT8TO16(A)  ( (((A_long)(A) * PF_MAX_CHAN16) + PF_HALF_CHAN8) / PF_MAX_CHAN8 )
+//
+// どれも四捨五入した結果になります (INT_MULT は a * b / 255 の四捨五入と同じ値です)。
+
+use crate::pixel::{Rgba16, Rgba8, MAX_CHANNEL16, MAX_CHANNEL8};
+
+macro_rules! fixed_ops {
+    (
+        $t:ty, $pixel:ty, $max:expr,
+        $mul:ident, $div:ident, $lerp:ident, $premultiply:ident, $unpremultiply:ident
+    ) => {
+        /// `a * b / MAX` を四捨五入します。MAX を超える値は MAX で止めます。
+        #[inline]
+        pub fn $mul(a: $t, b: $t) -> $t {
+            ((a as u32 * b as u32 + $max / 2) / $max).min($max) as $t
+        }
+
+        /// `a * MAX / b` を四捨五入します。`b` が 0 のときは 0、MAX を超える値は MAX で止めます。
+        #[inline]
+        pub fn $div(a: $t, b: $t) -> $t {
+            if b == 0 {
+                return 0;
+            }
+            ((a as u32 * $max + b as u32 / 2) / b as u32).min($max) as $t
+        }
+
+        /// `a` から `b` へ `t / MAX` の割合で補間します (INT_BLEND と同じ)。
+        #[inline]
+        pub fn $lerp(a: $t, b: $t, t: $t) -> $t {
+            let t = t.min($max as $t);
+            if b >= a {
+                a + $mul(b - a, t)
+            } else {
+                a - $mul(a - b, t)
+            }
+        }
+
+        /// 色にアルファを掛けます (ストレート => プリマルチプライ)。
+        #[inline]
+        pub fn $premultiply(p: $pixel) -> $pixel {
+            let a = p.alpha;
+            <$pixel>::new($mul(p.red, a), $mul(p.green, a), $mul(p.blue, a), a)
+        }
+
+        /// 色をアルファで割ります (プリマルチプライ => ストレート)。
+        #[inline]
+        pub fn $unpremultiply(p: $pixel) -> $pixel {
+            let a = p.alpha;
+            <$pixel>::new($div(p.red, a), $div(p.green, a), $div(p.blue, a), a)
+        }
+    };
+}
+
+fixed_ops!(
+    u8,
+    Rgba8,
+    MAX_CHANNEL8,
+    mul8,
+    div8,
+    lerp8,
+    premultiply8,
+    unpremultiply8
+);
+fixed_ops!(
+    u16,
+    Rgba16,
+    MAX_CHANNEL16,
+    mul16,
+    div16,
+    lerp16,
+    premultiply16,
+    unpremultiply16
+);
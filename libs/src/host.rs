@@ -0,0 +1,390 @@
+// After Effects とホスト非依存のカーネルをつなぐ部分です。
+
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use after_effects::{self as ae};
+
+use crate::image::{ImageView, ImageViewMut};
+use crate::kernels::PixelKernel;
+use crate::pixel::{Pixel, Rgba16, Rgba8, RgbaF32};
+use crate::render::Renderer;
+use crate::sequence::{self, SequenceState};
+
+// iterate_with のコールバックから呼び出して、ビット深度に応じたカーネルを実行します。
+pub fn process_generic<K: PixelKernel>(
+    kernel: &K,
+    pixel: ae::GenericPixel,
+    out_pixel: ae::GenericPixelMut,
+) -> Result<(), ae::Error> {
+    match (pixel, out_pixel) {
+        (ae::GenericPixel::Pixel8(pixel), ae::GenericPixelMut::Pixel8(out_pixel)) => {
+            *out_pixel = kernel.process(Rgba8::from(*pixel)).into();
+        }
+        (ae::GenericPixel::Pixel16(pixel), ae::GenericPixelMut::Pixel16(out_pixel)) => {
+            *out_pixel = kernel.process(Rgba16::from(*pixel)).into();
+        }
+        (ae::GenericPixel::PixelF32(pixel), ae::GenericPixelMut::PixelF32(out_pixel)) => {
+            *out_pixel = kernel.process(RgbaF32::from(*pixel)).into();
+        }
+        _ => return Err(ae::Error::BadCallbackParameter),
+    }
+    Ok(())
+}
+
+// レイヤー全体にカーネルを適用します。
+// AE ではレイヤーのバッファを直接 ImageView として参照し、帯ごとに並列に処理します。
+// Premiere はピクセルの並びが BGRA なので、これまで通り iterate_with で処理します。
+pub fn render_layer<K: PixelKernel>(
+    kernel: &K,
+    in_data: &ae::InData,
+    in_layer: &ae::Layer,
+    out_layer: &mut ae::Layer,
+) -> Result<(), ae::Error> {
+    let same_size =
+        in_layer.width() == out_layer.width() && in_layer.height() == out_layer.height();
+    if in_data.is_premiere() || !same_size {
+        return iterate_layer(kernel, in_data, in_layer, out_layer);
+    }
+
+    let renderer = Renderer::new();
+    match in_layer.bit_depth() {
+        8 => renderer.render(kernel, &view::<Rgba8>(in_layer)?, view_mut(out_layer)?),
+        16 => renderer.render(kernel, &view::<Rgba16>(in_layer)?, view_mut(out_layer)?),
+        32 => renderer.render(kernel, &view::<RgbaF32>(in_layer)?, view_mut(out_layer)?),
+        _ => return Err(ae::Error::BadCallbackParameter),
+    }
+    Ok(())
+}
+
+fn iterate_layer<K: PixelKernel>(
+    kernel: &K,
+    in_data: &ae::InData,
+    in_layer: &ae::Layer,
+    out_layer: &mut ae::Layer,
+) -> Result<(), ae::Error> {
+    let progress_final = out_layer.height() as _;
+    copy_layer(in_data, in_layer, out_layer)?;
+    in_layer.iterate_with(
+        out_layer,
+        0,
+        progress_final,
+        None,
+        |_x: i32,
+         _y: i32,
+         pixel: ae::GenericPixel,
+         out_pixel: ae::GenericPixelMut|
+         -> Result<(), ae::Error> { process_generic(kernel, pixel, out_pixel) },
+    )?;
+    Ok(())
+}
+
+fn copy_layer(
+    in_data: &ae::InData,
+    in_layer: &ae::Layer,
+    out_layer: &mut ae::Layer,
+) -> Result<(), ae::Error> {
+    ae::pf::suites::WorldTransform::new()?.copy_hq(
+        in_data.effect_ref(),
+        in_layer,
+        out_layer,
+        None,
+        None,
+    )
+}
+
+// Rgba8/16/F32 は PF_Pixel/PF_Pixel16/PF_PixelFloat と同じメモリ配置 (pixel.rs で確認している)
+fn view<P: Pixel>(layer: &ae::Layer) -> Result<ImageView<'_, P>, ae::Error> {
+    let bytes = layer.buffer();
+    let (stride, len) = pixel_layout::<P>(layer, bytes.as_ptr(), bytes.len())?;
+    let data = unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const P, len) };
+    ImageView::new(layer.width(), layer.height(), stride, data)
+        .ok_or(ae::Error::BadCallbackParameter)
+}
+
+fn view_mut<P: Pixel>(layer: &mut ae::Layer) -> Result<ImageViewMut<'_, P>, ae::Error> {
+    let (width, height) = (layer.width(), layer.height());
+    let (ptr, byte_len) = {
+        let bytes = layer.buffer_mut();
+        (bytes.as_mut_ptr(), bytes.len())
+    };
+    let (stride, len) = pixel_layout::<P>(layer, ptr, byte_len)?;
+    let data = unsafe { std::slice::from_raw_parts_mut(ptr as *mut P, len) };
+    ImageViewMut::new(width, height, stride, data).ok_or(ae::Error::BadCallbackParameter)
+}
+
+// (1行あたりのピクセル数, バッファ全体のピクセル数) を返します。
+fn pixel_layout<P: Pixel>(
+    layer: &ae::Layer,
+    ptr: *const u8,
+    byte_len: usize,
+) -> Result<(usize, usize), ae::Error> {
+    let size = std::mem::size_of::<P>();
+    let row_bytes = layer.buffer_stride();
+    if row_bytes % size != 0 || ptr as usize % std::mem::align_of::<P>() != 0 {
+        return Err(ae::Error::BadCallbackParameter);
+    }
+    Ok((row_bytes / size, byte_len / size))
+}
+
+// エフェクトごとに違う部分 (パラメータ・カーネル・インスタンスの状態) だけをまとめたトレイトです。
+// コマンドの振り分け・SmartFX・Premiere 対応・シーケンスデータの保存は全エフェクト共通で行います。
+// プラグイン側では `libs::fs_effect!(Plugin, Params);` と書いてこのトレイトを実装します。
+pub trait FsEffect: Default {
+    type Params: Eq + PartialEq + Hash + Copy + Debug;
+    type Kernel: PixelKernel;
+    /// インスタンスごとにプロジェクトへ保存する状態。持たない場合は `()`
+    type Sequence: SequenceState;
+
+    /// About に表示する文字列
+    const ABOUT: &'static str;
+
+    fn params_setup(&self, params: &mut ae::Parameters<Self::Params>) -> Result<(), ae::Error>;
+
+    /// 現在のパラメータとインスタンスの状態から、このフレームを処理するカーネルを作ります。
+    fn kernel(
+        params: &ae::Parameters<Self::Params>,
+        sequence: &Self::Sequence,
+    ) -> Result<Self::Kernel, ae::Error>;
+
+    /// false を返すとカーネルを作らずに、入力をそのまま出力へコピーします (全体のオンオフ)。
+    fn enabled(_params: &ae::Parameters<Self::Params>) -> Result<bool, ae::Error> {
+        Ok(true)
+    }
+
+    /// SUPERVISE を付けたパラメータ (ボタンなど) が操作されたときに呼ばれます。
+    /// インスタンスの状態を変えて描画し直す必要があるときは true を返します。
+    fn user_changed_param(
+        _params: &ae::Parameters<Self::Params>,
+        _sequence: &mut Self::Sequence,
+        _param: Self::Params,
+    ) -> Result<bool, ae::Error> {
+        Ok(false)
+    }
+}
+
+// エフェクト全体で1回だけ処理するコマンドです。
+pub fn handle_command<E: FsEffect>(
+    _effect: &mut E,
+    cmd: ae::Command,
+    in_data: ae::InData,
+    mut out_data: ae::OutData,
+    _params: &mut ae::Parameters<E::Params>,
+) -> Result<(), ae::Error> {
+    match cmd {
+        ae::Command::About => {
+            out_data.set_return_msg(E::ABOUT);
+        }
+        ae::Command::GlobalSetup => {
+            global_setup(&in_data)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// シーケンスデータとして AE に渡すインスタンスです。
+// SequenceSetup/Resetup/Flatten は after_effects クレートが flatten/unflatten を呼んで処理します。
+pub struct Instance<E: FsEffect> {
+    pub state: E::Sequence,
+}
+
+impl<E: FsEffect> Default for Instance<E> {
+    fn default() -> Self {
+        Self {
+            state: E::Sequence::default(),
+        }
+    }
+}
+
+impl<E: FsEffect> Instance<E> {
+    pub fn flatten(&self) -> Result<(u16, Vec<u8>), ae::Error> {
+        sequence::flatten(&self.state).map_err(|e| {
+            log::error!("{e}");
+            ae::Error::InternalStructDamaged
+        })
+    }
+
+    // 読めないシーケンスデータでプロジェクトが開けなくならないよう、既定値で続けます。
+    pub fn unflatten(version: u16, bytes: &[u8]) -> Result<Self, ae::Error> {
+        Ok(Self {
+            state: sequence::unflatten_or_default(version, bytes),
+        })
+    }
+
+    // インスタンスごとのコマンド (描画・パラメータの操作) を処理します。
+    pub fn handle_command(
+        &mut self,
+        cmd: ae::Command,
+        in_data: &ae::InData,
+        out_data: &mut ae::OutData,
+        params: &mut ae::Parameters<E::Params>,
+    ) -> Result<(), ae::Error> {
+        match cmd {
+            ae::Command::Render {
+                in_layer,
+                out_layer,
+            } => {
+                legacy_render::<E>(&self.state, in_data, in_layer, out_layer, params)?;
+            }
+            ae::Command::SmartPreRender { extra } => {
+                smart_pre_render(in_data, extra)?;
+            }
+            ae::Command::SmartRender { extra } => {
+                smart_render::<E>(&self.state, in_data, extra, params)?;
+            }
+            ae::Command::UserChangedParam { param_index } => {
+                let param = params.type_at(param_index);
+                if E::user_changed_param(params, &mut self.state, param)? {
+                    out_data.set_force_rerender();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn global_setup(in_data: &ae::InData) -> Result<(), ae::Error> {
+    win_dbg_logger::DEBUGGER_LOGGER.set_force_log_without_debugger(true);
+    log::info!("GlobalSetup");
+    // For Premiere - declare supported pixel formats
+    if in_data.is_premiere() {
+        let suite = ae::pf::suites::PixelFormat::new()?;
+
+        // Add the pixel formats we support in order of preference.
+        suite.clear_supported_pixel_formats(in_data.effect_ref())?;
+        let formats = [
+            ae::pr::PixelFormat::Bgra4444_8u,
+            ae::pr::PixelFormat::Bgra4444_16u,
+            ae::pr::PixelFormat::Bgra4444_32f,
+        ];
+        for x in formats {
+            suite.add_supported_pixel_format(in_data.effect_ref(), x)?;
+        }
+    }
+    Ok(())
+}
+
+fn legacy_render<E: FsEffect>(
+    sequence: &E::Sequence,
+    in_data: &ae::InData,
+    in_layer: ae::Layer,
+    out_layer: ae::Layer,
+    params: &ae::Parameters<E::Params>,
+) -> Result<(), ae::Error> {
+    if !in_data.is_premiere() {
+        // We don't support non-SmartFX unless it's Premiere
+        return Err(ae::Error::BadCallbackParameter);
+    }
+
+    do_render::<E>(sequence, in_data, in_layer, out_layer, params)
+}
+
+fn smart_pre_render(in_data: &ae::InData, mut extra: ae::PreRenderExtra) -> Result<(), ae::Error> {
+    let req = extra.output_request();
+
+    if let Ok(in_result) = extra.callbacks().checkout_layer(
+        0,
+        0,
+        &req,
+        in_data.current_time(),
+        in_data.time_step(),
+        in_data.time_scale(),
+    ) {
+        let _ = extra.union_result_rect(in_result.result_rect.into());
+        let _ = extra.union_max_result_rect(in_result.max_result_rect.into());
+    }
+    Ok(())
+}
+
+fn smart_render<E: FsEffect>(
+    sequence: &E::Sequence,
+    in_data: &ae::InData,
+    extra: ae::SmartRenderExtra,
+    params: &ae::Parameters<E::Params>,
+) -> Result<(), ae::Error> {
+    let cb = extra.callbacks();
+    let Some(input_world) = cb.checkout_layer_pixels(0)? else {
+        return Ok(());
+    };
+
+    if let Ok(Some(output_world)) = cb.checkout_output() {
+        do_render::<E>(sequence, in_data, input_world, output_world, params)?;
+    }
+
+    cb.checkin_layer_pixels(0)?;
+    Ok(())
+}
+
+fn do_render<E: FsEffect>(
+    sequence: &E::Sequence,
+    in_data: &ae::InData,
+    in_layer: ae::Layer,
+    mut out_layer: ae::Layer,
+    params: &ae::Parameters<E::Params>,
+) -> Result<(), ae::Error> {
+    if !E::enabled(params)? {
+        return copy_layer(in_data, &in_layer, &mut out_layer);
+    }
+    let kernel = E::kernel(params, sequence)?;
+    render_layer(&kernel, in_data, &in_layer, &mut out_layer)
+}
+
+/// `ae::define_effect!` と、[`FsEffect`] に処理を任せる `AdobePluginGlobal`・
+/// `AdobePluginInstance` の実装を生成します。
+#[macro_export]
+macro_rules! fs_effect {
+    ($plugin:ty, $params:ty) => {
+        ::after_effects::define_effect!($plugin, $crate::host::Instance<$plugin>, $params);
+
+        impl AdobePluginGlobal for $plugin {
+            fn can_load(_host_name: &str, _host_version: &str) -> bool {
+                true
+            }
+
+            fn params_setup(
+                &self,
+                params: &mut ::after_effects::Parameters<$params>,
+                _in_data: ::after_effects::InData,
+                _: ::after_effects::OutData,
+            ) -> Result<(), ::after_effects::Error> {
+                <$plugin as $crate::host::FsEffect>::params_setup(self, params)
+            }
+
+            fn handle_command(
+                &mut self,
+                cmd: ::after_effects::Command,
+                in_data: ::after_effects::InData,
+                out_data: ::after_effects::OutData,
+                params: &mut ::after_effects::Parameters<$params>,
+            ) -> Result<(), ::after_effects::Error> {
+                $crate::host::handle_command(self, cmd, in_data, out_data, params)
+            }
+        }
+
+        impl AdobePluginInstance for $crate::host::Instance<$plugin> {
+            fn flatten(&self) -> Result<(u16, Vec<u8>), ::after_effects::Error> {
+                $crate::host::Instance::flatten(self)
+            }
+
+            fn unflatten(version: u16, bytes: &[u8]) -> Result<Self, ::after_effects::Error> {
+                $crate::host::Instance::unflatten(version, bytes)
+            }
+
+            fn handle_command(
+                &mut self,
+                plugin: &mut PluginState,
+                cmd: ::after_effects::Command,
+            ) -> Result<(), ::after_effects::Error> {
+                $crate::host::Instance::handle_command(
+                    self,
+                    cmd,
+                    &plugin.in_data,
+                    &mut plugin.out_data,
+                    &mut plugin.params,
+                )
+            }
+        }
+    };
+}
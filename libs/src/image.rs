@@ -0,0 +1,222 @@
+// ホストに依存しない画像バッファです。
+// stride は1行あたりのピクセル数で、AE の rowbytes のように width より大きくなることがあります。
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image<P> {
+    width: usize,
+    height: usize,
+    stride: usize,
+    data: Vec<P>,
+}
+
+impl<P: Copy + Default> Image<P> {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::filled(width, height, P::default())
+    }
+
+    pub fn filled(width: usize, height: usize, value: P) -> Self {
+        Self {
+            width,
+            height,
+            stride: width,
+            data: vec![value; width * height],
+        }
+    }
+}
+
+impl<P> Image<P> {
+    /// `data` は `stride * height` 個以上のピクセルを持っている必要があります。
+    pub fn from_vec(width: usize, height: usize, stride: usize, data: Vec<P>) -> Option<Self> {
+        if stride < width || data.len() < stride * height {
+            return None;
+        }
+        Some(Self {
+            width,
+            height,
+            stride,
+            data,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&P> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.data.get(y * self.stride + x)
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut P> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.data.get_mut(y * self.stride + x)
+    }
+
+    pub fn row(&self, y: usize) -> &[P] {
+        let start = y * self.stride;
+        &self.data[start..start + self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [P] {
+        let start = y * self.stride;
+        &mut self.data[start..start + self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[P]> {
+        (0..self.height).map(move |y| self.row(y))
+    }
+
+    pub fn pixels(&self) -> impl Iterator<Item = &P> {
+        self.rows().flatten()
+    }
+
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = &mut P> {
+        let width = self.width;
+        let height = self.height;
+        self.data
+            .chunks_mut(self.stride.max(1))
+            .take(height)
+            .flat_map(move |row| row[..width].iter_mut())
+    }
+
+    pub fn into_vec(self) -> Vec<P> {
+        self.data
+    }
+}
+
+impl<P> Image<P> {
+    pub fn view(&self) -> ImageView<'_, P> {
+        ImageView {
+            width: self.width,
+            height: self.height,
+            stride: self.stride,
+            data: &self.data,
+        }
+    }
+
+    pub fn view_mut(&mut self) -> ImageViewMut<'_, P> {
+        ImageViewMut {
+            width: self.width,
+            height: self.height,
+            stride: self.stride,
+            data: &mut self.data,
+        }
+    }
+}
+
+// 他のバッファ (AE のレイヤーなど) を Image と同じ形で参照するためのビューです。
+// 最後の行は width 個あれば足り、stride の分まで無くても構いません。
+#[derive(Clone, Copy, Debug)]
+pub struct ImageView<'a, P> {
+    width: usize,
+    height: usize,
+    stride: usize,
+    data: &'a [P],
+}
+
+impl<'a, P> ImageView<'a, P> {
+    pub fn new(width: usize, height: usize, stride: usize, data: &'a [P]) -> Option<Self> {
+        if !fits(width, height, stride, data.len()) {
+            return None;
+        }
+        Some(Self {
+            width,
+            height,
+            stride,
+            data,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn row(&self, y: usize) -> &'a [P] {
+        let start = y * self.stride;
+        &self.data[start..start + self.width]
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageViewMut<'a, P> {
+    width: usize,
+    height: usize,
+    stride: usize,
+    data: &'a mut [P],
+}
+
+impl<'a, P> ImageViewMut<'a, P> {
+    pub fn new(width: usize, height: usize, stride: usize, data: &'a mut [P]) -> Option<Self> {
+        if !fits(width, height, stride, data.len()) {
+            return None;
+        }
+        Some(Self {
+            width,
+            height,
+            stride,
+            data,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [P] {
+        let start = y * self.stride;
+        &mut self.data[start..start + self.width]
+    }
+
+    /// 先頭の `rows` 行とそれ以降に分けます。並列処理で帯ごとに渡すために使います。
+    pub fn split_rows(self, rows: usize) -> (Self, Self) {
+        let rows = rows.min(self.height);
+        let mid = (rows * self.stride).min(self.data.len());
+        let (top, bottom) = self.data.split_at_mut(mid);
+        (
+            Self {
+                width: self.width,
+                height: rows,
+                stride: self.stride,
+                data: top,
+            },
+            Self {
+                width: self.width,
+                height: self.height - rows,
+                stride: self.stride,
+                data: bottom,
+            },
+        )
+    }
+}
+
+fn fits(width: usize, height: usize, stride: usize, len: usize) -> bool {
+    stride >= width && (height == 0 || len >= stride * (height - 1) + width)
+}
@@ -0,0 +1,505 @@
+// ref: https://github.com/bryful/F-s-PluginsProjects/tree/master/ColorChange
+
+use serde::{Deserialize, Serialize};
+
+use super::PixelKernel;
+use crate::color::{lab_to_rgb, luma, oklab_to_rgb, rgb_to_lab, rgb_to_oklab};
+use crate::color_index::ColorIndex;
+use crate::metric::{alpha_weight, ColorMetric, MetricColor};
+use crate::pixel::{Channel, Pixel, Rgba8};
+
+popup_enum! {
+    /// 1つのピクセルに複数のペアが一致したときの扱いです。
+    pub enum MatchMode ("match mode") {
+        /// 一致したペアを順に重ね、後のペアほど優先される (元の実装と同じ)
+        #[default]
+        LastMatch("last", "Last Match Wins"),
+        /// 最初に一致したペアだけで置き換える
+        FirstMatch("first", "First Match Wins"),
+        /// 変更前の色が一番近いペアだけで置き換える。同じ距離なら後のペア
+        Nearest("nearest", "Nearest Source Wins"),
+        /// ペアを順に適用し、各ペアは前のペアで置き換えた後の色と比べる
+        Chained("chained", "Chained"),
+    }
+}
+
+popup_enum! {
+    /// 一致したピクセルを変更後の色へどう置き換えるかです。
+    /// Replace 以外は、ピクセルと変更前の色の差を変更後の色に移して、アンチエイリアスや塗りのグラデーションを残します。
+    pub enum Transfer ("transfer") {
+        /// 変更後の色で塗りつぶす (元の実装と同じ)
+        #[default]
+        Replace("replace", "Replace"),
+        /// RGB の差をそのまま足す
+        RgbOffset("rgb-offset", "RGB Offset"),
+        /// 明るさ (luma) の差だけを足す
+        Luminance("luminance", "Preserve Luminance"),
+        /// Lab で明るさの差を足し、色相の回転と彩度の比を移す
+        LabShift("lab", "Lab Hue/Sat Shift"),
+        /// OKLab で LabShift と同じことをする
+        OklabShift("oklab", "OKLab Hue/Sat Shift"),
+    }
+}
+
+impl Transfer {
+    // 差を取る色空間に変換します。
+    fn to_space(self, [r, g, b]: [f32; 3]) -> [f32; 3] {
+        match self {
+            Transfer::LabShift => rgb_to_lab(r, g, b),
+            Transfer::OklabShift => rgb_to_oklab(r, g, b),
+            _ => [r, g, b],
+        }
+    }
+
+    fn to_rgb(self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        match self {
+            Transfer::LabShift => lab_to_rgb(x, y, z),
+            Transfer::OklabShift => oklab_to_rgb(x, y, z),
+            _ => [x, y, z],
+        }
+    }
+
+    // 変更前の色の彩度がこれより低いと色相が決まらないので、比ではなく差を移す
+    fn min_chroma(self) -> f32 {
+        match self {
+            Transfer::LabShift => 2.0,
+            _ => 0.005,
+        }
+    }
+}
+
+/// ペアごとに全体の設定を上書きする値です。None は全体の値を使います。
+/// 許容誤差とぼかし幅は全体と同じ正規化した単位 (0.0 - 1.0) です。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PairOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub softness: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<ColorMetric>,
+    /// 色に加えてアルファもこの値 (8bit) と比べる。None はアルファを見ない
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub src_alpha: Option<u8>,
+    /// 置き換えた色のアルファ (8bit)。None はアルファを変えない
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dst_alpha: Option<u8>,
+}
+
+impl PairOverride {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// 距離の測り方はいくつまで混ざるか
+const MAX_GROUPS: usize = ColorMetric::ALL.len();
+
+/// 一致の確認表示で、ペアごとに塗る色です。ペアの番号 ([`ColorChange::with_preview_slots`]) で順に使い、
+/// 足りなければ先頭に戻ります。
+pub const PREVIEW_COLORS: [Rgba8; 8] = [
+    Rgba8::new(255, 0, 0, 255),
+    Rgba8::new(0, 255, 0, 255),
+    Rgba8::new(0, 64, 255, 255),
+    Rgba8::new(255, 255, 0, 255),
+    Rgba8::new(0, 255, 255, 255),
+    Rgba8::new(255, 0, 255, 255),
+    Rgba8::new(255, 128, 0, 255),
+    Rgba8::new(128, 0, 255, 255),
+];
+
+// 確認表示で一致した色をペアの色にどれだけ寄せるか
+const PREVIEW_TINT: f32 = 0.6;
+// 確認表示で一致しなかった色に残す明るさ
+const PREVIEW_DIM: f32 = 0.25;
+
+pub struct ColorChange {
+    tolerance: f32,
+    softness: f32,
+    metric: ColorMetric,
+    mode: MatchMode,
+    transfer: Transfer,
+    preview: bool,
+    /// 確認表示でペアの色を選ぶ番号。足りない分はペアの添字を使う
+    preview_slots: Vec<usize>,
+    unpremultiply: bool,
+    pairs: Vec<(Rgba8, Rgba8)>,
+    overrides: Vec<PairOverride>,
+    sources: Vec<Source>,
+    groups: Vec<Group>,
+}
+
+// 上書きを反映したペアごとの設定
+struct Source {
+    key: MetricColor,
+    dst: Rgba8,
+    /// Transfer の色空間での変更前と変更後の色
+    src_t: [f32; 3],
+    dst_t: [f32; 3],
+    /// LabShift/OklabShift で (a, b) に掛ける複素数 (dst / src)。彩度が低いときは None
+    ratio: Option<[f32; 2]>,
+    src_alpha: Option<u8>,
+    dst_alpha: Option<u8>,
+    tolerance: f32,
+    softness: f32,
+    group: usize,
+}
+
+// 同じ測り方のペアをまとめた索引
+struct Group {
+    metric: ColorMetric,
+    /// 索引の添字 => ペアの添字 (昇順)
+    members: Vec<usize>,
+    index: ColorIndex,
+}
+
+impl ColorChange {
+    /// `tolerance` は正規化した許容誤差 (0.0 - 1.0)、`pairs` は (変更前の色, 変更後の色) です。
+    pub fn new(tolerance: f32, metric: ColorMetric, pairs: Vec<(Rgba8, Rgba8)>) -> Self {
+        let mut kernel = Self {
+            tolerance,
+            softness: 0.0,
+            metric,
+            mode: MatchMode::default(),
+            transfer: Transfer::default(),
+            preview: false,
+            preview_slots: Vec::new(),
+            unpremultiply: false,
+            pairs,
+            overrides: Vec::new(),
+            sources: Vec::new(),
+            groups: Vec::new(),
+        };
+        kernel.rebuild();
+        kernel
+    }
+
+    /// 許容誤差の外側で少しずつ置き換える幅 (正規化した単位) を設定します。
+    pub fn with_softness(mut self, softness: f32) -> Self {
+        self.softness = softness;
+        self.rebuild();
+        self
+    }
+
+    pub fn with_mode(mut self, mode: MatchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_transfer(mut self, transfer: Transfer) -> Self {
+        self.transfer = transfer;
+        self.rebuild();
+        self
+    }
+
+    /// 置き換える代わりに、一致したピクセルを [`PREVIEW_COLORS`] の色に寄せ、
+    /// 一致しなかったピクセルを暗くして表示します。色は MatchMode で選ばれたペアのものです。
+    pub fn with_preview(mut self, preview: bool) -> Self {
+        self.preview = preview;
+        self
+    }
+
+    /// 確認表示でペアの色を選ぶ番号を設定します。`slots[i]` が `i` 番目のペアの番号で、足りない分はペアの添字です。
+    /// 無効な組を除いてペアを渡すときは、元の組の番号を渡すと、組を切り替えても色が変わりません。
+    pub fn with_preview_slots(mut self, slots: Vec<usize>) -> Self {
+        self.preview_slots = slots;
+        self
+    }
+
+    /// 比べる前に色をアルファで割り (unpremultiply)、置き換えた後にアルファを掛け直します。
+    /// 半透明の縁のピクセルも、不透明な部分と同じ色として一致するようになります。
+    pub fn with_unpremultiply(mut self, unpremultiply: bool) -> Self {
+        self.unpremultiply = unpremultiply;
+        self
+    }
+
+    /// ペアごとの上書きを設定します。`overrides[i]` が `i` 番目のペアに対応し、足りない分は上書きしません。
+    pub fn with_pair_overrides(mut self, overrides: Vec<PairOverride>) -> Self {
+        self.overrides = overrides;
+        self.rebuild();
+        self
+    }
+
+    fn rebuild(&mut self) {
+        let mut groups: Vec<(ColorMetric, Vec<usize>, f32)> = Vec::new();
+        self.sources = self
+            .pairs
+            .iter()
+            .enumerate()
+            .map(|(i, &(src, dst))| {
+                let o = self.overrides.get(i).copied().unwrap_or_default();
+                let metric = o.metric.unwrap_or(self.metric);
+                let tolerance = o.tolerance.unwrap_or(self.tolerance);
+                let softness = o.softness.unwrap_or(self.softness);
+                let group = match groups.iter().position(|g| g.0 == metric) {
+                    Some(g) => g,
+                    None => {
+                        groups.push((metric, Vec::new(), 0.0));
+                        groups.len() - 1
+                    }
+                };
+                groups[group].1.push(i);
+                groups[group].2 = groups[group].2.max(tolerance + softness);
+                let src_t = self.transfer.to_space(normalized(src));
+                let dst_t = self.transfer.to_space(normalized(dst));
+                Source {
+                    key: metric.prepare(src),
+                    dst,
+                    src_t,
+                    dst_t,
+                    ratio: chroma_ratio(src_t, dst_t, self.transfer.min_chroma()),
+                    src_alpha: o.src_alpha,
+                    dst_alpha: o.dst_alpha,
+                    tolerance,
+                    softness,
+                    group,
+                }
+            })
+            .collect();
+        self.groups = groups
+            .into_iter()
+            .map(|(metric, members, reach)| {
+                let keys: Vec<_> = members.iter().map(|&i| self.sources[i].key).collect();
+                Group {
+                    metric,
+                    index: ColorIndex::new(metric, &keys, reach),
+                    members,
+                }
+            })
+            .collect();
+    }
+
+    // 一致しうるペアのうち、添字が `from` 以上のものを昇順で返します。
+    // 測り方ごとの候補はどれも昇順なので、並べ替えずに混ぜ合わせます。
+    fn candidates<P: Pixel>(&self, pixel: &P, from: usize) -> Candidates<'_> {
+        let mut lists = [(&[][..], &[][..]); MAX_GROUPS];
+        for (list, g) in lists.iter_mut().zip(&self.groups) {
+            let found = g.index.candidates(g.metric, pixel);
+            let start = found.partition_point(|&i| g.members[i] < from);
+            *list = (&found[start..], g.members.as_slice());
+        }
+        Candidates {
+            lists,
+            len: self.groups.len(),
+        }
+    }
+
+    // 測り方ごとの座標
+    fn coords<P: Pixel>(&self, pixel: &P) -> [[f32; 3]; MAX_GROUPS] {
+        let mut coords = [[0.0; 3]; MAX_GROUPS];
+        for (c, g) in coords.iter_mut().zip(&self.groups) {
+            *c = g.metric.coords(pixel);
+        }
+        coords
+    }
+
+    fn weight<P: Pixel>(&self, pixel: &P, coords: &[[f32; 3]; MAX_GROUPS], i: usize) -> f32 {
+        let s = &self.sources[i];
+        let w = self.groups[s.group].metric.weight_at(
+            pixel,
+            coords[s.group],
+            &s.key,
+            s.tolerance,
+            s.softness,
+        );
+        match s.src_alpha {
+            Some(a) if w > 0.0 => w * alpha_weight(pixel, a, s.tolerance, s.softness),
+            _ => w,
+        }
+    }
+
+    fn distance(&self, coords: &[[f32; 3]; MAX_GROUPS], i: usize) -> f32 {
+        let s = &self.sources[i];
+        self.groups[s.group]
+            .metric
+            .distance_at(coords[s.group], &s.key)
+    }
+
+    // out を、pixel を i 番目のペアで置き換えた色へ w の割合で寄せます。
+    fn replace<P: Pixel>(&self, out: P, pixel: &P, i: usize, w: f32) -> P {
+        let s = &self.sources[i];
+        let out_alpha = match s.dst_alpha {
+            Some(a) => out.with_alpha(P::Channel::from_u8(a)),
+            None => out,
+        };
+        if self.transfer == Transfer::Replace {
+            let d = P::from_rgba8(s.dst);
+            return out.lerp(out_alpha.with_rgb(d.red(), d.green(), d.blue()), w);
+        }
+
+        let n = pixel.to_normalized();
+        let c = self.transfer.to_space([n.red, n.green, n.blue]);
+        let (src, dst) = (s.src_t, s.dst_t);
+        let moved = match self.transfer {
+            Transfer::Luminance => {
+                let dy = luma(c[0], c[1], c[2]) - luma(src[0], src[1], src[2]);
+                dst.map(|v| v + dy)
+            }
+            Transfer::LabShift | Transfer::OklabShift => {
+                let [a, b] = match s.ratio {
+                    Some([re, im]) => [c[1] * re - c[2] * im, c[1] * im + c[2] * re],
+                    None => [c[1] - src[1] + dst[1], c[2] - src[2] + dst[2]],
+                };
+                [c[0] - src[0] + dst[0], a, b]
+            }
+            _ => [0, 1, 2].map(|k| c[k] - src[k] + dst[k]),
+        };
+        let [r, g, b] = self.transfer.to_rgb(moved).map(|v| v.max(0.0));
+        let to = out_alpha.with_rgb(
+            P::Channel::from_f32(r),
+            P::Channel::from_f32(g),
+            P::Channel::from_f32(b),
+        );
+        out.lerp(to, w)
+    }
+
+    // 置き換えに使ったペアとその重みを hit に渡しながら処理します。
+    fn apply<P: Pixel>(&self, pixel: P, mut hit: impl FnMut(usize, f32)) -> P {
+        if self.mode == MatchMode::Chained {
+            return self.process_chained(pixel, hit);
+        }
+
+        // 索引から一致しうるペアだけを元の順番で受け取る
+        let mut candidates = self.candidates(&pixel, 0).peekable();
+        if candidates.peek().is_none() {
+            return pixel;
+        }
+        let coords = self.coords(&pixel);
+        let found = match self.mode {
+            MatchMode::FirstMatch => candidates
+                .map(|i| (i, self.weight(&pixel, &coords, i)))
+                .find(|&(_, w)| w > 0.0),
+            MatchMode::Nearest => {
+                let mut best: Option<(f32, usize, f32)> = None;
+                for i in candidates {
+                    let w = self.weight(&pixel, &coords, i);
+                    if w <= 0.0 {
+                        continue;
+                    }
+                    let d = self.distance(&coords, i);
+                    if best.is_none_or(|(best_d, _, _)| d <= best_d) {
+                        best = Some((d, i, w));
+                    }
+                }
+                best.map(|(_, i, w)| (i, w))
+            }
+            _ => {
+                let mut out = pixel;
+                // 後ろのペアほど優先される(元の実装と同じ)
+                for i in candidates {
+                    let w = self.weight(&pixel, &coords, i);
+                    if w > 0.0 {
+                        hit(i, w);
+                        out = self.replace(out, &pixel, i, w);
+                    }
+                }
+                return out;
+            }
+        };
+        found.map_or(pixel, |(i, w)| {
+            hit(i, w);
+            self.replace(pixel, &pixel, i, w)
+        })
+    }
+
+    fn process_chained<P: Pixel>(&self, pixel: P, mut hit: impl FnMut(usize, f32)) -> P {
+        let mut out = pixel;
+        let mut coords = self.coords(&out);
+        // 次に調べるペアの添字。色が変わったら候補を引き直して、その続きから調べる
+        let mut next = 0;
+        'outer: loop {
+            for i in self.candidates(&out, next) {
+                next = i + 1;
+                let w = self.weight(&out, &coords, i);
+                if w > 0.0 {
+                    hit(i, w);
+                    out = self.replace(out, &out, i, w);
+                    coords = self.coords(&out);
+                    continue 'outer;
+                }
+            }
+            break;
+        }
+        out
+    }
+}
+
+// 測り方ごとの (索引の候補, 索引の添字 => ペアの添字) を、ペアの添字の昇順で取り出します。
+struct Candidates<'a> {
+    lists: [(&'a [usize], &'a [usize]); MAX_GROUPS],
+    len: usize,
+}
+
+impl Iterator for Candidates<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let lists = &mut self.lists[..self.len];
+        let (list, members) = lists
+            .iter_mut()
+            .filter(|(found, _)| !found.is_empty())
+            .min_by_key(|(found, members)| members[found[0]])?;
+        let i = members[list[0]];
+        *list = &list[1..];
+        Some(i)
+    }
+}
+
+fn normalized(c: Rgba8) -> [f32; 3] {
+    let n = c.to_normalized();
+    [n.red, n.green, n.blue]
+}
+
+// (a, b) を複素数とみなした dst / src。掛けると色相が回転し、彩度が dst / src 倍になる
+fn chroma_ratio(src: [f32; 3], dst: [f32; 3], min_chroma: f32) -> Option<[f32; 2]> {
+    let norm = src[1] * src[1] + src[2] * src[2];
+    if norm < min_chroma * min_chroma {
+        return None;
+    }
+    Some([
+        (dst[1] * src[1] + dst[2] * src[2]) / norm,
+        (dst[2] * src[1] - dst[1] * src[2]) / norm,
+    ])
+}
+
+impl PixelKernel for ColorChange {
+    fn process<P: Pixel>(&self, pixel: P) -> P {
+        if !self.unpremultiply {
+            return self.recolor(pixel);
+        }
+        let straight = pixel.unpremultiply();
+        let out = self.recolor(straight);
+        // 置き換えなかったピクセルは掛け直しの誤差が出ないように元のまま返す
+        if out == straight {
+            return pixel;
+        }
+        out.premultiply()
+    }
+}
+
+impl ColorChange {
+    fn recolor<P: Pixel>(&self, pixel: P) -> P {
+        if !self.preview {
+            return self.apply(pixel, |_, _| {});
+        }
+
+        // 最後に選ばれたペアの色を、一番大きい重みで塗る
+        let mut found: Option<(usize, f32)> = None;
+        self.apply(pixel, |i, w| {
+            let w = found.map_or(w, |(_, prev)| prev.max(w));
+            found = Some((i, w));
+        });
+        let black = P::from_rgba8(Rgba8::new(0, 0, 0, 255));
+        let dim = pixel.lerp(
+            pixel.with_rgb(black.red(), black.green(), black.blue()),
+            1.0 - PREVIEW_DIM,
+        );
+        let Some((i, w)) = found else {
+            return dim;
+        };
+        let slot = self.preview_slots.get(i).copied().unwrap_or(i);
+        let c = P::from_rgba8(PREVIEW_COLORS[slot % PREVIEW_COLORS.len()]);
+        let tint = pixel.lerp(pixel.with_rgb(c.red(), c.green(), c.blue()), PREVIEW_TINT);
+        dim.lerp(tint, w)
+    }
+}
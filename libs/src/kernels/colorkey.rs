@@ -0,0 +1,293 @@
+use serde::{Deserialize, Serialize};
+
+use super::PixelKernel;
+use crate::metric::{ColorMetric, MetricColor};
+use crate::pixel::{Channel, Pixel, Rgba8};
+
+pub struct ColorKey {
+    tolerance: f32,
+    softness: f32,
+    metric: ColorMetric,
+    keys: Vec<Key>,
+    back_color: Rgba8,
+    keyer: Option<Keyer>,
+    view: ViewMode,
+    unpremultiply: bool,
+}
+
+popup_enum! {
+    /// 結果をどう表示するかです。Composite 以外は許容誤差を調整するための確認用です。
+    pub enum ViewMode ("view mode") {
+        /// キーを抜いた結果 (元の実装と同じ)
+        #[default]
+        Composite("composite", "Composite"),
+        /// 結果のアルファを不透明なグレースケールで表示する
+        Matte("matte", "Matte"),
+        /// 不透明を白、透明を黒、半透明を灰色で表示する
+        Status("status", "Status"),
+        /// 元の画像のうち、抜いた分だけ [`OVERLAY_COLOR`] に寄せて表示する
+        Overlay("overlay", "Key Overlay"),
+    }
+}
+
+/// Overlay で抜いたピクセルに重ねる色です。
+pub const OVERLAY_COLOR: Rgba8 = Rgba8::new(255, 0, 0, 255);
+
+// 完全に抜いたピクセルを OVERLAY_COLOR にどれだけ寄せるか
+const OVERLAY_TINT: f32 = 0.6;
+
+// Status で半透明を表す灰色
+const STATUS_PARTIAL: f32 = 0.5;
+
+/// 抜く色と、その色だけの許容誤差です。
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyColor {
+    #[serde(with = "crate::palette::hex")]
+    pub color: Rgba8,
+    /// None なら全体の許容誤差を使う。キーヤーでは inner をこの値にする (outer との幅はそのまま)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f32>,
+}
+
+impl KeyColor {
+    pub fn new(color: Rgba8) -> Self {
+        Self {
+            color,
+            tolerance: None,
+        }
+    }
+}
+
+struct Key {
+    color: MetricColor,
+    tolerance: Option<f32>,
+    /// デスピルで取り除くキー色の色味の向き。灰色のキーでは None
+    spill_axis: Option<[f32; 3]>,
+}
+
+impl Key {
+    fn new(metric: ColorMetric, key: KeyColor) -> Self {
+        Self {
+            color: metric.prepare(key.color),
+            tolerance: key.tolerance,
+            spill_axis: spill_axis(key.color),
+        }
+    }
+}
+
+/// 2値のキーの代わりに、キー色からの距離でアルファを作るキーヤーの設定です。
+/// 距離と許容誤差は [`ColorMetric`] の正規化した単位です。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Keyer {
+    /// キー色からこの距離までは完全に透明
+    pub inner: f32,
+    /// キー色からこの距離より遠いと不透明。inner との間はアルファが直線で変化する
+    pub outer: f32,
+    /// 残ったピクセルからキー色の色味を取り除く強さ (0.0 - 1.0)
+    pub despill: f32,
+    /// マットを縮める (正) / 広げる (負) 量 (-1.0 - 1.0)。
+    /// 透明と不透明の部分はそのままで、半透明の部分だけを透明側・不透明側へ寄せる
+    pub choke: f32,
+    /// マットの変化の両端を滑らかにする量 (0.0 で直線、1.0 で smoothstep)
+    pub softness: f32,
+}
+
+impl Keyer {
+    /// 距離から作ったマット (0.0 が透明) に choke と softness を適用します。
+    pub fn adjust_matte(&self, matte: f32) -> f32 {
+        let c = self.choke.clamp(-1.0, 1.0);
+        let m = if c > 0.0 {
+            if c >= 1.0 {
+                (matte >= 1.0) as u8 as f32
+            } else {
+                (matte - c) / (1.0 - c)
+            }
+        } else if c < 0.0 {
+            if c <= -1.0 {
+                (matte > 0.0) as u8 as f32
+            } else {
+                matte / (1.0 + c)
+            }
+        } else {
+            matte
+        };
+        let m = m.clamp(0.0, 1.0);
+        let smooth = m * m * (3.0 - 2.0 * m);
+        m + (smooth - m) * self.softness.clamp(0.0, 1.0)
+    }
+}
+
+impl ColorKey {
+    /// `tolerance` は正規化した許容誤差 (0.0 - 1.0) です。
+    pub fn new(tolerance: f32, metric: ColorMetric, key_color: Rgba8, back_color: Rgba8) -> Self {
+        Self {
+            tolerance,
+            softness: 0.0,
+            metric,
+            keys: vec![Key::new(metric, KeyColor::new(key_color))],
+            back_color,
+            keyer: None,
+            view: ViewMode::Composite,
+            unpremultiply: false,
+        }
+    }
+
+    /// 許容誤差の外側で少しずつ透明にする幅 (正規化した単位) を設定します。
+    pub fn with_softness(mut self, softness: f32) -> Self {
+        self.softness = softness;
+        self
+    }
+
+    /// 抜く色を `keys` に置き換えます (`new` の `key_color` も含めて全部)。
+    /// 1回でどれかの色に近いピクセルを抜き、アルファは色ごとのアルファの一番小さい値になります。
+    /// `keys` が空のときは `new` の色のままです。
+    pub fn with_keys(mut self, keys: &[KeyColor]) -> Self {
+        if !keys.is_empty() {
+            self.keys = keys.iter().map(|&k| Key::new(self.metric, k)).collect();
+        }
+        self
+    }
+
+    /// キーヤーを使います。`tolerance` と `softness` の代わりに `keyer` の設定でアルファを作り、
+    /// 残ったピクセルの色は背景色にせず、デスピルだけを適用します。
+    pub fn with_keyer(mut self, keyer: Option<Keyer>) -> Self {
+        self.keyer = keyer;
+        self
+    }
+
+    /// 結果の代わりにマットや抜いた範囲を表示します。
+    pub fn with_view(mut self, view: ViewMode) -> Self {
+        self.view = view;
+        self
+    }
+
+    /// 色をアルファで割ってから (unpremultiply) 抜き、結果にアルファを掛け直します。
+    /// プリマルチプライされた入力でも、色がアルファを超えない正しい出力になります。
+    pub fn with_unpremultiply(mut self, unpremultiply: bool) -> Self {
+        self.unpremultiply = unpremultiply;
+        self
+    }
+
+    fn keyed<P: Pixel>(&self, pixel: P, w: f32) -> P {
+        let b = P::from_rgba8(self.back_color);
+        pixel.lerp(P::new(b.red(), b.green(), b.blue(), P::Channel::ZERO), w)
+    }
+
+    fn soft_key<P: Pixel>(&self, pixel: P, keyer: &Keyer) -> P {
+        let width = (keyer.outer - keyer.inner).max(0.0);
+        let c = self.metric.coords(&pixel);
+        // 一番透明になるキー色と、デスピルに使う一番近いキー色
+        let mut matte = 1.0f32;
+        let (mut nearest, mut spill_axis) = (f32::INFINITY, None);
+        for key in &self.keys {
+            let inner = key.tolerance.unwrap_or(keyer.inner);
+            let w = self.metric.weight_at(&pixel, c, &key.color, inner, width);
+            matte = matte.min(1.0 - w);
+            let d = self.metric.distance_at(c, &key.color) - inner;
+            if d < nearest {
+                (nearest, spill_axis) = (d, key.spill_axis);
+            }
+        }
+        let matte = keyer.adjust_matte(matte);
+        if matte <= 0.0 {
+            return self.keyed(pixel, 1.0);
+        }
+        let out = match spill_axis {
+            Some(axis) if keyer.despill > 0.0 => despill(pixel, axis, keyer.despill),
+            _ => pixel,
+        };
+        let alpha = P::Channel::from_f32(pixel.alpha().to_f32() * matte);
+        out.with_alpha(alpha)
+    }
+}
+
+impl PixelKernel for ColorKey {
+    fn process<P: Pixel>(&self, pixel: P) -> P {
+        let out = self.composite(pixel);
+        let alpha = out.alpha().to_f32();
+        let gray = |v: f32| {
+            let c = P::Channel::from_f32(v);
+            P::new(c, c, c, P::Channel::MAX)
+        };
+        match self.view {
+            ViewMode::Composite => out,
+            ViewMode::Matte => gray(alpha),
+            ViewMode::Status if alpha >= 1.0 => gray(1.0),
+            ViewMode::Status if alpha <= 0.0 => gray(0.0),
+            ViewMode::Status => gray(STATUS_PARTIAL),
+            ViewMode::Overlay => {
+                // 元のアルファに対して、どれだけ透明にしたか
+                let before = pixel.alpha().to_f32();
+                if before <= 0.0 || alpha >= before {
+                    return pixel;
+                }
+                let c = P::from_rgba8(OVERLAY_COLOR);
+                let tint = pixel.with_rgb(c.red(), c.green(), c.blue());
+                pixel.lerp(tint, OVERLAY_TINT * (1.0 - alpha / before))
+            }
+        }
+    }
+}
+
+impl ColorKey {
+    fn composite<P: Pixel>(&self, pixel: P) -> P {
+        if !self.unpremultiply {
+            return self.key(pixel);
+        }
+        let straight = pixel.unpremultiply();
+        let out = self.key(straight);
+        // 抜かなかったピクセルは掛け直しの誤差が出ないように元のまま返す
+        if out == straight {
+            return pixel;
+        }
+        out.premultiply()
+    }
+
+    fn key<P: Pixel>(&self, pixel: P) -> P {
+        if let Some(keyer) = &self.keyer {
+            return self.soft_key(pixel, keyer);
+        }
+        let c = self.metric.coords(&pixel);
+        let mut w = 0.0f32;
+        for key in &self.keys {
+            let tolerance = key.tolerance.unwrap_or(self.tolerance);
+            w = w.max(
+                self.metric
+                    .weight_at(&pixel, c, &key.color, tolerance, self.softness),
+            );
+            if w >= 1.0 {
+                break;
+            }
+        }
+        if w <= 0.0 {
+            return pixel;
+        }
+        self.keyed(pixel, w)
+    }
+}
+
+// 平均を引いた色 (色味) の向きを正規化したもの。灰色には色味が無いので None
+fn spill_axis(key: Rgba8) -> Option<[f32; 3]> {
+    let n = key.to_normalized();
+    let mean = (n.red + n.green + n.blue) / 3.0;
+    let v = [n.red - mean, n.green - mean, n.blue - mean];
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    (len > 1.0e-3).then(|| v.map(|x| x / len))
+}
+
+// 色味のうちキー色の向きの成分を `amount` の割合で取り除きます。
+// 向きの成分の合計は 0 なので、RGB の平均 (明るさ) は変わりません。
+fn despill<P: Pixel>(pixel: P, axis: [f32; 3], amount: f32) -> P {
+    let n = pixel.to_normalized();
+    let rgb = [n.red, n.green, n.blue];
+    let spill: f32 = rgb.iter().zip(axis).map(|(c, a)| c * a).sum();
+    if spill <= 0.0 {
+        return pixel;
+    }
+    let [r, g, b] = [0, 1, 2].map(|i| (rgb[i] - axis[i] * spill * amount.min(1.0)).max(0.0));
+    pixel.with_rgb(
+        P::Channel::from_f32(r),
+        P::Channel::from_f32(g),
+        P::Channel::from_f32(b),
+    )
+}
@@ -0,0 +1,142 @@
+// ref: https://github.com/bryful/F-s-PluginsProjects/tree/master/CreateAlpha
+
+use super::PixelKernel;
+use crate::pixel::{Channel, Pixel, Rgba8};
+
+popup_enum! {
+    /// アルファの作り方です。
+    pub enum AlphaMode ("alpha mode") {
+        /// 黒背景を前提に、RGB の最大値をアルファにして色を戻す (元の実装と同じ)
+        #[default]
+        Black("black", "Unmultiply Black"),
+        /// 白背景 (白い紙) から色を戻す
+        White("white", "Unmultiply White"),
+        /// 背景色から色を戻す
+        Color("color", "Unmultiply Color"),
+        /// Rec. 601 の係数の明るさをアルファにする
+        Luma601("luma601", "Luminance (Rec.601)"),
+        /// Rec. 709 の係数の明るさをアルファにする
+        Luma709("luma709", "Luminance (Rec.709)"),
+    }
+}
+
+const REC601: [f32; 3] = [0.299, 0.587, 0.114];
+const REC709: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// 背景の上に描かれた絵から、背景を取り除いてアルファを作ります。
+/// 出力を背景の上に合成すると、(ビット深度の丸めの差を除いて) 入力に戻ります。
+/// 半透明の入力は、先に背景の上に合成してから処理します。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CreateAlpha {
+    mode: AlphaMode,
+    background: Rgba8,
+}
+
+impl CreateAlpha {
+    /// `background` は [`AlphaMode::Color`] のときだけ使います。
+    pub fn new(mode: AlphaMode, background: Rgba8) -> Self {
+        Self { mode, background }
+    }
+
+    // 8/16bit は Channel の整数の演算 (libs::fixed) だけで計算する
+    fn unmultiply<P: Pixel>(&self, pixel: P, back: [P::Channel; 3]) -> P {
+        let c = composite(pixel, back);
+        // 色を 0 - MAX に収めるのに必要な一番小さいアルファ
+        let a = (0..3)
+            .map(|i| {
+                let (c, b) = (c[i], back[i]);
+                if c > b {
+                    // 背景が MAX なら div_alpha は 0 を返す
+                    c.sub_clamped(b).div_alpha(P::MAX.sub_clamped(b))
+                } else if c < b {
+                    b.sub_clamped(c).div_alpha(b)
+                } else {
+                    P::Channel::ZERO
+                }
+            })
+            .fold(P::Channel::ZERO, max_channel);
+        let a = min_channel(a, P::MAX);
+        if a <= P::Channel::ZERO {
+            return P::new(back[0], back[1], back[2], a);
+        }
+        // 丸めたアルファで色を戻すので、合成したときの誤差が小さい
+        let [r, g, b] = [0, 1, 2].map(|i| {
+            let (c, b) = (c[i], back[i]);
+            if c >= b {
+                b.add_clamped(c.sub_clamped(b).div_alpha(a))
+            } else {
+                b.sub_clamped(b.sub_clamped(c).div_alpha(a))
+            }
+        });
+        P::new(r, g, b, a)
+    }
+
+    fn luminance<P: Pixel>(&self, pixel: P, weights: [f32; 3]) -> P {
+        let zero = P::Channel::ZERO;
+        let c = composite(pixel, [zero; 3]);
+        // 係数の合計は 1.0 なので、補間を2回すると灰色は元の値、白は MAX のままになる。
+        // 係数が一番大きい緑を外側の補間の始点にして、割合の丸めの差を小さくする
+        let [wr, _, wb] = weights;
+        let a = c[1].lerp(c[2].lerp(c[0], wr / (wr + wb)), wr + wb);
+        if a <= zero {
+            return P::new(zero, zero, zero, zero);
+        }
+        // 明るさより明るいチャンネル (彩度の高い色) は MAX で止まる
+        let [r, g, b] = c.map(|c| min_channel(c.div_alpha(a), P::MAX));
+        P::new(r, g, b, a)
+    }
+}
+
+impl PixelKernel for CreateAlpha {
+    fn process<P: Pixel>(&self, pixel: P) -> P {
+        match self.mode {
+            AlphaMode::Black => unmultiply_black(pixel),
+            AlphaMode::White => self.unmultiply(pixel, [P::MAX; 3]),
+            AlphaMode::Color => {
+                let b = P::from_rgba8(self.background);
+                self.unmultiply(pixel, [b.red(), b.green(), b.blue()])
+            }
+            AlphaMode::Luma601 => self.luminance(pixel, REC601),
+            AlphaMode::Luma709 => self.luminance(pixel, REC709),
+        }
+    }
+}
+
+// 元の実装のまま計算する。premultiply と unpremultiply は libs::fixed の丸めで u32 で計算する
+// 32bit は元の実装と同じく、半透明のときだけ掛けた色を round_fp_short で 0.0 - 32.0 に収める
+fn unmultiply_black<P: Pixel>(pixel: P) -> P {
+    let mut p = pixel;
+    if p.alpha() < P::MAX {
+        let q = p.premultiply();
+        p = q.with_rgb(
+            q.red().round_fp_short(),
+            q.green().round_fp_short(),
+            q.blue().round_fp_short(),
+        );
+    }
+    let alpha = max_channel(max_channel(p.red(), p.green()), p.blue());
+    p.with_alpha(alpha).unpremultiply()
+}
+
+// 背景の上に合成した色。8/16bit は INT_BLEND と同じ丸めになる
+fn composite<P: Pixel>(pixel: P, back: [P::Channel; 3]) -> [P::Channel; 3] {
+    let a = pixel.alpha().to_f32();
+    let rgb = [pixel.red(), pixel.green(), pixel.blue()];
+    [0, 1, 2].map(|i| back[i].lerp(rgb[i], a))
+}
+
+fn max_channel<C: Channel>(a: C, b: C) -> C {
+    if a >= b {
+        a
+    } else {
+        b
+    }
+}
+
+fn min_channel<C: Channel>(a: C, b: C) -> C {
+    if a <= b {
+        a
+    } else {
+        b
+    }
+}
@@ -0,0 +1,24 @@
+// ホストに依存しないエフェクトのカーネルです。
+// プラグイン・CLI・テストのどこからでも同じ処理を呼べるようにしています。
+
+pub mod colorchange;
+pub mod colorkey;
+pub mod createalpha;
+pub mod pixelselector;
+
+use crate::image::Image;
+use crate::pixel::Pixel;
+use crate::render::render;
+
+// 1ピクセルを入力して1ピクセルを出力する処理です。
+// ビット深度ごとに書き分けず、Pixel に対してジェネリックに書きます。
+pub trait PixelKernel: Sync {
+    fn process<P: Pixel>(&self, pixel: P) -> P;
+
+    /// 画像全体に適用します。行の帯ごとに複数スレッドで処理します。
+    fn process_image<P: Pixel>(&self, image: &Image<P>) -> Image<P> {
+        let mut out = image.clone();
+        render(self, &image.view(), out.view_mut());
+        out
+    }
+}
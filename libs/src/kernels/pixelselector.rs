@@ -0,0 +1,83 @@
+// ref: https://github.com/bryful/F-s-PluginsProjects/tree/master/PixelSelector
+
+use super::PixelKernel;
+use crate::metric::{alpha_weight, ColorMetric, MetricColor};
+use crate::pixel::{Channel, Pixel, Rgba8};
+
+pub struct PixelSelector {
+    invert: bool,
+    /// 無効のときは None
+    target1: Option<MetricColor>,
+    tolerance: f32,
+    softness: f32,
+    metric: ColorMetric,
+    /// 色に加えてアルファも比べるときの値 (8bit)
+    target_alpha: Option<u8>,
+    unpremultiply: bool,
+}
+
+impl PixelSelector {
+    /// `tolerance` は正規化した許容誤差 (0.0 - 1.0) です。
+    pub fn new(invert: bool, target1: Option<Rgba8>, tolerance: f32, metric: ColorMetric) -> Self {
+        Self {
+            invert,
+            target1: target1.map(|c| metric.prepare(c)),
+            tolerance,
+            softness: 0.0,
+            metric,
+            target_alpha: None,
+            unpremultiply: false,
+        }
+    }
+
+    /// 許容誤差の外側で少しずつ選択を弱める幅 (正規化した単位) を設定します。
+    pub fn with_softness(mut self, softness: f32) -> Self {
+        self.softness = softness;
+        self
+    }
+
+    /// 色に加えて、アルファがこの値 (8bit) に近いピクセルだけを選びます。
+    pub fn with_target_alpha(mut self, alpha: Option<u8>) -> Self {
+        self.target_alpha = alpha;
+        self
+    }
+
+    /// 比べる前に色をアルファで割り (unpremultiply)、結果にアルファを掛け直します。
+    pub fn with_unpremultiply(mut self, unpremultiply: bool) -> Self {
+        self.unpremultiply = unpremultiply;
+        self
+    }
+
+    fn select<P: Pixel>(&self, pixel: P, target1: &MetricColor) -> P {
+        let mut w = self
+            .metric
+            .weight(&pixel, target1, self.tolerance, self.softness);
+        if let Some(a) = self.target_alpha {
+            w *= alpha_weight(&pixel, a, self.tolerance, self.softness);
+        }
+        // 選択された度合い。1.0 ならそのまま残し、0.0 なら白の透明にする
+        let selected = if self.invert { 1.0 - w } else { w };
+        if selected >= 1.0 {
+            return pixel;
+        }
+        P::new(P::MAX, P::MAX, P::MAX, P::Channel::ZERO).lerp(pixel, selected)
+    }
+}
+
+impl PixelKernel for PixelSelector {
+    fn process<P: Pixel>(&self, pixel: P) -> P {
+        let Some(target1) = &self.target1 else {
+            return pixel;
+        };
+        if !self.unpremultiply {
+            return self.select(pixel, target1);
+        }
+        let straight = pixel.unpremultiply();
+        let out = self.select(straight, target1);
+        // 選択されたままのピクセルは掛け直しの誤差が出ないように元のまま返す
+        if out == straight {
+            return pixel;
+        }
+        out.premultiply()
+    }
+}
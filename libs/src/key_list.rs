@@ -0,0 +1,39 @@
+// colorkey で使う、数に上限の無いキー色の一覧です。
+// エフェクトのシーケンスデータとしてプロジェクトに保存されます (SequenceState)。
+//
+// 保存形式 (バージョン 1):
+//   {"version": 1, "data": {"keys": [{"color": "00ff00", "tolerance": 0.2}, {"color": "0000ff"}]}}
+// 色は to_hex と同じ RRGGBB 形式です。tolerance は指定したときだけ書き出します。
+
+use serde::{Deserialize, Serialize};
+
+use crate::kernels::colorkey::KeyColor;
+use crate::list::{self, Edited, ListEdit};
+use crate::sequence::SequenceState;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyList {
+    pub keys: Vec<KeyColor>,
+}
+
+impl SequenceState for KeyList {
+    const VERSION: u16 = 1;
+}
+
+/// キー色の一覧の編集操作です。添字は 0 始まりです。
+pub type KeyEdit = ListEdit<KeyColor>;
+
+impl KeyList {
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// 編集を適用し、編集後に選択しておく添字を返します。
+    pub fn apply(&mut self, edit: KeyEdit) -> Edited {
+        list::apply(&mut self.keys, edit)
+    }
+}
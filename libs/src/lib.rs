@@ -1 +1,25 @@
+#[macro_use]
+mod macros;
+
+pub mod color;
+pub mod color_index;
+pub mod fixed;
+#[cfg(any(windows, target_os = "macos"))]
+pub mod host;
+pub mod image;
+pub mod kernels;
+pub mod key_list;
+pub mod list;
+pub mod metric;
+pub mod palette;
+mod params;
+pub mod pixel;
+pub mod render;
+pub mod sequence;
+pub mod slider;
 pub mod utils;
+
+pub use image::{Image, ImageView, ImageViewMut};
+pub use metric::ColorMetric;
+pub use pixel::{Channel, Pixel, Rgba16, Rgba8, RgbaF32};
+pub use render::Renderer;
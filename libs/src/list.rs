@@ -0,0 +1,71 @@
+// colorchange のパレットや colorkey のキー色の一覧のように、
+// プラグインのボタンで1項目ずつ編集する一覧の共通処理です。
+// 編集する項目は 1 始まりのスライダーで選ぶので、編集後にどれを選んでおくかも返します。
+
+/// 項目を選ぶスライダー (1 始まり) の上限です。
+/// After Effects の浮動小数点スライダーに指定できる一番大きい値で、一覧の長さはこれで制限しません。
+pub const MAX_INDEX: f32 = 1_000_000.0;
+
+/// 一覧の編集操作です。添字は 0 始まりです。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListEdit<T> {
+    /// 末尾に追加
+    Add(T),
+    /// 指定した位置の項目を置き換え
+    Replace(usize, T),
+    Remove(usize),
+    /// 1つ前と入れ替え
+    MoveUp(usize),
+    /// 1つ後と入れ替え
+    MoveDown(usize),
+}
+
+/// 編集した結果です。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edited {
+    /// 範囲外の添字など、何も変わらなかった
+    Unchanged,
+    /// 編集後に選択しておく添字
+    Selected(usize),
+    /// 最後の項目を消して一覧が空になった。選択できる項目は無い
+    Emptied,
+}
+
+impl Edited {
+    /// 一覧が変わったかどうか
+    pub fn changed(self) -> bool {
+        self != Edited::Unchanged
+    }
+}
+
+/// `items` に編集を適用します。
+pub fn apply<T>(items: &mut Vec<T>, edit: ListEdit<T>) -> Edited {
+    let len = items.len();
+    match edit {
+        ListEdit::Add(item) => {
+            items.push(item);
+            Edited::Selected(len)
+        }
+        ListEdit::Replace(i, item) if i < len => {
+            items[i] = item;
+            Edited::Selected(i)
+        }
+        ListEdit::Remove(i) if i < len => {
+            items.remove(i);
+            match items.len() {
+                0 => Edited::Emptied,
+                // 同じ位置 (末尾を消したときは新しい末尾) を選択する
+                n => Edited::Selected(i.min(n - 1)),
+            }
+        }
+        ListEdit::MoveUp(i) if i > 0 && i < len => {
+            items.swap(i - 1, i);
+            Edited::Selected(i - 1)
+        }
+        ListEdit::MoveDown(i) if i + 1 < len => {
+            items.swap(i, i + 1);
+            Edited::Selected(i + 1)
+        }
+        _ => Edited::Unchanged,
+    }
+}
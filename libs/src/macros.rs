@@ -0,0 +1,118 @@
+// libs の中で使うマクロです。lib.rs で最初に宣言しているので、どのモジュールからも使えます。
+
+// AE のポップアップと fs-cli の引数で選ぶ enum を作るマクロです。
+// 値ごとに (fs-cli での名前, ポップアップの表示名) を書くと、enum と
+// ALL・LABELS (どちらも書いた順番)・from_popup・name・Display・FromStr を生成します。
+//
+//   popup_enum! {
+//       /// 1つのピクセルに複数のペアが一致したときの扱いです。
+//       pub enum MatchMode ("match mode") {
+//           #[default]
+//           LastMatch("last", "Last Match Wins"),
+//           FirstMatch("first", "First Match Wins"),
+//       }
+//   }
+//
+// `#[default]` を付けた値が既定値で、範囲外のポップアップの値もこれになります。
+macro_rules! popup_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident ($noun:literal) {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident ($cli:literal, $label:literal)
+            ),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+        $vis enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )+
+        }
+
+        impl $name {
+            pub const ALL: [$name; [$($cli),+].len()] = [$($name::$variant),+];
+
+            /// ポップアップの表示名 (ALL と同じ順番)
+            pub const LABELS: [&'static str; [$($cli),+].len()] = [$($label),+];
+
+            /// AE のポップアップの値 (1 始まり) から変換します。範囲外は既定値になります。
+            pub fn from_popup(value: i32) -> Self {
+                usize::try_from(value - 1)
+                    .ok()
+                    .and_then(|i| Self::ALL.get(i).copied())
+                    .unwrap_or_default()
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($name::$variant => $cli,)+
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::ALL
+                    .into_iter()
+                    .find(|v| v.name().eq_ignore_ascii_case(s))
+                    .ok_or_else(|| {
+                        let names: Vec<_> = Self::ALL.iter().map(|v| v.name()).collect();
+                        format!(
+                            concat!("unknown ", $noun, " `{}` (expected one of {})"),
+                            s,
+                            names.join(", ")
+                        )
+                    })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    popup_enum! {
+        enum Shape ("shape") {
+            #[default]
+            Circle("circle", "Circle"),
+            Square("square", "Square"),
+            Star("star", "Five-Pointed Star"),
+        }
+    }
+
+    #[test]
+    fn popup_values_follow_declaration_order() {
+        assert_eq!(Shape::ALL, [Shape::Circle, Shape::Square, Shape::Star]);
+        assert_eq!(Shape::LABELS, ["Circle", "Square", "Five-Pointed Star"]);
+        assert_eq!(Shape::from_popup(1), Shape::Circle);
+        assert_eq!(Shape::from_popup(3), Shape::Star);
+        // 範囲外は既定値
+        assert_eq!(Shape::from_popup(0), Shape::Circle);
+        assert_eq!(Shape::from_popup(4), Shape::Circle);
+        assert_eq!(Shape::default(), Shape::Circle);
+    }
+
+    #[test]
+    fn parse_names() {
+        for shape in Shape::ALL {
+            assert_eq!(shape.name().parse::<Shape>(), Ok(shape));
+            assert_eq!(shape.to_string(), shape.name());
+        }
+        assert_eq!("SQUARE".parse::<Shape>(), Ok(Shape::Square));
+        assert_eq!(
+            "Circle ".parse::<Shape>(),
+            Err("unknown shape `Circle ` (expected one of circle, square, star)".into())
+        );
+    }
+}
@@ -0,0 +1,272 @@
+// 色の近さの測り方です。
+// どの方式でも距離は「1.0 がおおよそ色域全体」になるように正規化してあり、
+// 許容誤差スライダー (0 - 100%) をそのまま 0.0 - 1.0 として比較できます。
+// 黒と白の距離はどれも 1.0 です。ただし RGB Euclidean は各軸が 1.0 なので、対角線の黒と白は √3 になります。
+// HSV と HSL は色相の差に HUE_WEIGHT を掛けるので、鮮やかな補色どうしは 1.0 より遠くなります。
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::color::{delta_e2000, rgb_to_hsl, rgb_to_hsv, rgb_to_lab, rgb_to_oklab};
+use crate::pixel::{Channel, Pixel, Rgba8, RgbaF32};
+
+/// HSV・HSL の距離で、色相の差に掛ける重みです。
+/// 色相の差 (反対の色相で 1.0) が、同じ割合の彩度・明度の差の何倍になるかを表します。
+pub const HUE_WEIGHT: f32 = 2.0;
+
+popup_enum! {
+    pub enum ColorMetric ("metric") {
+        /// チャンネルごとの差の最大値 (元の実装と同じ立方体の範囲)
+        #[default]
+        RgbBox("rgb-box", "RGB Box"),
+        /// RGB 空間でのユークリッド距離
+        RgbEuclidean("rgb", "RGB Euclidean"),
+        /// 色相を重視した HSV での距離
+        Hsv("hsv", "HSV Hue Weighted"),
+        /// 色相を重視した HSL での距離
+        Hsl("hsl", "HSL Hue Weighted"),
+        /// CIE L*a*b* の ΔE76 / 100
+        LabDe76("lab76", "Lab ΔE76"),
+        /// CIE L*a*b* の ΔE2000 / 100
+        LabDe2000("lab2000", "Lab ΔE2000"),
+        /// OKLab でのユークリッド距離
+        Oklab("oklab", "OKLab"),
+    }
+}
+
+impl ColorMetric {
+    /// ペアごとに上書きするときのポップアップの表示名。先頭は全体の設定を使う項目で、後は LABELS です。
+    pub const OVERRIDE_LABELS: [&'static str; ColorMetric::ALL.len() + 1] = {
+        let mut labels = ["Use Global Metric"; ColorMetric::ALL.len() + 1];
+        let mut i = 0;
+        while i < ColorMetric::LABELS.len() {
+            labels[i + 1] = ColorMetric::LABELS[i];
+            i += 1;
+        }
+        labels
+    };
+
+    /// OVERRIDE_LABELS のポップアップの値から変換します。先頭と範囲外は None (全体の設定) です。
+    pub fn from_override_popup(value: i32) -> Option<Self> {
+        usize::try_from(value - 2)
+            .ok()
+            .and_then(|i| Self::ALL.get(i).copied())
+    }
+
+    /// [`ColorMetric::from_override_popup`] の逆です。
+    pub fn to_override_popup(metric: Option<Self>) -> i32 {
+        metric.map_or(1, |m| {
+            Self::ALL.iter().position(|&x| x == m).unwrap_or(0) as i32 + 2
+        })
+    }
+
+    /// 比較の基準になる色を、この方式の座標に変換しておきます。
+    pub fn prepare(self, color: Rgba8) -> MetricColor {
+        let n = RgbaF32::from_rgba8(color);
+        MetricColor {
+            color,
+            coords: self.coords_normalized(&n),
+        }
+    }
+
+    /// `pixel` と `key` の距離 (正規化済み) を返します。
+    pub fn distance<P: Pixel>(self, pixel: &P, key: &MetricColor) -> f32 {
+        self.distance_at(self.coords(pixel), key)
+    }
+
+    /// [`ColorMetric::coords`] で変換済みの座標 `c` と `key` の距離を返します。
+    pub fn distance_at(self, c: [f32; 3], key: &MetricColor) -> f32 {
+        let k = key.coords;
+        match self {
+            ColorMetric::RgbBox => (c[0] - k[0])
+                .abs()
+                .max((c[1] - k[1]).abs())
+                .max((c[2] - k[2]).abs()),
+            ColorMetric::RgbEuclidean => euclid(c, k),
+            ColorMetric::Hsv | ColorMetric::Hsl => {
+                // 色相の差は反対の色相で 1.0 にしてから重みを掛け、彩度が低い色ほど無視する
+                let dh = (c[0] - k[0]).abs();
+                let dh = HUE_WEIGHT * 2.0 * dh.min(1.0 - dh) * c[1].min(k[1]);
+                let ds = c[1] - k[1];
+                let dv = c[2] - k[2];
+                (dh * dh + ds * ds + dv * dv).sqrt()
+            }
+            ColorMetric::LabDe76 => euclid(c, k) / 100.0,
+            ColorMetric::LabDe2000 => delta_e2000(c, k) / 100.0,
+            ColorMetric::Oklab => euclid(c, k),
+        }
+    }
+
+    /// `pixel` が `key` から `tolerance` 以内なら true を返します。
+    pub fn matches<P: Pixel>(self, pixel: &P, key: &MetricColor, tolerance: f32) -> bool {
+        match self {
+            // ビット深度の精度のまま比較する
+            ColorMetric::RgbBox => pixel.rgb_within(&P::from_rgba8(key.color), tolerance),
+            _ => self.distance(pixel, key) <= tolerance + 1.0e-6,
+        }
+    }
+
+    /// `pixel` がどれだけ `key` に一致しているかを 0.0 - 1.0 で返します。
+    /// `tolerance` 以内は 1.0、そこから `softness` の幅で 0.0 まで下がります。
+    pub fn weight<P: Pixel>(
+        self,
+        pixel: &P,
+        key: &MetricColor,
+        tolerance: f32,
+        softness: f32,
+    ) -> f32 {
+        self.weight_at(pixel, self.coords(pixel), key, tolerance, softness)
+    }
+
+    /// [`ColorMetric::weight`] と同じですが、変換済みの座標 `c` を使います。
+    /// 1つのピクセルを多くの色と比べるときに、座標の変換を1回で済ませるために使います。
+    pub fn weight_at<P: Pixel>(
+        self,
+        pixel: &P,
+        c: [f32; 3],
+        key: &MetricColor,
+        tolerance: f32,
+        softness: f32,
+    ) -> f32 {
+        if self == ColorMetric::RgbBox {
+            if pixel.rgb_within(&P::from_rgba8(key.color), tolerance) {
+                return 1.0;
+            }
+            if softness <= 0.0 {
+                return 0.0;
+            }
+        }
+        let d = self.distance_at(c, key);
+        if self != ColorMetric::RgbBox && d <= tolerance + 1.0e-6 {
+            return 1.0;
+        }
+        if softness <= 0.0 {
+            return 0.0;
+        }
+        falloff(d, tolerance, softness)
+    }
+
+    /// ピクセルをこの方式の座標に変換します。
+    pub fn coords<P: Pixel>(self, pixel: &P) -> [f32; 3] {
+        self.coords_normalized(&pixel.to_normalized())
+    }
+
+    fn coords_normalized(self, n: &RgbaF32) -> [f32; 3] {
+        match self {
+            ColorMetric::RgbBox | ColorMetric::RgbEuclidean => [n.red, n.green, n.blue],
+            ColorMetric::Hsv => rgb_to_hsv(n.red, n.green, n.blue),
+            ColorMetric::Hsl => rgb_to_hsl(n.red, n.green, n.blue),
+            ColorMetric::LabDe76 | ColorMetric::LabDe2000 => rgb_to_lab(n.red, n.green, n.blue),
+            ColorMetric::Oklab => rgb_to_oklab(n.red, n.green, n.blue),
+        }
+    }
+}
+
+// パレットなどには name() の文字列で保存する
+impl Serialize for ColorMetric {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for ColorMetric {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(de::Error::custom)
+    }
+}
+
+/// `pixel` のアルファがどれだけ `alpha` (8bit の値) に一致しているかを 0.0 - 1.0 で返します。
+/// 許容誤差とぼかし幅は色と同じ正規化した単位で、ビット深度の精度のまま比較します。
+pub fn alpha_weight<P: Pixel>(pixel: &P, alpha: u8, tolerance: f32, softness: f32) -> f32 {
+    let key = P::Channel::from_u8(alpha);
+    if pixel.alpha().within(key, tolerance) {
+        return 1.0;
+    }
+    falloff(
+        (pixel.alpha().to_f32() - key.to_f32()).abs(),
+        tolerance,
+        softness,
+    )
+}
+
+/// 距離 `distance` に対する一致度を返します。
+/// `tolerance` 以内は 1.0、`tolerance + softness` 以上は 0.0、その間は直線で変化します。
+pub fn falloff(distance: f32, tolerance: f32, softness: f32) -> f32 {
+    if distance <= tolerance {
+        1.0
+    } else if softness <= 0.0 || distance >= tolerance + softness {
+        0.0
+    } else {
+        1.0 - (distance - tolerance) / softness
+    }
+}
+
+// 基準色を比較用の座標に変換したものです。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MetricColor {
+    pub color: Rgba8,
+    coords: [f32; 3],
+}
+
+impl MetricColor {
+    pub fn coords(&self) -> [f32; 3] {
+        self.coords
+    }
+}
+
+fn euclid(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let d0 = a[0] - b[0];
+    let d1 = a[1] - b[1];
+    let d2 = a[2] - b[2];
+    (d0 * d0 + d1 * d1 + d2 * d2).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgba8 = Rgba8::new(0, 0, 0, 255);
+    const WHITE: Rgba8 = Rgba8::new(255, 255, 255, 255);
+
+    #[test]
+    fn black_to_white_is_normalized() {
+        for metric in ColorMetric::ALL {
+            let expected = match metric {
+                ColorMetric::RgbEuclidean => 3f32.sqrt(),
+                _ => 1.0,
+            };
+            for (a, b) in [(BLACK, WHITE), (WHITE, BLACK)] {
+                let d = metric.distance(&a, &metric.prepare(b));
+                assert!((d - expected).abs() < 1.0e-3, "{metric}: {d}");
+            }
+            assert_eq!(
+                metric.distance(&WHITE, &metric.prepare(WHITE)),
+                0.0,
+                "{metric}"
+            );
+        }
+    }
+
+    #[test]
+    fn hue_costs_more_than_saturation_and_value() {
+        let key = |coords| MetricColor {
+            color: BLACK,
+            coords,
+        };
+        // どれも範囲の 10% の差 (色相は反対の色相までの 1/10 = 18°)
+        let base = key([0.0, 1.0, 0.5]);
+        for metric in [ColorMetric::Hsv, ColorMetric::Hsl] {
+            let hue = metric.distance_at([0.05, 1.0, 0.5], &base);
+            let saturation = metric.distance_at([0.0, 0.9, 0.5], &base);
+            let value = metric.distance_at([0.0, 1.0, 0.4], &base);
+            assert!((hue - HUE_WEIGHT * 0.1).abs() < 1.0e-5, "{metric}: {hue}");
+            assert!((saturation - 0.1).abs() < 1.0e-5 && (value - 0.1).abs() < 1.0e-5);
+            // 1周をまたいでも同じ
+            assert!((metric.distance_at([0.95, 1.0, 0.5], &base) - hue).abs() < 1.0e-5);
+        }
+        // ピクセルでも、赤から 18° ずれた橙は、同じ割合だけ暗い赤より遠い
+        let red = ColorMetric::Hsv.prepare(Rgba8::new(255, 0, 0, 255));
+        let orange = Rgba8::new(255, 77, 0, 255);
+        let dark = Rgba8::new(230, 0, 0, 255);
+        assert!(ColorMetric::Hsv.distance(&orange, &red) > ColorMetric::Hsv.distance(&dark, &red));
+    }
+}
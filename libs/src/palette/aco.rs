@@ -0,0 +1,125 @@
+// Photoshop Color Swatches (.aco)
+//
+//   バージョン 1: u16 1, 色数 u16, 色 (色空間 u16, 値 u16 × 4) × 色数
+//   バージョン 2: u16 2, 色数 u16, 色 (色空間 u16, 値 u16 × 4, 名前の長さ u32 (終端込み), 名前 UTF-16BE) × 色数
+// Photoshop はバージョン 1 の後にバージョン 2 を続けて書きます。読むときは先頭の方だけを使います。
+// 色空間は RGB (0)、HSB (1)、CMYK (2)、Lab (7)、グレースケール (8) に対応しています。
+
+use super::{unit_to_u8, utf16_with_nul, Invalid, Reader};
+use crate::color::lab_to_rgb;
+use crate::pixel::Rgba8;
+
+const RGB: u16 = 0;
+const HSB: u16 = 1;
+const CMYK: u16 = 2;
+const LAB: u16 = 7;
+const GRAY: u16 = 8;
+
+pub(super) fn read(bytes: &[u8]) -> Result<Vec<Rgba8>, Invalid> {
+    let mut r = Reader::new(bytes);
+    let version = r.u16()?;
+    if version != 1 && version != 2 {
+        return Err(format!("unsupported version {version}").into());
+    }
+    let count = r.u16()?;
+    let mut colors = Vec::new();
+    for _ in 0..count {
+        let space = r.u16()?;
+        let v = [r.u16()?, r.u16()?, r.u16()?, r.u16()?];
+        if version == 2 {
+            let len = r.u32()? as usize;
+            r.utf16(len)?;
+        }
+        colors.push(to_rgb(space, v)?);
+    }
+    Ok(colors)
+}
+
+fn to_rgb(space: u16, v: [u16; 4]) -> Result<Rgba8, Invalid> {
+    let unit = |x: u16| x as f32 / 65535.0;
+    let (red, green, blue) = match space {
+        RGB => return Ok(Rgba8::new(to8(v[0]), to8(v[1]), to8(v[2]), 0xFF)),
+        HSB => hsv_to_rgb(unit(v[0]), unit(v[1]), unit(v[2])),
+        CMYK => {
+            // 0 がインク 100%
+            let (c, m, y, k) = (
+                1.0 - unit(v[0]),
+                1.0 - unit(v[1]),
+                1.0 - unit(v[2]),
+                1.0 - unit(v[3]),
+            );
+            (
+                (1.0 - c) * (1.0 - k),
+                (1.0 - m) * (1.0 - k),
+                (1.0 - y) * (1.0 - k),
+            )
+        }
+        LAB => {
+            // L は 0 - 10000、a と b は符号付きで -12800 - 12700 (どれも 100 倍した値)
+            let [r, g, b] = lab_to_rgb(
+                v[0] as f32 / 100.0,
+                v[1] as i16 as f32 / 100.0,
+                v[2] as i16 as f32 / 100.0,
+            );
+            (r, g, b)
+        }
+        GRAY => {
+            // 0 - 10000 で、10000 が黒
+            let v = 1.0 - v[0].min(10000) as f32 / 10000.0;
+            (v, v, v)
+        }
+        _ => return Err(format!("unsupported color space {space}").into()),
+    };
+    Ok(Rgba8::new(
+        unit_to_u8(red),
+        unit_to_u8(green),
+        unit_to_u8(blue),
+        0xFF,
+    ))
+}
+
+// 0 - 65535 => 0 - 255 (一番近い値)
+fn to8(v: u16) -> u8 {
+    ((v as u32 + 128) / 257) as u8
+}
+
+// h, s, v はすべて 0.0 - 1.0
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (f32, f32, f32) {
+    let h = (h * 6.0) % 6.0;
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    (r + m, g + m, b + m)
+}
+
+pub(super) fn write(swatches: &[(String, Rgba8)]) -> Vec<u8> {
+    // 色数は u16 なので、それを超える分は書き出さない
+    let swatches = &swatches[..swatches.len().min(u16::MAX as usize)];
+    let mut out = Vec::new();
+    for version in [1u16, 2] {
+        out.extend_from_slice(&version.to_be_bytes());
+        out.extend_from_slice(&(swatches.len() as u16).to_be_bytes());
+        for (name, color) in swatches {
+            out.extend_from_slice(&RGB.to_be_bytes());
+            for v in [color.red, color.green, color.blue, 0] {
+                out.extend_from_slice(&(v as u16 * 257).to_be_bytes());
+            }
+            if version == 2 {
+                let name = utf16_with_nul(name);
+                out.extend_from_slice(&(name.len() as u32).to_be_bytes());
+                for c in name {
+                    out.extend_from_slice(&c.to_be_bytes());
+                }
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,106 @@
+// Adobe Swatch Exchange (.ase)
+//
+//   "ASEF", バージョン u16 1, u16 0, ブロック数 u32
+//   ブロック: 種類 u16, 長さ u32, 中身
+//     0x0001 色:         名前の長さ u16 (終端込みの文字数), 名前 UTF-16BE,
+//                        色空間 "RGB " / "CMYK" / "LAB " / "Gray", 値 f32 × 3/4/3/1, 種類 u16
+//     0xC001/0xC002 グループの開始/終了 (中の色はそのまま順番に読む)
+// 数値はすべてビッグエンディアンです。
+
+use super::{unit_to_u8, utf16_with_nul, Invalid, Reader};
+use crate::color::lab_to_rgb;
+use crate::pixel::Rgba8;
+
+const SIGNATURE: &[u8; 4] = b"ASEF";
+const COLOR_ENTRY: u16 = 0x0001;
+// 色の種類 (Global/Spot/Normal)
+const NORMAL: u16 = 2;
+
+pub(super) fn read(bytes: &[u8]) -> Result<Vec<Rgba8>, Invalid> {
+    let mut r = Reader::new(bytes);
+    if r.take(4).ok() != Some(SIGNATURE.as_slice()) {
+        return Err("missing ASEF signature".into());
+    }
+    let major = r.u16()?;
+    let _minor = r.u16()?;
+    if major != 1 {
+        return Err(format!("unsupported version {major}").into());
+    }
+    let blocks = r.u32()?;
+
+    let mut colors = Vec::new();
+    for _ in 0..blocks {
+        let kind = r.u16()?;
+        let len = r.u32()? as usize;
+        let body = r.take(len)?;
+        if kind == COLOR_ENTRY {
+            colors.push(read_color(body)?);
+        }
+    }
+    Ok(colors)
+}
+
+fn read_color(body: &[u8]) -> Result<Rgba8, Invalid> {
+    let mut r = Reader::new(body);
+    let name_len = r.u16()? as usize;
+    r.utf16(name_len)?;
+    let model = r.take(4)?;
+    let (red, green, blue) = match model {
+        b"RGB " => (r.f32()?, r.f32()?, r.f32()?),
+        b"Gray" => {
+            let v = r.f32()?;
+            (v, v, v)
+        }
+        b"CMYK" => {
+            let (c, m, y, k) = (r.f32()?, r.f32()?, r.f32()?, r.f32()?);
+            (
+                (1.0 - c) * (1.0 - k),
+                (1.0 - m) * (1.0 - k),
+                (1.0 - y) * (1.0 - k),
+            )
+        }
+        b"LAB " => {
+            // L は 0 - 1、a/b はそのままの値
+            let (l, a, b) = (r.f32()?, r.f32()?, r.f32()?);
+            let [red, green, blue] = lab_to_rgb(l * 100.0, a, b);
+            (red, green, blue)
+        }
+        _ => {
+            let name = String::from_utf8_lossy(model);
+            return Err(format!("unsupported color model `{name}`").into());
+        }
+    };
+    Ok(Rgba8::new(
+        unit_to_u8(red),
+        unit_to_u8(green),
+        unit_to_u8(blue),
+        0xFF,
+    ))
+}
+
+pub(super) fn write(swatches: &[(String, Rgba8)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(SIGNATURE);
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&0u16.to_be_bytes());
+    out.extend_from_slice(&(swatches.len() as u32).to_be_bytes());
+
+    for (name, color) in swatches {
+        let name = utf16_with_nul(name);
+        let mut body = Vec::new();
+        body.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        for c in name {
+            body.extend_from_slice(&c.to_be_bytes());
+        }
+        body.extend_from_slice(b"RGB ");
+        for v in [color.red, color.green, color.blue] {
+            body.extend_from_slice(&(v as f32 / 255.0).to_be_bytes());
+        }
+        body.extend_from_slice(&NORMAL.to_be_bytes());
+
+        out.extend_from_slice(&COLOR_ENTRY.to_be_bytes());
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        out.extend_from_slice(&body);
+    }
+    out
+}
@@ -0,0 +1,57 @@
+// 変更前と変更後の色の組の CSV (.csv)
+//
+//   src,dst,enabled
+//   ff0000,800000,true
+//   #00ff00,008000
+//
+// 色は RRGGBB / #RRGGBB、enabled は true/false/1/0 で省略すると true です。
+// 1行目の先頭が色でなければ見出しとして読み飛ばします。
+
+use super::{Invalid, Palette, PaletteEntry};
+use crate::pixel::Rgba8;
+
+pub(super) fn read(bytes: &[u8]) -> Result<Palette, Invalid> {
+    let text = std::str::from_utf8(bytes).map_err(|_| "not UTF-8 text")?;
+    // Excel などが付ける BOM
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let n = i + 1;
+        let fields: Vec<&str> = line
+            .split(',')
+            .map(|f| f.trim().trim_matches('"').trim())
+            .collect();
+        if fields.iter().all(|f| f.is_empty()) {
+            continue;
+        }
+        if n == 1 && Rgba8::from_hex(fields[0]).is_none() {
+            continue;
+        }
+        if !(2..=3).contains(&fields.len()) {
+            return Err(format!("line {n}: expected `src,dst[,enabled]`").into());
+        }
+        let color = |f: &str| {
+            Rgba8::from_hex(f).ok_or_else(|| format!("line {n}: expected RRGGBB, got `{f}`"))
+        };
+        let enabled = match fields.get(2).copied() {
+            None | Some("") => true,
+            Some(f) if f.eq_ignore_ascii_case("true") || f == "1" => true,
+            Some(f) if f.eq_ignore_ascii_case("false") || f == "0" => false,
+            Some(f) => return Err(format!("line {n}: expected true/false, got `{f}`").into()),
+        };
+        entries.push(PaletteEntry {
+            enabled,
+            ..PaletteEntry::new(color(fields[0])?, color(fields[1])?)
+        });
+    }
+    Ok(Palette { entries })
+}
+
+pub(super) fn write(palette: &Palette) -> Vec<u8> {
+    let mut out = String::from("src,dst,enabled\n");
+    for e in &palette.entries {
+        out += &format!("{},{},{}\n", e.src.to_hex(), e.dst.to_hex(), e.enabled);
+    }
+    out.into_bytes()
+}
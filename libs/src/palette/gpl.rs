@@ -0,0 +1,52 @@
+// GIMP Palette (.gpl)
+//
+//   GIMP Palette
+//   Name: fs-rs
+//   Columns: 2
+//   #
+//   255   0   0	Source 1
+//   128   0   0	Destination 1
+//
+// 1行に1色 (R G B 名前) です。# で始まる行と Name:/Columns: の行は読み飛ばします。
+
+use super::Invalid;
+use crate::pixel::Rgba8;
+
+const HEADER: &str = "GIMP Palette";
+
+pub(super) fn read(bytes: &[u8]) -> Result<Vec<Rgba8>, Invalid> {
+    let text = std::str::from_utf8(bytes).map_err(|_| "not UTF-8 text")?;
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err("missing `GIMP Palette` header".into());
+    }
+
+    let mut colors = Vec::new();
+    for (n, line) in lines {
+        if line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let mut channel = || -> Result<u8, Invalid> {
+            fields
+                .next()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| format!("line {n}: expected `R G B [name]`").into())
+        };
+        let (red, green, blue) = (channel()?, channel()?, channel()?);
+        colors.push(Rgba8::new(red, green, blue, 0xFF));
+    }
+    Ok(colors)
+}
+
+pub(super) fn write(swatches: &[(String, Rgba8)]) -> Vec<u8> {
+    let mut out = format!("{HEADER}\nName: fs-rs\nColumns: 2\n#\n");
+    for (name, c) in swatches {
+        out += &format!("{:3} {:3} {:3}\t{name}\n", c.red, c.green, c.blue);
+    }
+    out.into_bytes()
+}
@@ -0,0 +1,21 @@
+// シーケンスデータと同じ形式の JSON (.json)
+//
+//   {"version": 1, "data": {"entries": [{"src": "ff0000", "dst": "800000", "enabled": true}]}}
+//
+// 書き出すときは人が読めるように整形します。
+
+use super::{Invalid, Palette};
+use crate::sequence;
+
+pub(super) fn read(bytes: &[u8]) -> Result<Palette, Invalid> {
+    sequence::unflatten(bytes).map_err(|e| e.to_string().into())
+}
+
+pub(super) fn write(palette: &Palette) -> Vec<u8> {
+    // Palette は常に JSON にできる
+    let (_, bytes) = sequence::flatten(palette).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let mut out = serde_json::to_vec_pretty(&value).unwrap();
+    out.push(b'\n');
+    out
+}
@@ -0,0 +1,368 @@
+// colorchange で使う、数に上限の無い変更前→変更後の色の一覧です。
+// エフェクトのシーケンスデータとしてプロジェクトに保存されます (SequenceState)。
+//
+// 保存形式 (バージョン 1):
+//   {"version": 1, "data": {"entries": [{"src": "ff0000", "dst": "800000", "enabled": true}, ...]}}
+// 色は to_hex と同じ RRGGBB 形式です。enabled は省略すると true になります。
+// 組ごとの上書き (tolerance, softness, metric) は指定したときだけ書き出します。
+//
+// ファイルとの読み書き (PaletteFormat):
+// - CSV (src,dst,enabled) と JSON (上の保存形式と同じ) は組をそのまま表します。
+//   組ごとの上書きを保存できるのは JSON だけです。
+// - ASE/ACO/GPL のような色見本の形式では、色を順に2つずつ「変更前, 変更後」の組として扱います。
+//   enabled は保存できないので、書き出すとすべての組が有効になります。
+
+mod aco;
+mod ase;
+mod csv;
+mod gpl;
+mod json;
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::kernels::colorchange::PairOverride;
+use crate::list::{self, Edited, ListEdit};
+use crate::pixel::Rgba8;
+use crate::sequence::SequenceState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaletteEntry {
+    #[serde(with = "hex")]
+    pub src: Rgba8,
+    #[serde(with = "hex")]
+    pub dst: Rgba8,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+    /// このペアだけの許容誤差・ぼかし幅・測り方。省略すると全体の設定を使います
+    #[serde(flatten)]
+    pub overrides: PairOverride,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+impl PaletteEntry {
+    pub fn new(src: Rgba8, dst: Rgba8) -> Self {
+        Self {
+            src,
+            dst,
+            enabled: true,
+            overrides: PairOverride::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Palette {
+    pub entries: Vec<PaletteEntry>,
+}
+
+impl SequenceState for Palette {
+    const VERSION: u16 = 1;
+}
+
+/// パレットの編集操作です。添字は 0 始まりです。
+pub type PaletteEdit = ListEdit<PaletteEntry>;
+
+impl Palette {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 編集を適用し、編集後に選択しておく添字を返します。
+    pub fn apply(&mut self, edit: PaletteEdit) -> Edited {
+        list::apply(&mut self.entries, edit)
+    }
+
+    /// 有効な組を並び順のまま返します。後の組ほど優先されます。
+    pub fn enabled_pairs(&self) -> impl Iterator<Item = (Rgba8, Rgba8)> + '_ {
+        self.entries
+            .iter()
+            .filter(|e| e.enabled)
+            .map(|e| (e.src, e.dst))
+    }
+
+    /// [`Palette::enabled_pairs`] と同じ順番で、有効な組のパレットの中での添字を返します。
+    /// 無効な組も数えるので、組を切り替えても他の組の添字は変わりません。
+    pub fn enabled_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.enabled)
+            .map(|(i, _)| i)
+    }
+
+    /// [`Palette::enabled_pairs`] と同じ順番で、有効な組の上書きを返します。
+    pub fn enabled_overrides(&self) -> impl Iterator<Item = PairOverride> + '_ {
+        self.entries
+            .iter()
+            .filter(|e| e.enabled)
+            .map(|e| e.overrides)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PaletteFormat {
+    /// Adobe Swatch Exchange (.ase)
+    Ase,
+    /// Photoshop Color Swatches (.aco)
+    Aco,
+    /// GIMP Palette (.gpl)
+    Gpl,
+    /// src,dst,enabled の CSV (.csv)
+    Csv,
+    /// シーケンスデータと同じ JSON (.json)
+    Json,
+}
+
+impl PaletteFormat {
+    pub const ALL: [PaletteFormat; 5] = [
+        PaletteFormat::Ase,
+        PaletteFormat::Aco,
+        PaletteFormat::Gpl,
+        PaletteFormat::Csv,
+        PaletteFormat::Json,
+    ];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            PaletteFormat::Ase => "ase",
+            PaletteFormat::Aco => "aco",
+            PaletteFormat::Gpl => "gpl",
+            PaletteFormat::Csv => "csv",
+            PaletteFormat::Json => "json",
+        }
+    }
+
+    /// 拡張子 (大文字小文字は区別しない) から形式を決めます。
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        Self::ALL
+            .into_iter()
+            .find(|f| f.extension().eq_ignore_ascii_case(ext))
+    }
+}
+
+impl fmt::Display for PaletteFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(io::Error),
+    /// 拡張子から形式が分からない
+    UnknownFormat(String),
+    /// ファイルの中身が壊れている、または対応していない
+    Invalid {
+        format: PaletteFormat,
+        message: String,
+    },
+    /// 色見本の色の数が奇数で組にできない
+    OddColorCount(usize),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteError::Io(e) => write!(f, "{e}"),
+            PaletteError::UnknownFormat(path) => write!(
+                f,
+                "unknown palette format `{path}` (expected .ase, .aco, .gpl, .csv or .json)"
+            ),
+            PaletteError::Invalid { format, message } => {
+                write!(f, "invalid {format} palette: {message}")
+            }
+            PaletteError::OddColorCount(n) => write!(
+                f,
+                "palette has {n} colors; expected source/destination pairs (an even count)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PaletteError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PaletteError {
+    fn from(e: io::Error) -> Self {
+        PaletteError::Io(e)
+    }
+}
+
+impl Palette {
+    /// `format` のファイルの中身を読み込みます。
+    pub fn read(format: PaletteFormat, bytes: &[u8]) -> Result<Self, PaletteError> {
+        let result = match format {
+            PaletteFormat::Ase => ase::read(bytes).and_then(Palette::from_swatches),
+            PaletteFormat::Aco => aco::read(bytes).and_then(Palette::from_swatches),
+            PaletteFormat::Gpl => gpl::read(bytes).and_then(Palette::from_swatches),
+            PaletteFormat::Csv => csv::read(bytes),
+            PaletteFormat::Json => json::read(bytes),
+        };
+        result.map_err(|e| match e {
+            Invalid::Message(message) => PaletteError::Invalid { format, message },
+            Invalid::OddColorCount(n) => PaletteError::OddColorCount(n),
+        })
+    }
+
+    /// `format` のファイルの中身を作ります。
+    pub fn write(&self, format: PaletteFormat) -> Vec<u8> {
+        match format {
+            PaletteFormat::Ase => ase::write(&self.swatches()),
+            PaletteFormat::Aco => aco::write(&self.swatches()),
+            PaletteFormat::Gpl => gpl::write(&self.swatches()),
+            PaletteFormat::Csv => csv::write(self),
+            PaletteFormat::Json => json::write(self),
+        }
+    }
+
+    /// 拡張子で形式を決めてファイルから読み込みます。
+    pub fn load(path: &Path) -> Result<Self, PaletteError> {
+        let format = format_of(path)?;
+        Self::read(format, &std::fs::read(path)?)
+    }
+
+    /// 拡張子で形式を決めてファイルに書き出します。
+    pub fn save(&self, path: &Path) -> Result<(), PaletteError> {
+        let format = format_of(path)?;
+        std::fs::write(path, self.write(format))?;
+        Ok(())
+    }
+
+    // 色を順に2つずつ組にします。
+    fn from_swatches(colors: Vec<Rgba8>) -> Result<Self, Invalid> {
+        if !colors.len().is_multiple_of(2) {
+            return Err(Invalid::OddColorCount(colors.len()));
+        }
+        let entries = colors
+            .chunks_exact(2)
+            .map(|c| PaletteEntry::new(c[0], c[1]))
+            .collect();
+        Ok(Self { entries })
+    }
+
+    // 色見本として書き出す (名前, 色) の並び
+    fn swatches(&self) -> Vec<(String, Rgba8)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .flat_map(|(i, e)| {
+                [
+                    (format!("Source {}", i + 1), e.src),
+                    (format!("Destination {}", i + 1), e.dst),
+                ]
+            })
+            .collect()
+    }
+}
+
+fn format_of(path: &Path) -> Result<PaletteFormat, PaletteError> {
+    PaletteFormat::from_path(path)
+        .ok_or_else(|| PaletteError::UnknownFormat(path.display().to_string()))
+}
+
+// 各形式の読み込みのエラー。形式の名前は Palette::read で付けます。
+enum Invalid {
+    Message(String),
+    OddColorCount(usize),
+}
+
+impl From<String> for Invalid {
+    fn from(message: String) -> Self {
+        Invalid::Message(message)
+    }
+}
+
+impl From<&str> for Invalid {
+    fn from(message: &str) -> Self {
+        Invalid::Message(message.to_string())
+    }
+}
+
+// 0.0 - 1.0 => 0 - 255
+fn unit_to_u8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// ASE/ACO で使うビッグエンディアンのバイト列の読み込み
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Invalid> {
+        if self.bytes.len() < n {
+            return Err("unexpected end of file".into());
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, Invalid> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Invalid> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, Invalid> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    // UTF-16BE の文字列 (units は文字数)
+    fn utf16(&mut self, units: usize) -> Result<String, Invalid> {
+        let b = self.take(units.checked_mul(2).ok_or("name is too long")?)?;
+        let chars: Vec<u16> = b
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        Ok(String::from_utf16_lossy(&chars))
+    }
+}
+
+// 終端の 0 を含めた UTF-16BE の文字列
+fn utf16_with_nul(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+// colorkey のキー色の一覧でも使う
+pub(crate) mod hex {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::pixel::Rgba8;
+
+    pub fn serialize<S: Serializer>(c: &Rgba8, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&c.to_hex())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Rgba8, D::Error> {
+        let s = String::deserialize(d)?;
+        Rgba8::from_hex(&s).ok_or_else(|| de::Error::custom(format!("expected RRGGBB, got `{s}`")))
+    }
+}
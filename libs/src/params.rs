@@ -0,0 +1,155 @@
+// 同じ形のパラメータを何組も並べるためのマクロです。
+// 組の中身を1回だけ書くと、値をまとめた構造体・組の中のパラメータを表す enum・
+// params_setup での追加・do_render での読み込みを生成します。
+//
+//   libs::param_group! {
+//       /// 変更前と変更後の色の組
+//       struct ColorPair / PairParam {
+//           target: checkbox = Target("Target"),
+//           src: color = SrcColor("Source Color"),
+//       }
+//   }
+//
+//   enum Params { Level, Pair(usize, PairParam) }
+//
+//   // params_setup: 組ごとに "Target0", "Source Color0", "Target1", ... の順で追加される
+//   for (i, defaults) in DEFAULTS.iter().enumerate() {
+//       ColorPair::add(params, i, defaults, Params::Pair)?;
+//   }
+//
+//   // do_render
+//   let pairs = ColorPair::get_all(params, DEFAULTS.len(), Params::Pair)?;
+//   if pairs[i].target { ... pairs[i].src ... }
+//
+// 使えるパラメータの種類は checkbox (bool)、color (Pixel8)、percent (0 - 100 の f64)、
+// metric (Option<ColorMetric>。先頭の "Use Global Metric" が None) です。
+// 生成されるコードは after_effects クレートを使うので、プラグインのクレートから呼び出してください。
+
+#[macro_export]
+macro_rules! param_group {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $values:ident / $param:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field:ident : $kind:ident = $variant:ident ($label:literal)
+            ),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug)]
+        $vis struct $values {
+            $(
+                $(#[$field_meta])*
+                pub $field: $crate::param_group!(@type $kind),
+            )+
+        }
+
+        #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+        $vis enum $param {
+            $($variant,)+
+        }
+
+        impl $param {
+            /// 組の中での並び順 (追加する順番)
+            pub const ALL: &'static [$param] = &[$($param::$variant,)+];
+        }
+
+        impl $values {
+            /// `index` 番目の組を `defaults` の値で追加します。名前の後ろに `index` が付きます。
+            /// `id` には `Params::Pair` のように、組の番号とパラメータから Params を作る関数を渡します。
+            pub fn add<P>(
+                params: &mut ::after_effects::Parameters<P>,
+                index: usize,
+                defaults: &Self,
+                id: impl Fn(usize, $param) -> P,
+            ) -> Result<(), ::after_effects::Error>
+            where
+                P: Eq + PartialEq + ::std::hash::Hash + Copy + ::std::fmt::Debug,
+            {
+                $(
+                    params.add(
+                        id(index, $param::$variant),
+                        &format!("{}{}", $label, index),
+                        $crate::param_group!(@def $kind, defaults.$field),
+                    )?;
+                )+
+                Ok(())
+            }
+
+            /// `index` 番目の組の現在の値を読み込みます。
+            pub fn get<P>(
+                params: &::after_effects::Parameters<P>,
+                index: usize,
+                id: impl Fn(usize, $param) -> P,
+            ) -> Result<Self, ::after_effects::Error>
+            where
+                P: Eq + PartialEq + ::std::hash::Hash + Copy + ::std::fmt::Debug,
+            {
+                Ok(Self {
+                    $(
+                        $field: $crate::param_group!(
+                            @get $kind,
+                            params.get(id(index, $param::$variant))?
+                        ),
+                    )+
+                })
+            }
+
+            /// 0 番目から `count` 組分の値を読み込みます。
+            pub fn get_all<P>(
+                params: &::after_effects::Parameters<P>,
+                count: usize,
+                id: impl Fn(usize, $param) -> P,
+            ) -> Result<Vec<Self>, ::after_effects::Error>
+            where
+                P: Eq + PartialEq + ::std::hash::Hash + Copy + ::std::fmt::Debug,
+            {
+                (0..count).map(|i| Self::get(params, i, &id)).collect()
+            }
+        }
+    };
+
+    (@type checkbox) => { bool };
+    (@type color) => { ::after_effects::Pixel8 };
+    (@type percent) => { f64 };
+    (@type metric) => { Option<$crate::ColorMetric> };
+
+    (@def checkbox, $default:expr) => {
+        ::after_effects::CheckBoxDef::setup(|f| {
+            f.set_default($default);
+            f.set_value(f.default());
+        })
+    };
+    (@def color, $default:expr) => {
+        ::after_effects::ColorDef::setup(|f| {
+            f.set_default($default);
+            f.set_value(f.default());
+        })
+    };
+    (@def percent, $default:expr) => {
+        ::after_effects::FloatSliderDef::setup(|f| {
+            f.set_default($default);
+            f.set_precision(1);
+            f.set_valid_min(0.0);
+            f.set_valid_max(100.0);
+            f.set_slider_min(0.0);
+            f.set_slider_max(100.0);
+            f.set_value(f.default());
+        })
+    };
+    (@def metric, $default:expr) => {
+        ::after_effects::PopupDef::setup(|f| {
+            f.set_options(&$crate::ColorMetric::OVERRIDE_LABELS);
+            f.set_default($crate::ColorMetric::to_override_popup($default));
+            f.set_value(f.default());
+        })
+    };
+
+    (@get checkbox, $param:expr) => { $param.as_checkbox()?.value() };
+    (@get color, $param:expr) => { $param.as_color()?.value() };
+    (@get percent, $param:expr) => { $param.as_float_slider()?.value() };
+    (@get metric, $param:expr) => {
+        $crate::ColorMetric::from_override_popup($param.as_popup()?.value())
+    };
+}
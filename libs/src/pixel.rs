@@ -0,0 +1,451 @@
+// ホスト(After Effects)に依存しないピクセル型です。
+// フィールドの並びは PF_Pixel / PF_Pixel16 / PF_PixelFloat と同じ ARGB 順にしてあるので、
+// AE の EffectWorld のメモリをそのまま読み書きできます。
+
+use crate::fixed;
+use crate::utils::{self, conv_16_to_8, conv_32_to_8};
+
+pub const MAX_CHANNEL8: u32 = 255;
+pub const HALF_CHANNEL8: u32 = 128;
+pub const MAX_CHANNEL16: u32 = 32768;
+pub const HALF_CHANNEL16: u32 = 16384;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rgba8 {
+    pub alpha: u8,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rgba16 {
+    pub alpha: u16,
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RgbaF32 {
+    pub alpha: f32,
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+}
+
+impl Rgba8 {
+    pub const fn new(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Self {
+            alpha,
+            red,
+            green,
+            blue,
+        }
+    }
+}
+
+impl Rgba8 {
+    /// `RRGGBB` / `#RRGGBB` 形式の色を読み込みます。アルファは 255 になります。
+    pub fn from_hex(s: &str) -> Option<Self> {
+        let hex = s.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let v = u32::from_str_radix(hex, 16).ok()?;
+        Some(Self::new((v >> 16) as u8, (v >> 8) as u8, v as u8, 0xFF))
+    }
+
+    pub fn to_hex(&self) -> String {
+        format!("{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+impl Rgba16 {
+    pub const fn new(red: u16, green: u16, blue: u16, alpha: u16) -> Self {
+        Self {
+            alpha,
+            red,
+            green,
+            blue,
+        }
+    }
+}
+
+impl RgbaF32 {
+    pub const fn new(red: f32, green: f32, blue: f32, alpha: f32) -> Self {
+        Self {
+            alpha,
+            red,
+            green,
+            blue,
+        }
+    }
+}
+
+#[cfg(any(windows, target_os = "macos"))]
+mod host {
+    use super::{Rgba16, Rgba8, RgbaF32};
+    use after_effects::sys::{PF_Pixel, PF_Pixel16, PF_PixelFloat};
+
+    macro_rules! impl_host_conversion {
+        ($ours:ty, $theirs:ty) => {
+            impl From<$theirs> for $ours {
+                #[inline]
+                fn from(p: $theirs) -> Self {
+                    Self {
+                        alpha: p.alpha,
+                        red: p.red,
+                        green: p.green,
+                        blue: p.blue,
+                    }
+                }
+            }
+
+            impl From<$ours> for $theirs {
+                #[inline]
+                fn from(p: $ours) -> Self {
+                    Self {
+                        alpha: p.alpha,
+                        red: p.red,
+                        green: p.green,
+                        blue: p.blue,
+                    }
+                }
+            }
+
+            // 同じレイアウトであることをコンパイル時に確認する
+            const _: () = assert!(
+                std::mem::size_of::<$ours>() == std::mem::size_of::<$theirs>()
+                    && std::mem::align_of::<$ours>() == std::mem::align_of::<$theirs>()
+            );
+        };
+    }
+
+    impl_host_conversion!(Rgba8, PF_Pixel);
+    impl_host_conversion!(Rgba16, PF_Pixel16);
+    impl_host_conversion!(RgbaF32, PF_PixelFloat);
+}
+
+// 8/16/32bit のチャンネル値を同じ形で扱うためのトレイトです。
+// 正規化した値は 0.0 - 1.0 (32bit は 1.0 を超えることもある) になります。
+pub trait Channel: Copy + PartialOrd + Default + std::fmt::Debug + Send + Sync + 'static {
+    const ZERO: Self;
+    const MAX: Self;
+    const HALF: Self;
+
+    fn to_f32(self) -> f32;
+    fn from_f32(v: f32) -> Self;
+
+    /// 8bit の値を AE SDK と同じ計算でこのビット深度に変換します。
+    fn from_u8(v: u8) -> Self;
+
+    /// 差が正規化した単位で `tolerance` 以内なら true を返します。
+    fn within(self, other: Self, tolerance: f32) -> bool;
+
+    /// `self` から `other` へ `t` (0.0 - 1.0) の割合で補間します。
+    /// 既定の実装は正規化した値を f32 でそのまま補間します (32bit 用)。
+    fn lerp(self, other: Self, t: f32) -> Self {
+        if t <= 0.0 {
+            return self;
+        }
+        if t >= 1.0 {
+            return other;
+        }
+        let a = self.to_f32();
+        Self::from_f32(a + (other.to_f32() - a) * t)
+    }
+
+    /// `self * alpha / MAX` (整数は四捨五入)
+    fn mul_alpha(self, alpha: Self) -> Self;
+    /// `self * MAX / alpha` (alpha が 0 のときは 0。整数は四捨五入して MAX で止める)
+    fn div_alpha(self, alpha: Self) -> Self;
+
+    /// `self + other` (MAX で止める)
+    fn add_clamped(self, other: Self) -> Self;
+    /// `self - other` (ZERO で止める)
+    fn sub_clamped(self, other: Self) -> Self;
+
+    /// 元の実装の utils::round_fp_short と同じく 32bit の値を 0.0 - 32.0 に収めます。
+    /// 8/16bit はもともと範囲内なので、そのまま返します。
+    fn round_fp_short(self) -> Self {
+        self
+    }
+}
+
+macro_rules! impl_int_channel {
+    ($t:ty, $max:expr, $half:expr, $mul:path, $div:path, $lerp:path) => {
+        impl Channel for $t {
+            const ZERO: Self = 0;
+            const MAX: Self = $max as $t;
+            const HALF: Self = $half as $t;
+
+            #[inline]
+            fn to_f32(self) -> f32 {
+                self as f32 / $max as f32
+            }
+
+            #[inline]
+            fn from_f32(v: f32) -> Self {
+                (v * $max as f32).round().clamp(0.0, $max as f32) as $t
+            }
+
+            #[inline]
+            fn from_u8(v: u8) -> Self {
+                // #define FS_CONVERT8TO16(A) ( (((A_long)(A) * PF_MAX_CHAN16) + PF_HALF_CHAN8) / PF_MAX_CHAN8 )
+                ((v as u32 * $max + HALF_CHANNEL8) / MAX_CHANNEL8) as $t
+            }
+
+            #[inline]
+            fn within(self, other: Self, tolerance: f32) -> bool {
+                // 整数の差に対して比較するので、丸め誤差分だけ余裕を持たせる
+                (self.abs_diff(other) as f32) <= tolerance * $max as f32 + 1.0e-3
+            }
+
+            /// 8/16bit は `t` をそのビット深度の整数に丸めてから libs::fixed で補間します。
+            #[inline]
+            fn lerp(self, other: Self, t: f32) -> Self {
+                // 割合もこのビット深度の整数にして、INT_BLEND と同じ丸めで補間する
+                $lerp(self, other, Self::from_f32(t))
+            }
+
+            #[inline]
+            fn mul_alpha(self, alpha: Self) -> Self {
+                // 四捨五入しないと unpremultiply した値を戻したときに 1 小さくなる
+                $mul(self, alpha)
+            }
+
+            #[inline]
+            fn div_alpha(self, alpha: Self) -> Self {
+                $div(self, alpha)
+            }
+
+            #[inline]
+            fn add_clamped(self, other: Self) -> Self {
+                // u16 は MAX が 32768 なので、型の上限ではなく MAX で止める
+                self.saturating_add(other).min($max as $t)
+            }
+
+            #[inline]
+            fn sub_clamped(self, other: Self) -> Self {
+                self.saturating_sub(other)
+            }
+        }
+    };
+}
+
+impl_int_channel!(
+    u8,
+    MAX_CHANNEL8,
+    HALF_CHANNEL8,
+    fixed::mul8,
+    fixed::div8,
+    fixed::lerp8
+);
+impl_int_channel!(
+    u16,
+    MAX_CHANNEL16,
+    HALF_CHANNEL16,
+    fixed::mul16,
+    fixed::div16,
+    fixed::lerp16
+);
+
+impl Channel for f32 {
+    const ZERO: Self = 0.0;
+    const MAX: Self = 1.0;
+    const HALF: Self = 0.5;
+
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    #[inline]
+    fn from_f32(v: f32) -> Self {
+        v
+    }
+
+    #[inline]
+    fn from_u8(v: u8) -> Self {
+        v as f32 / MAX_CHANNEL8 as f32
+    }
+
+    #[inline]
+    fn within(self, other: Self, tolerance: f32) -> bool {
+        (self - other).abs() <= tolerance + 1.0e-6
+    }
+
+    #[inline]
+    fn mul_alpha(self, alpha: Self) -> Self {
+        self * alpha
+    }
+
+    #[inline]
+    fn div_alpha(self, alpha: Self) -> Self {
+        if alpha <= 0.0 {
+            return 0.0;
+        }
+        self / alpha
+    }
+
+    #[inline]
+    fn add_clamped(self, other: Self) -> Self {
+        (self + other).min(1.0)
+    }
+
+    #[inline]
+    fn sub_clamped(self, other: Self) -> Self {
+        (self - other).max(0.0)
+    }
+
+    #[inline]
+    fn round_fp_short(self) -> Self {
+        utils::round_fp_short(self)
+    }
+}
+
+// 各ビット深度のピクセルを共通に扱うためのトレイトです。
+// エフェクトのカーネルはこのトレイトに対してジェネリックに1回だけ書きます。
+pub trait Pixel: Copy + Default + PartialEq + std::fmt::Debug + Send + Sync + 'static {
+    type Channel: Channel;
+
+    const MAX: Self::Channel = <Self::Channel as Channel>::MAX;
+    const HALF: Self::Channel = <Self::Channel as Channel>::HALF;
+
+    fn new(
+        red: Self::Channel,
+        green: Self::Channel,
+        blue: Self::Channel,
+        alpha: Self::Channel,
+    ) -> Self;
+
+    fn red(&self) -> Self::Channel;
+    fn green(&self) -> Self::Channel;
+    fn blue(&self) -> Self::Channel;
+    fn alpha(&self) -> Self::Channel;
+
+    /// F's Plugins と同じ丸めで 8bit に変換します。
+    fn to_rgba8(&self) -> Rgba8;
+    /// 8bit の色 (カラーパラメータなど) をこのビット深度に変換します。
+    fn from_rgba8(p: Rgba8) -> Self {
+        Self::new(
+            Self::Channel::from_u8(p.red),
+            Self::Channel::from_u8(p.green),
+            Self::Channel::from_u8(p.blue),
+            Self::Channel::from_u8(p.alpha),
+        )
+    }
+
+    /// RGB の各チャンネルの差が正規化した単位で `tolerance` 以内なら true を返します。
+    /// 16/32bit でも 8bit に丸めず、そのビット深度の精度で比較します。
+    fn rgb_within(&self, other: &Self, tolerance: f32) -> bool {
+        self.red().within(other.red(), tolerance)
+            && self.green().within(other.green(), tolerance)
+            && self.blue().within(other.blue(), tolerance)
+    }
+
+    fn with_rgb(self, red: Self::Channel, green: Self::Channel, blue: Self::Channel) -> Self {
+        Self::new(red, green, blue, self.alpha())
+    }
+
+    fn with_alpha(self, alpha: Self::Channel) -> Self {
+        Self::new(self.red(), self.green(), self.blue(), alpha)
+    }
+
+    /// 全チャンネルを `other` へ `t` (0.0 - 1.0) の割合で補間します。
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self::new(
+            self.red().lerp(other.red(), t),
+            self.green().lerp(other.green(), t),
+            self.blue().lerp(other.blue(), t),
+            self.alpha().lerp(other.alpha(), t),
+        )
+    }
+
+    fn to_normalized(&self) -> RgbaF32 {
+        RgbaF32::new(
+            self.red().to_f32(),
+            self.green().to_f32(),
+            self.blue().to_f32(),
+            self.alpha().to_f32(),
+        )
+    }
+
+    fn from_normalized(p: RgbaF32) -> Self {
+        Self::new(
+            Self::Channel::from_f32(p.red),
+            Self::Channel::from_f32(p.green),
+            Self::Channel::from_f32(p.blue),
+            Self::Channel::from_f32(p.alpha),
+        )
+    }
+
+    /// 色にアルファを掛けます (ストレート => プリマルチプライ)。
+    fn premultiply(&self) -> Self {
+        let a = self.alpha();
+        Self::new(
+            self.red().mul_alpha(a),
+            self.green().mul_alpha(a),
+            self.blue().mul_alpha(a),
+            a,
+        )
+    }
+
+    /// 色をアルファで割ります (プリマルチプライ => ストレート)。
+    /// 色がアルファ以下のピクセルは、premultiply で元の値に戻ります。
+    fn unpremultiply(&self) -> Self {
+        let a = self.alpha();
+        Self::new(
+            self.red().div_alpha(a),
+            self.green().div_alpha(a),
+            self.blue().div_alpha(a),
+            a,
+        )
+    }
+}
+
+macro_rules! impl_pixel {
+    ($t:ty, $channel:ty, $to8:expr) => {
+        impl Pixel for $t {
+            type Channel = $channel;
+
+            #[inline]
+            fn new(red: $channel, green: $channel, blue: $channel, alpha: $channel) -> Self {
+                <$t>::new(red, green, blue, alpha)
+            }
+
+            #[inline]
+            fn red(&self) -> $channel {
+                self.red
+            }
+
+            #[inline]
+            fn green(&self) -> $channel {
+                self.green
+            }
+
+            #[inline]
+            fn blue(&self) -> $channel {
+                self.blue
+            }
+
+            #[inline]
+            fn alpha(&self) -> $channel {
+                self.alpha
+            }
+
+            #[inline]
+            fn to_rgba8(&self) -> Rgba8 {
+                $to8(self)
+            }
+        }
+    };
+}
+
+impl_pixel!(Rgba8, u8, |p: &Rgba8| *p);
+impl_pixel!(Rgba16, u16, conv_16_to_8);
+impl_pixel!(RgbaF32, f32, conv_32_to_8);
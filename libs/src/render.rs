@@ -0,0 +1,112 @@
+// カーネルを画像全体に適用するレンダラーです。
+// 出力を数行ずつの帯に分け、空いたスレッドから順に帯を取って処理します。
+
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::thread;
+
+use crate::image::{ImageView, ImageViewMut};
+use crate::kernels::PixelKernel;
+use crate::pixel::Pixel;
+
+/// 1つの帯の行数 (既定値)
+pub const DEFAULT_BAND_ROWS: usize = 32;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Renderer {
+    threads: usize,
+    band_rows: usize,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    /// CPU の論理コア数だけスレッドを使います。
+    pub fn new() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            band_rows: DEFAULT_BAND_ROWS,
+        }
+    }
+
+    /// 1 を指定すると呼び出したスレッドだけで処理します。
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn with_band_rows(mut self, rows: usize) -> Self {
+        self.band_rows = rows.max(1);
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// `src` の各ピクセルに `kernel` を適用して `dst` に書き込みます。
+    /// 大きさが違う場合は左上から重なっている範囲だけを処理します。
+    pub fn render<K, P>(&self, kernel: &K, src: &ImageView<P>, dst: ImageViewMut<P>)
+    where
+        K: PixelKernel + ?Sized,
+        P: Pixel,
+    {
+        let width = src.width().min(dst.width());
+        let height = src.height().min(dst.height());
+
+        let (mut rest, _) = dst.split_rows(height);
+        let mut bands = Vec::new();
+        let mut y = 0;
+        while rest.height() > 0 {
+            let (band, tail) = rest.split_rows(self.band_rows);
+            let rows = band.height();
+            bands.push((y, band));
+            y += rows;
+            rest = tail;
+        }
+
+        let run = |y: usize, mut band: ImageViewMut<P>| {
+            for dy in 0..band.height() {
+                let src_row = &src.row(y + dy)[..width];
+                let dst_row = &mut band.row_mut(dy)[..width];
+                for (out, pixel) in dst_row.iter_mut().zip(src_row) {
+                    *out = kernel.process(*pixel);
+                }
+            }
+        };
+
+        let threads = self.threads.min(bands.len());
+        if threads <= 1 {
+            for (y, band) in bands {
+                run(y, band);
+            }
+            return;
+        }
+
+        let queue = Mutex::new(bands.into_iter());
+        thread::scope(|s| {
+            for _ in 0..threads {
+                s.spawn(|| loop {
+                    let next = queue.lock().unwrap().next();
+                    let Some((y, band)) = next else {
+                        break;
+                    };
+                    run(y, band);
+                });
+            }
+        });
+    }
+}
+
+/// 既定の設定の [`Renderer`] で `kernel` を適用します。
+pub fn render<K, P>(kernel: &K, src: &ImageView<P>, dst: ImageViewMut<P>)
+where
+    K: PixelKernel + ?Sized,
+    P: Pixel,
+{
+    Renderer::new().render(kernel, src, dst);
+}
@@ -0,0 +1,133 @@
+// エフェクトのインスタンスごとに保存する状態 (シーケンスデータ) の読み書きです。
+// AE はプロジェクトの保存時にこれをバイト列にして (flatten)、開くときに元に戻します (unflatten)。
+//
+// 中身は {"version": 1, "data": ...} の形の JSON です。
+// 形式を変えたときは VERSION を上げ、古い形式は SequenceState::migrate で読み替えます。
+// ホストに依存しないので Linux でもテストできます。
+
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// シーケンスデータとして保存できる状態です。
+pub trait SequenceState: Serialize + DeserializeOwned + Default {
+    /// 現在の形式のバージョン
+    const VERSION: u16;
+
+    /// 古いバージョン `version` で保存された `data` を現在の形式に変換します。
+    /// 既定では読み替えずにエラーにします。
+    fn migrate(version: u16, data: serde_json::Value) -> Result<Self, SequenceError> {
+        let _ = data;
+        Err(SequenceError::Unsupported(version))
+    }
+}
+
+// 状態を持たないエフェクト用
+impl SequenceState for () {
+    const VERSION: u16 = 0;
+}
+
+#[derive(Debug)]
+pub enum SequenceError {
+    /// JSON として読めない、または形が合わない
+    Json(serde_json::Error),
+    /// 読み替えられない古いバージョン
+    Unsupported(u16),
+    /// このプラグインより新しいバージョンで保存されている
+    Newer { found: u16, current: u16 },
+}
+
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SequenceError::Json(e) => write!(f, "invalid sequence data: {e}"),
+            SequenceError::Unsupported(v) => write!(f, "unsupported sequence data version {v}"),
+            SequenceError::Newer { found, current } => write!(
+                f,
+                "sequence data version {found} is newer than this plugin ({current})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SequenceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SequenceError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SequenceError {
+    fn from(e: serde_json::Error) -> Self {
+        SequenceError::Json(e)
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, S> {
+    version: u16,
+    data: &'a S,
+}
+
+#[derive(Deserialize)]
+struct RawEnvelope {
+    version: u16,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+/// 状態を保存用のバイト列にします。AE に渡すバージョン番号も一緒に返します。
+pub fn flatten<S: SequenceState>(state: &S) -> Result<(u16, Vec<u8>), SequenceError> {
+    let bytes = serde_json::to_vec(&Envelope {
+        version: S::VERSION,
+        data: state,
+    })?;
+    Ok((S::VERSION, bytes))
+}
+
+/// [`flatten`] で作ったバイト列から状態を読み込みます。
+/// 空のバイト列は新しいインスタンスとして既定値を返します。
+/// バージョンはバイト列の中に書かれたものを使います。
+pub fn unflatten<S: SequenceState>(bytes: &[u8]) -> Result<S, SequenceError> {
+    read(bytes).map(|(_, state)| state)
+}
+
+/// AE から受け取ったシーケンスデータを読み込みます。
+/// 読めないとき (壊れている、新しいバージョンで保存されたなど) は警告を出して既定値を返し、
+/// エフェクトを初期状態で開けるようにします。
+/// `version` は AE が一緒に保存したバージョンで、中に書かれたものと違うときも警告します。
+pub fn unflatten_or_default<S: SequenceState>(version: u16, bytes: &[u8]) -> S {
+    match read(bytes) {
+        Ok((found, state)) => {
+            if found != version {
+                log::warn!("sequence data version {found} does not match the host ({version})");
+            }
+            state
+        }
+        Err(e) => {
+            log::warn!("{e}; using the default sequence data");
+            S::default()
+        }
+    }
+}
+
+fn read<S: SequenceState>(bytes: &[u8]) -> Result<(u16, S), SequenceError> {
+    if bytes.is_empty() {
+        return Ok((S::VERSION, S::default()));
+    }
+    let raw: RawEnvelope = serde_json::from_slice(bytes)?;
+    let state = if raw.version == S::VERSION {
+        serde_json::from_value(raw.data)?
+    } else if raw.version < S::VERSION {
+        S::migrate(raw.version, raw.data)?
+    } else {
+        return Err(SequenceError::Newer {
+            found: raw.version,
+            current: S::VERSION,
+        });
+    };
+    Ok((raw.version, state))
+}
@@ -0,0 +1,22 @@
+// スライダーやコマンドラインで指定した値を、カーネルに渡す値にする関数です。
+// プラグイン・fs-cli・golden テストで同じ単位を使うために、許容誤差やアルファの変換はここにまとめます。
+
+use crate::pixel::MAX_CHANNEL8;
+use crate::utils::round_byte_fp_long;
+
+/// パーセント (0.0 - 100.0) を 0.0 - 1.0 にします。
+/// Level・Threshold・Softness などの許容誤差は、Pixel Selector 以外のエフェクトではこの単位で指定します。
+pub fn percent(v: f64) -> f32 {
+    (v / 100.0) as f32
+}
+
+/// 8bit の差 (0.0 - 255.0) を 0.0 - 1.0 にします。
+/// Pixel Selector の Threshold と Softness は、元の実装と同じくこの単位で指定します。
+pub fn level8(v: f64) -> f32 {
+    (v / MAX_CHANNEL8 as f64) as f32
+}
+
+/// パーセント (0.0 - 100.0) のスライダーの値を 8bit のアルファ (0 - 255) にします。範囲外は 0 か 255 で止めます。
+pub fn percent_to_alpha8(v: f64) -> u8 {
+    round_byte_fp_long((v * 2.55) as f32)
+}
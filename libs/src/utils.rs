@@ -33,9 +33,8 @@
     #endif
 */
 
-use after_effects::{
-    sys::{PF_Pixel, PF_Pixel16, PF_PixelFloat},
-    HALF_CHANNEL16, HALF_CHANNEL8, MAX_CHANNEL16, MAX_CHANNEL8,
+use crate::pixel::{
+    Rgba16, Rgba8, RgbaF32, HALF_CHANNEL16, HALF_CHANNEL8, MAX_CHANNEL16, MAX_CHANNEL8,
 };
 
 pub fn abs<T>(x: T) -> T
@@ -49,7 +48,7 @@ where
     }
 }
 
-pub fn comp_pix8_lv(s: &PF_Pixel, d: &PF_Pixel, lv: u8) -> bool {
+pub fn comp_pix8_lv(s: &Rgba8, d: &Rgba8, lv: u8) -> bool {
     (s.blue as i32 - d.blue as i32).abs() <= lv as i32
         && (s.green as i32 - d.green as i32).abs() <= lv as i32
         && (s.red as i32 - d.red as i32).abs() <= lv as i32
@@ -99,6 +98,8 @@ pub fn round_short(x: i32) -> u16 {
     temp as u16
 }
 
+// 元の実装のまま
+#[allow(clippy::manual_clamp)]
 pub fn round_fp_short(x: f32) -> f32 {
     let mut temp = x;
     if temp < 0.0 {
@@ -110,9 +111,9 @@ pub fn round_fp_short(x: f32) -> f32 {
     temp
 }
 
-pub fn conv_16_to_8(p: &PF_Pixel16) -> PF_Pixel {
+pub fn conv_16_to_8(p: &Rgba16) -> Rgba8 {
     //#define FS_CONVERT16TO8(A)		( (((A_long)(A) * PF_MAX_CHAN8) + PF_HALF_CHAN16) / PF_MAX_CHAN16)
-    PF_Pixel {
+    Rgba8 {
         alpha: round_byte_fp_long(
             (((p.alpha as f32) * (MAX_CHANNEL8 as f32)) + HALF_CHANNEL16 as f32)
                 / MAX_CHANNEL16 as f32,
@@ -132,8 +133,8 @@ pub fn conv_16_to_8(p: &PF_Pixel16) -> PF_Pixel {
     }
 }
 
-pub fn conv_32_to_8(p: &PF_PixelFloat) -> PF_Pixel {
-    PF_Pixel {
+pub fn conv_32_to_8(p: &RgbaF32) -> Rgba8 {
+    Rgba8 {
         alpha: round_byte_fp_long(p.alpha * (MAX_CHANNEL8 as f32) + 0.5),
         red: round_byte_fp_long(p.red * (MAX_CHANNEL8 as f32) + 0.5),
         green: round_byte_fp_long(p.green * (MAX_CHANNEL8 as f32) + 0.5),
@@ -141,8 +142,8 @@ pub fn conv_32_to_8(p: &PF_PixelFloat) -> PF_Pixel {
     }
 }
 
-pub fn conv_8_to_16(p: &PF_Pixel) -> PF_Pixel16 {
-    PF_Pixel16 {
+pub fn conv_8_to_16(p: &Rgba8) -> Rgba16 {
+    Rgba16 {
         //#define FS_CONVERT8TO16(A)		( (((A_long)(A) * PF_MAX_CHAN16) + PF_HALF_CHAN8) / PF_MAX_CHAN8 )
         alpha: round_short_fp_long(
             (((p.alpha as f32) * (MAX_CHANNEL16 as f32)) + HALF_CHANNEL8 as f32)
@@ -163,9 +164,9 @@ pub fn conv_8_to_16(p: &PF_Pixel) -> PF_Pixel16 {
     }
 }
 
-pub fn conv_8_to_32(p: &PF_Pixel) -> PF_PixelFloat {
+pub fn conv_8_to_32(p: &Rgba8) -> RgbaF32 {
     //#define FS_CONVERT8TO32(A)      ((double)(long)((double)A*10000.0/(double)PF_MAX_CHAN8 + 0.5)/10000.0)
-    PF_PixelFloat {
+    RgbaF32 {
         //convert to 32-bit float
         alpha: (p.alpha as f32 * 10000.0 / MAX_CHANNEL8 as f32 + 0.5) / 10000.0,
         red: (p.red as f32 * 10000.0 / MAX_CHANNEL8 as f32 + 0.5) / 10000.0,
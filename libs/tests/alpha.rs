@@ -0,0 +1,190 @@
+// プリマルチプライの変換と、アルファを考慮した色の比較・置き換えのテストです。
+
+mod common;
+
+use common::rgb;
+use libs::kernels::colorchange::{ColorChange, PairOverride};
+use libs::kernels::pixelselector::PixelSelector;
+use libs::kernels::PixelKernel;
+use libs::slider::percent_to_alpha8;
+use libs::{ColorMetric, Pixel, Rgba16, Rgba8, RgbaF32};
+use proptest::prelude::*;
+
+#[test]
+fn premultiply_round_trip_8bit() {
+    // 色がアルファ以下の (正しい) プリマルチプライの値は、全部元に戻る
+    for a in 0..=255u8 {
+        for c in 0..=a {
+            let p = Rgba8::new(c, a - c, c / 2, a);
+            assert_eq!(p.unpremultiply().premultiply(), p, "{p:?}");
+        }
+    }
+}
+
+#[test]
+fn premultiply_8bit_values() {
+    assert_eq!(
+        Rgba8::new(255, 128, 1, 128).premultiply(),
+        Rgba8::new(128, 64, 1, 128)
+    );
+    assert_eq!(
+        Rgba8::new(128, 64, 1, 128).unpremultiply(),
+        Rgba8::new(255, 128, 2, 128)
+    );
+    // 不透明はそのまま、透明は黒
+    assert_eq!(rgb(10, 20, 30).premultiply(), rgb(10, 20, 30));
+    assert_eq!(rgb(10, 20, 30).unpremultiply(), rgb(10, 20, 30));
+    assert_eq!(
+        Rgba8::new(10, 20, 30, 0).premultiply(),
+        Rgba8::new(0, 0, 0, 0)
+    );
+    assert_eq!(
+        Rgba8::new(10, 20, 30, 0).unpremultiply(),
+        Rgba8::new(0, 0, 0, 0)
+    );
+    // アルファより大きい色 (壊れた値) は最大値で止まる
+    assert_eq!(Rgba8::new(200, 0, 0, 100).unpremultiply().red, 255);
+}
+
+proptest! {
+    #[test]
+    fn premultiply_round_trip_16bit(a in 0..=32768u16, r in any::<u16>(), g in any::<u16>()) {
+        let c = |v: u16| if a == 0 { 0 } else { v % (a + 1) };
+        let p = Rgba16::new(c(r), c(g), c(r / 3), a);
+        prop_assert_eq!(p.unpremultiply().premultiply(), p);
+    }
+
+    #[test]
+    fn straight_round_trip_16bit(r in 0..=32768u16, a in 1..=32768u16) {
+        // ストレートの色は、アルファで量子化される分だけずれる
+        let p = Rgba16::new(r, 32768 - r, 0, a);
+        let back = p.premultiply().unpremultiply();
+        let step = 32768 / a as u32 + 1;
+        prop_assert!((back.red.abs_diff(p.red) as u32) <= step, "{:?} {:?}", p, back);
+    }
+
+    #[test]
+    fn premultiply_round_trip_32bit(r in 0.0f32..1.0, a in 0.001f32..=1.0) {
+        let p = RgbaF32::new(r, 1.0 - r, 0.5, a);
+        let back = p.premultiply().unpremultiply();
+        prop_assert!((back.red - p.red).abs() < 1.0e-5);
+        prop_assert!((back.green - p.green).abs() < 1.0e-5);
+        prop_assert_eq!(back.alpha, a);
+    }
+}
+
+#[test]
+fn premultiply_32bit_zero_alpha() {
+    let p = RgbaF32::new(0.5, 0.5, 0.5, 0.0);
+    assert_eq!(p.unpremultiply(), RgbaF32::new(0.0, 0.0, 0.0, 0.0));
+}
+
+const RED: Rgba8 = Rgba8::new(255, 0, 0, 255);
+const BLUE: Rgba8 = Rgba8::new(0, 0, 255, 255);
+
+// 半分透明な赤 (プリマルチプライ)
+const HALF_RED: Rgba8 = Rgba8::new(128, 0, 0, 128);
+
+#[test]
+fn unpremultiply_matches_soft_edges() {
+    let plain = ColorChange::new(0.05, ColorMetric::RgbBox, vec![(RED, BLUE)]);
+    assert_eq!(plain.process(HALF_RED), HALF_RED);
+
+    let k = ColorChange::new(0.05, ColorMetric::RgbBox, vec![(RED, BLUE)]).with_unpremultiply(true);
+    assert_eq!(k.process(HALF_RED), Rgba8::new(0, 0, 128, 128));
+    assert_eq!(k.process(RED), BLUE);
+    for depth in [
+        k.process(Rgba16::from_rgba8(HALF_RED)).to_rgba8(),
+        k.process(RgbaF32::from_rgba8(HALF_RED)).to_rgba8(),
+    ] {
+        // 8bit に戻すときの丸めの差だけ許す
+        assert!(depth.blue.abs_diff(128) <= 1 && depth.red <= 1, "{depth:?}");
+        assert!(depth.alpha.abs_diff(128) <= 1, "{depth:?}");
+    }
+}
+
+#[test]
+fn unmatched_pixels_are_untouched() {
+    let k = ColorChange::new(0.0, ColorMetric::RgbBox, vec![(RED, BLUE)]).with_unpremultiply(true);
+    for a in 0..=255u8 {
+        // 壊れた値 (色 > アルファ) も含めて、一致しなければそのまま
+        let p = Rgba8::new(a / 3, 200, a, a);
+        assert_eq!(k.process(p), p);
+        let p16 = Rgba16::from_rgba8(p);
+        assert_eq!(k.process(p16), p16);
+    }
+}
+
+fn alpha_pair(src_alpha: Option<u8>, dst_alpha: Option<u8>) -> Vec<PairOverride> {
+    vec![PairOverride {
+        src_alpha,
+        dst_alpha,
+        ..PairOverride::default()
+    }]
+}
+
+#[test]
+fn pairs_match_alpha() {
+    let k = ColorChange::new(0.05, ColorMetric::RgbBox, vec![(RED, BLUE)])
+        .with_pair_overrides(alpha_pair(Some(128), None))
+        .with_unpremultiply(true);
+    // 半透明の赤だけが一致する
+    assert_eq!(k.process(RED), RED);
+    assert_eq!(k.process(HALF_RED), Rgba8::new(0, 0, 128, 128));
+    assert_eq!(k.process(Rgba16::from_rgba8(RED)), Rgba16::from_rgba8(RED));
+
+    // アルファの差も softness で少しずつ弱まる
+    let soft = ColorChange::new(0.0, ColorMetric::RgbBox, vec![(RED, BLUE)])
+        .with_softness(0.2)
+        .with_pair_overrides(alpha_pair(Some(255), None));
+    let out = soft.process(Rgba8::new(255, 0, 0, 230));
+    assert!(out.red > 0 && out.red < 255 && out.blue > 0, "{out:?}");
+    assert_eq!(out.alpha, 230);
+}
+
+#[test]
+fn pairs_write_destination_alpha() {
+    let k = ColorChange::new(0.05, ColorMetric::RgbBox, vec![(RED, BLUE)])
+        .with_pair_overrides(alpha_pair(None, Some(64)));
+    assert_eq!(k.process(RED), Rgba8::new(0, 0, 255, 64));
+    assert_eq!(k.process(rgb(0, 255, 0)), rgb(0, 255, 0));
+    let deep = k.process(RgbaF32::from_rgba8(RED));
+    assert!((deep.alpha - 64.0 / 255.0).abs() < 1.0e-6);
+
+    // プリマルチプライし直すので、色もアルファに合わせて暗くなる
+    let k = k.with_unpremultiply(true);
+    assert_eq!(k.process(HALF_RED), Rgba8::new(0, 0, 64, 64));
+}
+
+#[test]
+fn pixelselector_alpha() {
+    let selector = |alpha| {
+        PixelSelector::new(false, Some(RED), 0.05, ColorMetric::RgbBox)
+            .with_target_alpha(alpha)
+            .with_unpremultiply(true)
+    };
+    // 半透明の赤も選ばれる
+    assert_eq!(selector(None).process(HALF_RED), HALF_RED);
+    assert_eq!(selector(None).process(rgb(0, 255, 0)).alpha, 0);
+    // アルファも比べると、不透明な赤は選ばれない
+    assert_eq!(selector(Some(128)).process(HALF_RED), HALF_RED);
+    assert_eq!(selector(Some(128)).process(RED).alpha, 0);
+
+    let plain = PixelSelector::new(false, Some(RED), 0.05, ColorMetric::RgbBox);
+    assert_eq!(plain.process(HALF_RED).alpha, 0);
+}
+
+#[test]
+fn percent_sliders_become_alpha8() {
+    assert_eq!(percent_to_alpha8(0.0), 0);
+    assert_eq!(percent_to_alpha8(100.0), 255);
+    assert_eq!(percent_to_alpha8(50.0), 128);
+    assert_eq!(percent_to_alpha8(10.0), 26);
+    // 範囲外は止める
+    assert_eq!(percent_to_alpha8(-5.0), 0);
+    assert_eq!(percent_to_alpha8(120.0), 255);
+    // 8bit の値をパーセントにして戻すと同じ値になる
+    for a in 0..=255u8 {
+        assert_eq!(percent_to_alpha8(a as f64 / 2.55), a);
+    }
+}
@@ -0,0 +1,247 @@
+// ColorChange の索引 (ハッシュ・格子) を使った結果が、全部のペアを順に調べた結果と同じことを確かめます。
+
+use libs::kernels::colorchange::{ColorChange, MatchMode, PairOverride};
+use libs::kernels::PixelKernel;
+use libs::{ColorMetric, Pixel, Rgba16, Rgba8, RgbaF32};
+
+fn replace<P: Pixel>(pixel: P, dst: Rgba8, w: f32) -> P {
+    let d = P::from_rgba8(dst);
+    pixel.lerp(pixel.with_rgb(d.red(), d.green(), d.blue()), w)
+}
+
+// 上書きを反映したペアごとの設定
+#[derive(Clone, Copy)]
+struct Rule {
+    src: Rgba8,
+    dst: Rgba8,
+    tolerance: f32,
+    softness: f32,
+    metric: ColorMetric,
+}
+
+impl Rule {
+    fn weight<P: Pixel>(&self, p: &P) -> f32 {
+        self.metric.weight(
+            p,
+            &self.metric.prepare(self.src),
+            self.tolerance,
+            self.softness,
+        )
+    }
+
+    fn distance<P: Pixel>(&self, p: &P) -> f32 {
+        self.metric.distance(p, &self.metric.prepare(self.src))
+    }
+}
+
+fn rules(
+    tolerance: f32,
+    softness: f32,
+    metric: ColorMetric,
+    pairs: &[(Rgba8, Rgba8)],
+    overrides: &[PairOverride],
+) -> Vec<Rule> {
+    pairs
+        .iter()
+        .enumerate()
+        .map(|(i, &(src, dst))| {
+            let o = overrides.get(i).copied().unwrap_or_default();
+            Rule {
+                src,
+                dst,
+                tolerance: o.tolerance.unwrap_or(tolerance),
+                softness: o.softness.unwrap_or(softness),
+                metric: o.metric.unwrap_or(metric),
+            }
+        })
+        .collect()
+}
+
+// 索引を使わずに全部のペアを順に調べる処理
+fn reference<P: Pixel>(pixel: P, mode: MatchMode, rules: &[Rule]) -> P {
+    let mut out = pixel;
+    match mode {
+        MatchMode::LastMatch => {
+            for r in rules {
+                let w = r.weight(&pixel);
+                if w > 0.0 {
+                    out = replace(out, r.dst, w);
+                }
+            }
+        }
+        MatchMode::FirstMatch => {
+            if let Some((dst, w)) = rules
+                .iter()
+                .map(|r| (r.dst, r.weight(&pixel)))
+                .find(|&(_, w)| w > 0.0)
+            {
+                out = replace(pixel, dst, w);
+            }
+        }
+        MatchMode::Nearest => {
+            let mut best = None;
+            for r in rules {
+                let w = r.weight(&pixel);
+                let d = r.distance(&pixel);
+                if w > 0.0 && best.is_none_or(|(best_d, _, _)| d <= best_d) {
+                    best = Some((d, r.dst, w));
+                }
+            }
+            if let Some((_, dst, w)) = best {
+                out = replace(pixel, dst, w);
+            }
+        }
+        MatchMode::Chained => {
+            for r in rules {
+                let w = r.weight(&out);
+                if w > 0.0 {
+                    out = replace(out, r.dst, w);
+                }
+            }
+        }
+    }
+    out
+}
+
+// 決まった順番で散らばった色を作る (乱数の代わり)
+fn color(i: u32) -> Rgba8 {
+    let h = i.wrapping_mul(2654435761);
+    Rgba8::new((h >> 24) as u8, (h >> 16) as u8, (h >> 8) as u8, 255)
+}
+
+fn pairs(n: u32) -> Vec<(Rgba8, Rgba8)> {
+    let mut pairs: Vec<_> = (0..n).map(|i| (color(i), color(i + 1000))).collect();
+    // 同じ変更前の色が複数あるとき
+    pairs.push((color(3), Rgba8::new(1, 2, 3, 255)));
+    // Chained で前のペアの結果に一致するペア
+    pairs.push((Rgba8::new(1, 2, 3, 255), color(5)));
+    pairs.push((color(1005), color(7)));
+    pairs
+}
+
+// ペアが n 個のときに調べるピクセル。一致する色としない色を count 個ずつ
+fn pixels<P: Pixel>(n: u32, count: u32) -> Vec<P> {
+    let mut pixels: Vec<P> = (0..count)
+        .map(|i| P::from_rgba8(color(i % (n + n / 2))))
+        .collect();
+    // 基準色の近くの色
+    pixels.extend((0..count).map(|i| {
+        let c = color(i % n);
+        let d = (i % 21) as u8;
+        P::from_rgba8(Rgba8::new(
+            c.red.saturating_add(d),
+            c.green.saturating_sub(d / 2),
+            c.blue,
+            (i % 256) as u8,
+        ))
+    }));
+    pixels
+}
+
+// 全部の組み合わせを調べると時間がかかるので、普段は少ないペアとピクセルで調べる
+const QUICK: (u32, u32) = (40, 120);
+const EXHAUSTIVE: (u32, u32) = (200, 600);
+
+fn check<P: Pixel>((n, count): (u32, u32)) {
+    let pairs = pairs(n);
+    for metric in ColorMetric::ALL {
+        for (tolerance, softness) in [(0.0, 0.0), (0.02, 0.0), (0.05, 0.03), (0.0, 0.04)] {
+            for mode in MatchMode::ALL {
+                let kernel = ColorChange::new(tolerance, metric, pairs.clone())
+                    .with_softness(softness)
+                    .with_mode(mode);
+                let rules = rules(tolerance, softness, metric, &pairs, &[]);
+                for pixel in pixels::<P>(n, count) {
+                    assert_eq!(
+                        kernel.process(pixel),
+                        reference(pixel, mode, &rules),
+                        "{metric} {mode} tolerance={tolerance} softness={softness} pixel={pixel:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+// ペアごとに許容誤差・ぼかし幅・測り方を混ぜる
+fn overrides(n: usize) -> Vec<PairOverride> {
+    (0..n)
+        .map(|i| match i % 5 {
+            0 => PairOverride::default(),
+            1 => PairOverride {
+                tolerance: Some(0.08),
+                ..PairOverride::default()
+            },
+            2 => PairOverride {
+                tolerance: Some(0.0),
+                softness: Some(0.0),
+                ..PairOverride::default()
+            },
+            3 => PairOverride {
+                metric: Some(ColorMetric::ALL[i % ColorMetric::ALL.len()]),
+                ..PairOverride::default()
+            },
+            _ => PairOverride {
+                tolerance: Some(0.03),
+                softness: Some(0.05),
+                metric: Some(ColorMetric::Oklab),
+                ..PairOverride::default()
+            },
+        })
+        .collect()
+}
+
+fn check_overrides<P: Pixel>((n, count): (u32, u32)) {
+    let pairs = pairs(n);
+    let overrides = overrides(pairs.len());
+    for metric in [ColorMetric::RgbBox, ColorMetric::LabDe76] {
+        for mode in MatchMode::ALL {
+            let kernel = ColorChange::new(0.02, metric, pairs.clone())
+                .with_softness(0.01)
+                .with_mode(mode)
+                .with_pair_overrides(overrides.clone());
+            let rules = rules(0.02, 0.01, metric, &pairs, &overrides);
+            for pixel in pixels::<P>(n, count) {
+                assert_eq!(
+                    kernel.process(pixel),
+                    reference(pixel, mode, &rules),
+                    "{metric} {mode} pixel={pixel:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn overrides_match_linear_scan() {
+    check_overrides::<Rgba8>(QUICK);
+    check_overrides::<Rgba16>(QUICK);
+    check_overrides::<RgbaF32>(QUICK);
+}
+
+#[test]
+fn index_matches_linear_scan_8bit() {
+    check::<Rgba8>(QUICK);
+}
+
+#[test]
+fn index_matches_linear_scan_16bit() {
+    check::<Rgba16>(QUICK);
+}
+
+#[test]
+fn index_matches_linear_scan_32bit() {
+    check::<RgbaF32>(QUICK);
+}
+
+// cargo test --release -p libs --test colorchange_index -- --ignored
+#[test]
+#[ignore = "slow; run with --ignored"]
+fn index_matches_linear_scan_exhaustive() {
+    check::<Rgba8>(EXHAUSTIVE);
+    check::<Rgba16>(EXHAUSTIVE);
+    check::<RgbaF32>(EXHAUSTIVE);
+    check_overrides::<Rgba8>((120, 600));
+    check_overrides::<Rgba16>((120, 600));
+    check_overrides::<RgbaF32>((120, 600));
+}
@@ -0,0 +1,145 @@
+// 許容誤差の範囲が重なったペアに対する、ColorChange の各 MatchMode の結果を確かめます。
+
+mod common;
+
+use common::{overlapping, rgb, RED_A, RED_B, TO_A, TO_B};
+use libs::kernels::colorchange::{ColorChange, MatchMode};
+use libs::kernels::PixelKernel;
+use libs::{ColorMetric, Pixel, Rgba16, Rgba8, RgbaF32};
+
+fn run<P: Pixel>(kernel: &ColorChange, c: Rgba8) -> Rgba8 {
+    kernel.process(P::from_rgba8(c)).to_rgba8()
+}
+
+// 期待値も同じビット深度を通して比べる (8bit に戻すときの丸めを揃える)
+fn check<P: Pixel>(kernel: &ColorChange, input: Rgba8, expected: Rgba8) {
+    assert_eq!(
+        run::<P>(kernel, input),
+        P::from_rgba8(expected).to_rgba8(),
+        "{} {input:?}",
+        std::any::type_name::<P>()
+    );
+}
+
+fn check_all_depths(kernel: &ColorChange, input: Rgba8, expected: Rgba8) {
+    check::<Rgba8>(kernel, input, expected);
+    check::<Rgba16>(kernel, input, expected);
+    check::<RgbaF32>(kernel, input, expected);
+}
+
+#[test]
+fn last_match_wins() {
+    let k = overlapping(MatchMode::LastMatch);
+    check_all_depths(&k, rgb(102, 0, 0), TO_B);
+    check_all_depths(&k, rgb(108, 0, 0), TO_B);
+    // 片方だけに一致
+    check_all_depths(&k, rgb(85, 0, 0), TO_A);
+    check_all_depths(&k, rgb(125, 0, 0), TO_B);
+    check_all_depths(&k, rgb(140, 0, 0), rgb(140, 0, 0));
+}
+
+#[test]
+fn last_match_is_the_default() {
+    let default = ColorChange::new(
+        20.0 / 255.0,
+        ColorMetric::RgbBox,
+        vec![(RED_A, TO_A), (RED_B, TO_B)],
+    );
+    for r in 70..=140 {
+        let c = rgb(r, 0, 0);
+        assert_eq!(
+            run::<Rgba8>(&default, c),
+            run::<Rgba8>(&overlapping(MatchMode::LastMatch), c)
+        );
+    }
+}
+
+#[test]
+fn first_match_wins() {
+    let k = overlapping(MatchMode::FirstMatch);
+    check_all_depths(&k, rgb(102, 0, 0), TO_A);
+    check_all_depths(&k, rgb(108, 0, 0), TO_A);
+    check_all_depths(&k, rgb(85, 0, 0), TO_A);
+    check_all_depths(&k, rgb(125, 0, 0), TO_B);
+    check_all_depths(&k, rgb(140, 0, 0), rgb(140, 0, 0));
+}
+
+#[test]
+fn nearest_source_wins() {
+    let k = overlapping(MatchMode::Nearest);
+    check_all_depths(&k, rgb(102, 0, 0), TO_A);
+    check_all_depths(&k, rgb(108, 0, 0), TO_B);
+    // 同じ距離なら後のペア
+    check_all_depths(&k, rgb(105, 0, 0), TO_B);
+    check_all_depths(&k, rgb(85, 0, 0), TO_A);
+    check_all_depths(&k, rgb(125, 0, 0), TO_B);
+}
+
+#[test]
+fn nearest_uses_the_metric() {
+    // 箱型では同じ距離、ユークリッド距離では RED_A の方が近い
+    let k = ColorChange::new(
+        0.2,
+        ColorMetric::RgbEuclidean,
+        vec![(rgb(100, 100, 100), TO_A), (rgb(120, 80, 100), TO_B)],
+    )
+    .with_mode(MatchMode::Nearest);
+    check_all_depths(&k, rgb(110, 100, 100), TO_A);
+}
+
+#[test]
+fn chained_sees_previous_output() {
+    // 赤 → 緑 → 青。緑 → 青 のペアは置き換えた後の色に一致する
+    let pairs = vec![
+        (rgb(255, 0, 0), rgb(0, 255, 0)),
+        (rgb(0, 255, 0), rgb(0, 0, 255)),
+    ];
+    let chained =
+        ColorChange::new(0.0, ColorMetric::RgbBox, pairs.clone()).with_mode(MatchMode::Chained);
+    check_all_depths(&chained, rgb(255, 0, 0), rgb(0, 0, 255));
+    check_all_depths(&chained, rgb(0, 255, 0), rgb(0, 0, 255));
+
+    let last = ColorChange::new(0.0, ColorMetric::RgbBox, pairs);
+    check_all_depths(&last, rgb(255, 0, 0), rgb(0, 255, 0));
+}
+
+#[test]
+fn chained_only_looks_forward() {
+    // 後のペアが前のペアの変更前の色を作っても、前には戻らない
+    let pairs = vec![
+        (rgb(0, 255, 0), rgb(0, 0, 255)),
+        (rgb(255, 0, 0), rgb(0, 255, 0)),
+    ];
+    let k = ColorChange::new(0.0, ColorMetric::RgbBox, pairs).with_mode(MatchMode::Chained);
+    check_all_depths(&k, rgb(255, 0, 0), rgb(0, 255, 0));
+}
+
+#[test]
+fn chained_overlapping_tolerance() {
+    // 102 は RED_A で TO_A になり、TO_A は RED_B に一致しないのでそのまま
+    let k = overlapping(MatchMode::Chained);
+    check_all_depths(&k, rgb(102, 0, 0), TO_A);
+    // RED_B の範囲だけにある色は TO_B
+    check_all_depths(&k, rgb(125, 0, 0), TO_B);
+}
+
+#[test]
+fn modes_with_softness() {
+    // 115 は RED_B の範囲内、RED_A からは 15 で softness の範囲 (10 - 20) の途中
+    let pairs = vec![(RED_A, TO_A), (RED_B, TO_B)];
+    let kernel = |mode| {
+        ColorChange::new(10.0 / 255.0, ColorMetric::RgbBox, pairs.clone())
+            .with_softness(10.0 / 255.0)
+            .with_mode(mode)
+    };
+    let c = rgb(115, 0, 0);
+    assert_eq!(run::<Rgba8>(&kernel(MatchMode::LastMatch), c), TO_B);
+    assert_eq!(run::<Rgba8>(&kernel(MatchMode::Nearest), c), TO_B);
+    // 最初に一致するのは RED_A の半分の重み
+    let first = run::<Rgba8>(&kernel(MatchMode::FirstMatch), c);
+    assert!(
+        first.red > 0 && first.red < 115 && first.blue > 0,
+        "{first:?}"
+    );
+    assert_eq!(first.green, 0);
+}
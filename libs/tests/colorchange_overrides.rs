@@ -0,0 +1,95 @@
+// ColorChange のペアごとの上書き (許容誤差・ぼかし幅・測り方) のテストです。
+
+mod common;
+
+use common::rgb;
+use libs::kernels::colorchange::{ColorChange, PairOverride};
+use libs::kernels::PixelKernel;
+use libs::{ColorMetric, Rgba8};
+
+const SKIN: Rgba8 = Rgba8::new(200, 150, 120, 255);
+const SKY: Rgba8 = Rgba8::new(100, 150, 250, 255);
+const TO_SKIN: Rgba8 = Rgba8::new(220, 160, 130, 255);
+const TO_SKY: Rgba8 = Rgba8::new(0, 0, 0, 255);
+
+fn pairs() -> Vec<(Rgba8, Rgba8)> {
+    vec![(SKIN, TO_SKIN), (SKY, TO_SKY)]
+}
+
+fn tolerance(level: f32) -> PairOverride {
+    PairOverride {
+        tolerance: Some(level / 255.0),
+        ..PairOverride::default()
+    }
+}
+
+#[test]
+fn empty_overrides_keep_global_behaviour() {
+    let plain = ColorChange::new(10.0 / 255.0, ColorMetric::RgbBox, pairs());
+    let overridden = ColorChange::new(10.0 / 255.0, ColorMetric::RgbBox, pairs())
+        .with_pair_overrides(vec![PairOverride::default(); 2]);
+    for v in (0..=255).step_by(3) {
+        for c in [rgb(v, 150, 120), rgb(100, 150, v), rgb(v, v, v)] {
+            assert_eq!(overridden.process(c), plain.process(c), "{c:?}");
+        }
+    }
+}
+
+#[test]
+fn per_pair_tolerance() {
+    // 肌は狭く (2)、空は広く (40)
+    let k = ColorChange::new(10.0 / 255.0, ColorMetric::RgbBox, pairs())
+        .with_pair_overrides(vec![tolerance(2.0), tolerance(40.0)]);
+    assert_eq!(k.process(rgb(202, 150, 120)), TO_SKIN);
+    assert_eq!(k.process(rgb(205, 150, 120)), rgb(205, 150, 120));
+    assert_eq!(k.process(rgb(130, 150, 250)), TO_SKY);
+    assert_eq!(k.process(rgb(141, 150, 250)), rgb(141, 150, 250));
+}
+
+#[test]
+fn missing_overrides_use_global() {
+    // 2つ目のペアには上書きが無いので全体の 10
+    let k = ColorChange::new(10.0 / 255.0, ColorMetric::RgbBox, pairs())
+        .with_pair_overrides(vec![tolerance(0.0)]);
+    assert_eq!(k.process(rgb(201, 150, 120)), rgb(201, 150, 120));
+    assert_eq!(k.process(rgb(110, 150, 250)), TO_SKY);
+}
+
+#[test]
+fn per_pair_softness_and_metric() {
+    let k = ColorChange::new(0.0, ColorMetric::RgbBox, pairs()).with_pair_overrides(vec![
+        PairOverride {
+            tolerance: Some(0.0),
+            softness: Some(20.0 / 255.0),
+            metric: None,
+            ..PairOverride::default()
+        },
+        PairOverride {
+            tolerance: Some(0.1),
+            softness: None,
+            metric: Some(ColorMetric::Oklab),
+            ..PairOverride::default()
+        },
+    ]);
+    // 肌から 10 離れた色は半分だけ置き換わる
+    let half = k.process(rgb(210, 150, 120));
+    assert!(half.red > 210 && half.red < 220, "{half:?}");
+    // 空は OKLab の距離で比べる
+    assert_eq!(k.process(rgb(110, 155, 245)), TO_SKY);
+}
+
+#[test]
+fn override_popup() {
+    assert_eq!(ColorMetric::from_override_popup(1), None);
+    for (i, m) in ColorMetric::ALL.into_iter().enumerate() {
+        let value = ColorMetric::to_override_popup(Some(m));
+        assert_eq!(value, i as i32 + 2);
+        assert_eq!(ColorMetric::from_override_popup(value), Some(m));
+    }
+    assert_eq!(ColorMetric::to_override_popup(None), 1);
+    assert_eq!(ColorMetric::from_override_popup(99), None);
+    assert_eq!(
+        ColorMetric::OVERRIDE_LABELS.len(),
+        ColorMetric::ALL.len() + 1
+    );
+}
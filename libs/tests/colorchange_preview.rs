@@ -0,0 +1,125 @@
+// ColorChange の一致の確認表示 (with_preview) のテストです。
+
+mod common;
+
+use common::{overlapping, rgb, RED_A, RED_B};
+use libs::kernels::colorchange::{ColorChange, MatchMode, PREVIEW_COLORS};
+use libs::kernels::PixelKernel;
+use libs::palette::{Palette, PaletteEntry};
+use libs::{ColorMetric, Pixel, Rgba16, Rgba8};
+
+// i 番目のペアの色に 60% 寄せた色
+fn tinted(pixel: Rgba8, i: usize) -> Rgba8 {
+    let c = PREVIEW_COLORS[i];
+    pixel.lerp(pixel.with_rgb(c.red, c.green, c.blue), 0.6)
+}
+
+#[test]
+fn unmatched_pixels_are_dimmed() {
+    let k = overlapping(MatchMode::LastMatch).with_preview(true);
+    assert_eq!(
+        k.process(Rgba8::new(200, 100, 40, 128)),
+        Rgba8::new(50, 25, 10, 128)
+    );
+    assert_eq!(k.process(rgb(0, 0, 0)), rgb(0, 0, 0));
+}
+
+#[test]
+fn matched_pixels_are_tinted() {
+    let k = overlapping(MatchMode::LastMatch).with_preview(true);
+    // 赤に 60% 寄せる: 100 + (255 - 100) * 0.6
+    assert_eq!(k.process(rgb(85, 0, 0)), tinted(rgb(85, 0, 0), 0));
+    assert_eq!(tinted(RED_A, 0), rgb(193, 0, 0));
+    assert_eq!(k.process(rgb(125, 0, 0)), tinted(rgb(125, 0, 0), 1));
+    // 16bit でもほぼ同じ色になる (8bit に戻すときの丸めの差だけ)
+    let deep = k.process(Rgba16::from_rgba8(rgb(125, 0, 0))).to_rgba8();
+    let expected = tinted(rgb(125, 0, 0), 1);
+    for (a, b) in [
+        (deep.red, expected.red),
+        (deep.green, expected.green),
+        (deep.blue, expected.blue),
+    ] {
+        assert!(a.abs_diff(b) <= 1, "{deep:?} {expected:?}");
+    }
+}
+
+#[test]
+fn color_follows_the_match_mode() {
+    let c = rgb(102, 0, 0);
+    let last = overlapping(MatchMode::LastMatch)
+        .with_preview(true)
+        .process(c);
+    let first = overlapping(MatchMode::FirstMatch)
+        .with_preview(true)
+        .process(c);
+    let nearest = overlapping(MatchMode::Nearest)
+        .with_preview(true)
+        .process(c);
+    assert_eq!(last, tinted(c, 1));
+    assert_eq!(first, tinted(c, 0));
+    assert_eq!(nearest, tinted(c, 0));
+}
+
+#[test]
+fn chained_uses_the_last_applied_pair() {
+    let pairs = vec![
+        (rgb(255, 0, 0), rgb(0, 255, 0)),
+        (rgb(0, 255, 0), rgb(0, 0, 255)),
+    ];
+    let k = ColorChange::new(0.0, ColorMetric::RgbBox, pairs)
+        .with_mode(MatchMode::Chained)
+        .with_preview(true);
+    assert_eq!(k.process(rgb(255, 0, 0)), tinted(rgb(255, 0, 0), 1));
+}
+
+#[test]
+fn soft_edges_blend_between_dim_and_tint() {
+    let k = ColorChange::new(10.0 / 255.0, ColorMetric::RgbBox, vec![(RED_A, RED_B)])
+        .with_softness(10.0 / 255.0)
+        .with_preview(true);
+    // 115 は softness の範囲の真ん中
+    let c = rgb(115, 0, 0);
+    let out = k.process(c);
+    let dim = 115 / 4;
+    assert!(out.red > dim && out.red < tinted(c, 0).red, "{out:?}");
+}
+
+#[test]
+fn colors_cycle_after_eight_pairs() {
+    let pairs: Vec<_> = (0..10u8)
+        .map(|i| (rgb(i * 20, 0, 0), rgb(0, 0, 0)))
+        .collect();
+    let k = ColorChange::new(0.0, ColorMetric::RgbBox, pairs).with_preview(true);
+    assert_eq!(k.process(rgb(180, 0, 0)), tinted(rgb(180, 0, 0), 1));
+    assert_eq!(k.process(rgb(20, 0, 0)), tinted(rgb(20, 0, 0), 1));
+}
+
+// 1組目が無効なときは2組目だけを渡すが、色は2組目のもののまま
+#[test]
+fn disabled_first_slot_keeps_slot_colors() {
+    let k = ColorChange::new(0.0, ColorMetric::RgbBox, vec![(RED_B, rgb(0, 200, 0))])
+        .with_preview(true)
+        .with_preview_slots(vec![1]);
+    assert_eq!(k.process(RED_B), tinted(RED_B, 1));
+    // 番号が無ければペアの添字の色
+    let k = ColorChange::new(0.0, ColorMetric::RgbBox, vec![(RED_B, rgb(0, 200, 0))])
+        .with_preview(true);
+    assert_eq!(k.process(RED_B), tinted(RED_B, 0));
+}
+
+#[test]
+fn palette_slots_count_disabled_entries() {
+    let mut palette = Palette {
+        entries: (0..4u8)
+            .map(|i| PaletteEntry::new(rgb(i * 20, 0, 0), rgb(0, 0, 0)))
+            .collect(),
+    };
+    palette.entries[0].enabled = false;
+    palette.entries[2].enabled = false;
+    let slots: Vec<_> = palette.enabled_indices().collect();
+    assert_eq!(slots, [1, 3]);
+    let k = ColorChange::new(0.0, ColorMetric::RgbBox, palette.enabled_pairs().collect())
+        .with_preview(true)
+        .with_preview_slots(slots);
+    assert_eq!(k.process(rgb(60, 0, 0)), tinted(rgb(60, 0, 0), 3));
+}
@@ -0,0 +1,139 @@
+// ColorChange の Transfer (陰影を残す置き換え方) のテストです。
+
+mod common;
+
+use common::{close_rgb_same_alpha, rgb};
+use libs::color::{luma, rgb_to_oklab};
+use libs::kernels::colorchange::{ColorChange, Transfer};
+use libs::kernels::PixelKernel;
+use libs::{ColorMetric, Pixel, Rgba16, Rgba8, RgbaF32};
+
+const RED: Rgba8 = Rgba8::new(200, 0, 0, 255);
+const BLUE: Rgba8 = Rgba8::new(0, 0, 200, 255);
+
+fn kernel(transfer: Transfer, src: Rgba8, dst: Rgba8, level: f32) -> ColorChange {
+    ColorChange::new(level / 255.0, ColorMetric::RgbBox, vec![(src, dst)]).with_transfer(transfer)
+}
+
+fn oklab(c: Rgba8) -> [f32; 3] {
+    let n = c.to_normalized();
+    rgb_to_oklab(n.red, n.green, n.blue)
+}
+
+fn hue(lab: [f32; 3]) -> f32 {
+    lab[2].atan2(lab[1]).to_degrees()
+}
+
+fn chroma(lab: [f32; 3]) -> f32 {
+    lab[1].hypot(lab[2])
+}
+
+#[test]
+fn source_color_becomes_destination() {
+    let colors = [
+        (RED, BLUE),
+        (rgb(128, 128, 128), rgb(200, 100, 50)),
+        (rgb(30, 160, 90), rgb(250, 240, 10)),
+    ];
+    for transfer in Transfer::ALL {
+        for (src, dst) in colors {
+            let k = kernel(transfer, src, dst, 10.0);
+            let out8 = k.process(src);
+            let out16 = k.process(Rgba16::from_rgba8(src)).to_rgba8();
+            let out32 = k.process(RgbaF32::from_rgba8(src)).to_rgba8();
+            for out in [out8, out16, out32] {
+                assert!(
+                    close_rgb_same_alpha(out, dst, 1),
+                    "{transfer} {src:?} => {out:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn rgb_offset() {
+    let k = kernel(Transfer::RgbOffset, RED, BLUE, 40.0);
+    assert_eq!(k.process(rgb(220, 10, 10)), rgb(20, 10, 210));
+    assert_eq!(k.process(rgb(180, 0, 0)), rgb(0, 0, 200));
+    // 32bit でも 0 より小さくはならない
+    let out = k.process(RgbaF32::from_rgba8(rgb(180, 0, 0)));
+    assert_eq!(out.red, 0.0);
+}
+
+#[test]
+fn luminance_keeps_shading() {
+    let k = kernel(Transfer::Luminance, RED, BLUE, 40.0);
+    // 明るさの差 0.2126 * 20 = 4.25 を全チャンネルに足す
+    assert_eq!(k.process(rgb(220, 0, 0)), rgb(4, 4, 204));
+    assert_eq!(k.process(rgb(200, 20, 0)), rgb(14, 14, 214));
+}
+
+#[test]
+fn hue_shift_recolors_soft_edges() {
+    // 赤と白の中間 (アンチエイリアスの縁) を青に置き換える
+    let edge = rgb(255, 128, 128);
+    let (src, dst) = (rgb(255, 0, 0), rgb(0, 0, 255));
+    for transfer in [Transfer::LabShift, Transfer::OklabShift] {
+        let k = kernel(transfer, src, dst, 140.0);
+        let out = k.process(edge);
+        let lab = oklab(out);
+        // 変更後と同じ青系で、縁の分だけ淡い
+        assert!(
+            (hue(lab) - hue(oklab(dst))).abs() < 15.0,
+            "{transfer} {out:?}"
+        );
+        assert!(chroma(lab) < chroma(oklab(dst)), "{transfer} {out:?}");
+        assert!(lab[0] > oklab(dst)[0], "{transfer} {out:?}");
+    }
+
+    // Replace は縁も同じ青で塗りつぶす
+    let flat = kernel(Transfer::Replace, src, dst, 140.0);
+    assert_eq!(flat.process(edge), dst);
+}
+
+#[test]
+fn gray_source_uses_offset() {
+    // 灰色には色相が無いので、(a, b) の差を移す
+    let src = rgb(128, 128, 128);
+    let dst = rgb(200, 100, 50);
+    for transfer in [Transfer::LabShift, Transfer::OklabShift] {
+        let k = kernel(transfer, src, dst, 20.0);
+        let lighter = k.process(rgb(140, 140, 140));
+        assert!(
+            lighter.red > dst.red && lighter.green > dst.green,
+            "{lighter:?}"
+        );
+        let f = k.process(RgbaF32::from_rgba8(rgb(138, 128, 128)));
+        assert!(f.red.is_finite() && f.green.is_finite() && f.blue.is_finite());
+    }
+}
+
+#[test]
+fn gradients_stay_ordered() {
+    // 暗い赤から明るい赤へのグラデーションは、置き換えた後も明るさの順番が変わらない
+    let ramp: Vec<Rgba8> = (170..=230).map(|r| rgb(r, r / 8, r / 8)).collect();
+    for transfer in Transfer::ALL.into_iter().skip(1) {
+        let k = kernel(transfer, RED, rgb(40, 90, 200), 40.0);
+        let lumas: Vec<f32> = ramp
+            .iter()
+            .map(|&c| {
+                let n = k.process(RgbaF32::from_rgba8(c));
+                luma(n.red, n.green, n.blue)
+            })
+            .collect();
+        assert!(
+            lumas.windows(2).all(|w| w[1] >= w[0] - 1.0e-5),
+            "{transfer} {lumas:?}"
+        );
+        assert!(lumas[lumas.len() - 1] > lumas[0], "{transfer}");
+    }
+}
+
+#[test]
+fn softness_blends_toward_transferred_color() {
+    let k = kernel(Transfer::RgbOffset, RED, BLUE, 10.0).with_softness(20.0 / 255.0);
+    // 差 20 は softness の真ん中なので半分だけ寄る
+    let out = k.process(rgb(220, 0, 0));
+    assert!(close_rgb_same_alpha(out, rgb(120, 0, 100), 1), "{out:?}");
+}
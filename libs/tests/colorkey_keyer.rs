@@ -0,0 +1,260 @@
+// ColorKey のキーヤー (with_keyer) のテストです。
+// キー色から離れていくグラデーションで、アルファの変化とデスピルを確かめます。
+
+mod common;
+
+use common::{close_rgba, rgb};
+use libs::kernels::colorkey::{ColorKey, Keyer};
+use libs::kernels::PixelKernel;
+use libs::{ColorMetric, Pixel, Rgba16, Rgba8, RgbaF32};
+
+const GREEN: Rgba8 = Rgba8::new(0, 255, 0, 255);
+const BACK: Rgba8 = Rgba8::new(10, 20, 30, 255);
+
+fn keyer(keyer: Keyer) -> ColorKey {
+    ColorKey::new(0.0, ColorMetric::RgbBox, GREEN, BACK).with_keyer(Some(keyer))
+}
+
+// 緑から赤へのグラデーション。RgbBox の距離は i / 255
+fn ramp() -> impl Iterator<Item = (u8, Rgba8)> {
+    (0..=255u8).map(|i| (i, rgb(i, 255 - i, 0)))
+}
+
+#[test]
+fn alpha_ramps_between_inner_and_outer() {
+    let k = keyer(Keyer {
+        inner: 0.2,
+        outer: 0.6,
+        ..Keyer::default()
+    });
+    for (i, c) in ramp() {
+        let alpha = k.process(c).alpha;
+        let d = i as f32 / 255.0;
+        if d <= 0.2 {
+            assert_eq!(alpha, 0, "{i}");
+        } else if d >= 0.6 {
+            assert_eq!(alpha, 255, "{i}");
+        } else {
+            let expected = (d - 0.2) / 0.4 * 255.0;
+            assert!((alpha as f32 - expected).abs() <= 1.0, "{i} {alpha}");
+        }
+    }
+    // 完全に透明なところは背景色になる
+    assert_eq!(k.process(GREEN), Rgba8::new(10, 20, 30, 0));
+}
+
+#[test]
+fn hard_edge_when_inner_equals_outer() {
+    let k = keyer(Keyer {
+        inner: 0.5,
+        outer: 0.5,
+        ..Keyer::default()
+    });
+    for (i, c) in ramp() {
+        let alpha = k.process(c).alpha;
+        assert_eq!(alpha, if i as f32 / 255.0 <= 0.5 { 0 } else { 255 }, "{i}");
+    }
+}
+
+#[test]
+fn alpha_is_multiplied() {
+    let k = keyer(Keyer {
+        inner: 0.0,
+        outer: 1.0,
+        ..Keyer::default()
+    });
+    // 距離 0.5 で半分、元のアルファ 128 に掛ける
+    let out = k.process(Rgba8::new(0, 255, 128, 128));
+    assert!(out.alpha.abs_diff(64) <= 1, "{out:?}");
+}
+
+#[test]
+fn depths_agree() {
+    let k = keyer(Keyer {
+        inner: 0.1,
+        outer: 0.7,
+        despill: 1.0,
+        choke: 0.2,
+        softness: 0.5,
+    });
+    for (i, c) in ramp() {
+        let out8 = k.process(c);
+        let out16 = k.process(Rgba16::from_rgba8(c)).to_rgba8();
+        let out32 = k.process(RgbaF32::from_rgba8(c)).to_rgba8();
+        assert!(close_rgba(out8, out16, 1), "{i} {out8:?} {out16:?}");
+        assert!(close_rgba(out8, out32, 1), "{i} {out8:?} {out32:?}");
+    }
+}
+
+#[test]
+fn despill_neutralizes_key_hue() {
+    let k = keyer(Keyer {
+        inner: 0.05,
+        outer: 0.1,
+        despill: 1.0,
+        ..Keyer::default()
+    });
+    // 緑がかった灰色は灰色になる (RGB の平均は変わらない)
+    assert!(close_rgba(
+        k.process(rgb(51, 204, 51)),
+        rgb(102, 102, 102),
+        1
+    ));
+    // 黄色からは緑だけが抜けて橙になる
+    assert!(close_rgba(
+        k.process(rgb(204, 204, 51)),
+        rgb(230, 153, 77),
+        1
+    ));
+    // 緑の成分が無い色はそのまま
+    assert_eq!(k.process(rgb(204, 51, 51)), rgb(204, 51, 51));
+    assert_eq!(k.process(rgb(200, 0, 200)), rgb(200, 0, 200));
+
+    let half = keyer(Keyer {
+        inner: 0.05,
+        outer: 0.1,
+        despill: 0.5,
+        ..Keyer::default()
+    });
+    assert!(close_rgba(
+        half.process(rgb(51, 204, 51)),
+        rgb(77, 153, 77),
+        1
+    ));
+}
+
+#[test]
+fn despill_keeps_brightness_along_gradient() {
+    let k = keyer(Keyer {
+        inner: 0.0,
+        outer: 0.0,
+        despill: 1.0,
+        ..Keyer::default()
+    });
+    // 灰色から緑へのグラデーション
+    for i in 1..=127u8 {
+        let c = rgb(128 - i, 128 + i, 128 - i);
+        let out = k.process(c);
+        let mean = |p: Rgba8| (p.red as i32 + p.green as i32 + p.blue as i32) / 3;
+        assert!((mean(out) - mean(c)).abs() <= 1, "{c:?} {out:?}");
+        assert!(out.green.abs_diff(out.red) <= 1, "{c:?} {out:?}");
+    }
+}
+
+#[test]
+fn gray_key_has_no_despill() {
+    let k =
+        ColorKey::new(0.0, ColorMetric::RgbBox, rgb(128, 128, 128), BACK).with_keyer(Some(Keyer {
+            inner: 0.0,
+            outer: 0.1,
+            despill: 1.0,
+            ..Keyer::default()
+        }));
+    assert_eq!(k.process(rgb(51, 204, 51)), rgb(51, 204, 51));
+}
+
+#[test]
+fn choke_and_softness_reshape_the_matte() {
+    let choke = |choke| Keyer {
+        choke,
+        ..Keyer::default()
+    };
+    assert_eq!(choke(0.5).adjust_matte(0.75), 0.5);
+    assert_eq!(choke(0.5).adjust_matte(0.25), 0.0);
+    assert_eq!(choke(0.5).adjust_matte(1.0), 1.0);
+    assert_eq!(choke(-0.5).adjust_matte(0.25), 0.5);
+    assert_eq!(choke(-0.5).adjust_matte(0.0), 0.0);
+    assert_eq!(choke(1.0).adjust_matte(0.99), 0.0);
+    assert_eq!(choke(-1.0).adjust_matte(0.01), 1.0);
+
+    let soft = Keyer {
+        softness: 1.0,
+        ..Keyer::default()
+    };
+    assert_eq!(soft.adjust_matte(0.25), 0.15625);
+    assert_eq!(soft.adjust_matte(0.5), 0.5);
+    assert_eq!(soft.adjust_matte(1.0), 1.0);
+}
+
+#[test]
+fn choke_shrinks_the_matte_along_gradient() {
+    let base = Keyer {
+        inner: 0.1,
+        outer: 0.5,
+        ..Keyer::default()
+    };
+    let plain = keyer(base);
+    let choked = keyer(Keyer { choke: 0.3, ..base });
+    let spread = keyer(Keyer {
+        choke: -0.3,
+        ..base
+    });
+    let soft = keyer(Keyer {
+        softness: 1.0,
+        ..base
+    });
+    let mut last = [0u8; 4];
+    for (i, c) in ramp() {
+        let alphas = [&plain, &choked, &spread, &soft].map(|k| k.process(c).alpha);
+        let [a, choked, spread, _] = alphas;
+        assert!(choked <= a && a <= spread, "{i} {alphas:?}");
+        // どれも距離が離れるほど不透明になる
+        assert!(alphas.iter().zip(last).all(|(a, l)| *a >= l), "{i}");
+        last = alphas;
+    }
+    assert_eq!(last, [255; 4]);
+}
+
+#[test]
+fn classic_softness_falls_off_linearly() {
+    // threshold 10、softness 20 (RGB の距離) で赤を抜く。F's Plugins には無い機能
+    let k = ColorKey::new(0.1, ColorMetric::RgbEuclidean, rgb(255, 0, 0), rgb(0, 0, 0))
+        .with_softness(0.2);
+    let cases = [
+        (255, Rgba8::new(0, 0, 0, 0)),
+        (230, Rgba8::new(0, 0, 0, 0)),
+        (217, Rgba8::new(53, 0, 0, 63)),
+        (204, Rgba8::new(102, 0, 0, 128)),
+        (191, Rgba8::new(144, 0, 0, 192)),
+        (178, rgb(178, 0, 0)),
+    ];
+    for (r, expected) in cases {
+        let out = k.process(rgb(r, 0, 0));
+        // 補間の丸めの差を許す
+        assert!(close_rgba(out, expected, 1), "{r} {out:?} {expected:?}");
+    }
+}
+
+#[test]
+fn unpremultiply_keeps_premultiplied_output_valid() {
+    let keyer = keyer(Keyer {
+        inner: 0.1,
+        outer: 0.6,
+        despill: 1.0,
+        ..Keyer::default()
+    })
+    .with_unpremultiply(true);
+    let hard = ColorKey::new(0.1, ColorMetric::RgbBox, GREEN, BACK)
+        .with_softness(0.4)
+        .with_unpremultiply(true);
+    for k in [&keyer, &hard] {
+        for (i, c) in ramp() {
+            for a in [255u8, 200, 128, 40, 1] {
+                let input = c.with_alpha(a).premultiply();
+                let out = k.process(input);
+                assert!(
+                    out.red <= out.alpha && out.green <= out.alpha && out.blue <= out.alpha,
+                    "{i} {input:?} => {out:?}"
+                );
+                let out = k.process(RgbaF32::from_rgba8(input));
+                assert!(out.red.max(out.green).max(out.blue) <= out.alpha + 1.0e-6);
+            }
+        }
+    }
+    // 半透明の縁も不透明な部分と同じ色として抜ける
+    let edge = GREEN.with_alpha(128).premultiply();
+    assert_eq!(keyer.process(edge).alpha, 0);
+    // 抜かないピクセルは元のまま
+    let red = Rgba8::new(200, 0, 0, 255).with_alpha(90).premultiply();
+    assert_eq!(keyer.process(red), red);
+}
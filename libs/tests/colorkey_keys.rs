@@ -0,0 +1,162 @@
+// ColorKey で複数の色を抜く (with_keys) テストと、シーケンスデータに保存するキー色の一覧のテストです。
+
+mod common;
+
+use common::rgb;
+use libs::kernels::colorkey::{ColorKey, KeyColor, Keyer};
+use libs::kernels::PixelKernel;
+use libs::key_list::{KeyEdit, KeyList};
+use libs::list::Edited::{Emptied, Selected, Unchanged};
+use libs::sequence::{flatten, unflatten};
+use libs::{ColorMetric, Pixel, Rgba16, Rgba8};
+
+const SKY: Rgba8 = Rgba8::new(120, 180, 230, 255);
+const GRASS: Rgba8 = Rgba8::new(60, 160, 40, 255);
+const BACK: Rgba8 = Rgba8::new(0, 0, 0, 255);
+const KEYED: Rgba8 = Rgba8::new(0, 0, 0, 0);
+
+fn key(color: Rgba8, tolerance: Option<f32>) -> KeyColor {
+    KeyColor { color, tolerance }
+}
+
+fn kernel(keys: &[KeyColor]) -> ColorKey {
+    ColorKey::new(10.0 / 255.0, ColorMetric::RgbBox, keys[0].color, BACK).with_keys(keys)
+}
+
+#[test]
+fn every_key_is_removed_in_one_pass() {
+    let k = kernel(&[KeyColor::new(SKY), KeyColor::new(GRASS)]);
+    assert_eq!(k.process(SKY), KEYED);
+    assert_eq!(k.process(GRASS), KEYED);
+    assert_eq!(k.process(rgb(125, 185, 225)), KEYED);
+    assert_eq!(k.process(rgb(200, 50, 50)), rgb(200, 50, 50));
+    let deep = Rgba16::from_rgba8(GRASS);
+    assert_eq!(k.process(deep), Rgba16::from_rgba8(KEYED));
+}
+
+#[test]
+fn single_key_matches_new() {
+    let plain = ColorKey::new(10.0 / 255.0, ColorMetric::RgbBox, SKY, BACK).with_softness(0.1);
+    let keys = kernel(&[KeyColor::new(SKY)]).with_softness(0.1);
+    let empty = ColorKey::new(10.0 / 255.0, ColorMetric::RgbBox, SKY, BACK)
+        .with_softness(0.1)
+        .with_keys(&[]);
+    for i in 0..=255u8 {
+        let c = rgb(i, 180, 230);
+        assert_eq!(keys.process(c), plain.process(c), "{i}");
+        assert_eq!(empty.process(c), plain.process(c), "{i}");
+    }
+}
+
+#[test]
+fn keys_have_their_own_tolerance() {
+    let k = kernel(&[KeyColor::new(SKY), key(GRASS, Some(30.0 / 255.0))]);
+    // SKY は全体の 10、GRASS は 30
+    assert_eq!(k.process(rgb(140, 180, 230)), rgb(140, 180, 230));
+    assert_eq!(k.process(rgb(130, 180, 230)), KEYED);
+    assert_eq!(k.process(rgb(80, 160, 40)), KEYED);
+    assert_eq!(k.process(rgb(100, 160, 40)), rgb(100, 160, 40));
+}
+
+#[test]
+fn soft_alpha_is_the_minimum_across_keys() {
+    let keyer = Keyer {
+        inner: 0.05,
+        outer: 0.4,
+        ..Keyer::default()
+    };
+    let soft = |keys: &[KeyColor]| kernel(keys).with_keyer(Some(keyer));
+    let sky = soft(&[KeyColor::new(SKY)]);
+    let grass = soft(&[key(GRASS, Some(0.1))]);
+    let both = soft(&[KeyColor::new(SKY), key(GRASS, Some(0.1))]);
+    // SKY から GRASS へのグラデーション
+    for i in 0..=32u8 {
+        let t = i as f32 / 32.0;
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        let c = rgb(
+            mix(SKY.red, GRASS.red),
+            mix(SKY.green, GRASS.green),
+            mix(SKY.blue, GRASS.blue),
+        );
+        let expected = sky.process(c).alpha.min(grass.process(c).alpha);
+        assert_eq!(both.process(c).alpha, expected, "{c:?}");
+    }
+    assert_eq!(both.process(SKY).alpha, 0);
+    assert_eq!(both.process(GRASS).alpha, 0);
+    assert_eq!(both.process(rgb(255, 0, 0)).alpha, 255);
+}
+
+#[test]
+fn despill_uses_the_nearest_key() {
+    let green = rgb(0, 255, 0);
+    let blue = rgb(0, 0, 255);
+    let k = kernel(&[KeyColor::new(green), KeyColor::new(blue)]).with_keyer(Some(Keyer {
+        inner: 0.05,
+        outer: 0.1,
+        despill: 1.0,
+        ..Keyer::default()
+    }));
+    let close = |a: Rgba8, b: Rgba8| {
+        a.red.abs_diff(b.red) <= 1 && a.green.abs_diff(b.green) <= 1 && a.blue.abs_diff(b.blue) <= 1
+    };
+    // 緑がかった灰色からは緑、青みがかった灰色からは青だけを抜く
+    let out = k.process(rgb(51, 204, 51));
+    assert!(close(out, rgb(102, 102, 102)), "{out:?}");
+    let out = k.process(rgb(51, 51, 204));
+    assert!(close(out, rgb(102, 102, 102)), "{out:?}");
+}
+
+#[test]
+fn key_count_is_not_limited() {
+    // プラグインの固定の 4 色を超えても全部抜ける
+    let keys: Vec<_> = (0..40u8)
+        .map(|i| KeyColor::new(rgb(i * 6, 255 - i * 6, 128)))
+        .collect();
+    let k = kernel(&keys);
+    for key in &keys {
+        assert_eq!(k.process(key.color), KEYED, "{key:?}");
+    }
+    assert_eq!(k.process(rgb(255, 0, 0)), rgb(255, 0, 0));
+}
+
+#[test]
+fn key_list_edits() {
+    let mut list = KeyList::default();
+    assert_eq!(list.apply(KeyEdit::Add(KeyColor::new(SKY))), Selected(0));
+    assert_eq!(list.apply(KeyEdit::Add(key(GRASS, Some(0.2)))), Selected(1));
+    assert_eq!(list.apply(KeyEdit::Add(KeyColor::new(BACK))), Selected(2));
+    assert_eq!(
+        list.apply(KeyEdit::Replace(0, KeyColor::new(rgb(1, 2, 3)))),
+        Selected(0)
+    );
+    assert_eq!(list.keys[0].color, rgb(1, 2, 3));
+    // 末尾を消したら新しい末尾を選ぶ
+    assert_eq!(list.apply(KeyEdit::Remove(2)), Selected(1));
+    assert_eq!(list.apply(KeyEdit::Remove(0)), Selected(0));
+    assert_eq!(list.keys, vec![key(GRASS, Some(0.2))]);
+    // 範囲外は何もしない
+    assert_eq!(list.apply(KeyEdit::Remove(5)), Unchanged);
+    assert_eq!(
+        list.apply(KeyEdit::Replace(1, KeyColor::new(SKY))),
+        Unchanged
+    );
+    assert_eq!(list.len(), 1);
+    // 最後の1つを消したら、選択できる色は無い
+    assert_eq!(list.apply(KeyEdit::Remove(0)), Emptied);
+    assert!(list.is_empty());
+}
+
+#[test]
+fn key_list_round_trip() {
+    let list = KeyList {
+        keys: vec![KeyColor::new(SKY), key(GRASS, Some(0.25))],
+    };
+    let (version, bytes) = flatten(&list).unwrap();
+    assert_eq!(version, 1);
+    assert_eq!(
+        String::from_utf8(bytes.clone()).unwrap(),
+        r#"{"version":1,"data":{"keys":[{"color":"78b4e6"},{"color":"3ca028","tolerance":0.25}]}}"#
+    );
+    assert_eq!(unflatten::<KeyList>(&bytes).unwrap(), list);
+    assert!(unflatten::<KeyList>(&[]).unwrap().is_empty());
+}
@@ -0,0 +1,130 @@
+// ColorKey の確認用の表示 (with_view) のテストです。
+
+mod common;
+
+use common::rgb;
+use libs::kernels::colorkey::{ColorKey, Keyer, ViewMode, OVERLAY_COLOR};
+use libs::kernels::PixelKernel;
+use libs::{ColorMetric, Pixel, Rgba16, Rgba8, RgbaF32};
+
+fn gray(v: u8) -> Rgba8 {
+    rgb(v, v, v)
+}
+
+const GREEN: Rgba8 = Rgba8::new(0, 255, 0, 255);
+
+// 緑から距離 0.2 までは透明、0.6 からは不透明
+fn kernel(view: ViewMode) -> ColorKey {
+    ColorKey::new(0.0, ColorMetric::RgbBox, GREEN, rgb(10, 20, 30))
+        .with_keyer(Some(Keyer {
+            inner: 0.2,
+            outer: 0.6,
+            ..Keyer::default()
+        }))
+        .with_view(view)
+}
+
+// 0.4 は真ん中なので半透明
+const KEYED: Rgba8 = Rgba8::new(20, 245, 0, 255);
+const PARTIAL: Rgba8 = Rgba8::new(102, 153, 0, 255);
+const OPAQUE: Rgba8 = Rgba8::new(200, 30, 40, 255);
+
+#[test]
+fn composite_is_the_default() {
+    let plain =
+        ColorKey::new(0.0, ColorMetric::RgbBox, GREEN, rgb(10, 20, 30)).with_keyer(Some(Keyer {
+            inner: 0.2,
+            outer: 0.6,
+            ..Keyer::default()
+        }));
+    let k = kernel(ViewMode::Composite);
+    for c in [KEYED, PARTIAL, OPAQUE] {
+        assert_eq!(k.process(c), plain.process(c));
+    }
+    assert_eq!(k.process(KEYED), Rgba8::new(10, 20, 30, 0));
+}
+
+#[test]
+fn matte_shows_alpha_as_gray() {
+    let k = kernel(ViewMode::Matte);
+    assert_eq!(k.process(KEYED), gray(0));
+    assert_eq!(k.process(OPAQUE), gray(255));
+    let half = k.process(PARTIAL);
+    assert!(
+        half.red.abs_diff(128) <= 1 && half.red == half.blue,
+        "{half:?}"
+    );
+    assert_eq!(half.alpha, 255);
+    // 元から半透明なピクセルは、そのアルファが灰色になる
+    assert_eq!(k.process(Rgba8::new(200, 30, 40, 64)), gray(64));
+}
+
+#[test]
+fn status_shows_three_levels() {
+    let k = kernel(ViewMode::Status);
+    assert_eq!(k.process(KEYED), gray(0));
+    assert_eq!(k.process(OPAQUE), gray(255));
+    assert_eq!(k.process(PARTIAL), gray(128));
+    // ほとんど不透明でも、少しでも抜けていれば灰色
+    assert_eq!(k.process(rgb(150, 105, 0)), gray(128));
+    assert_eq!(
+        k.process(Rgba16::from_rgba8(OPAQUE)),
+        Rgba16::from_rgba8(gray(255))
+    );
+    assert_eq!(
+        k.process(RgbaF32::from_rgba8(KEYED)),
+        RgbaF32::new(0.0, 0.0, 0.0, 1.0)
+    );
+}
+
+#[test]
+fn status_along_gradient() {
+    let k = kernel(ViewMode::Status);
+    // 緑から赤へのグラデーション。RgbBox の距離は i / 255
+    for i in 0..=255u8 {
+        let out = k.process(rgb(i, 255 - i, 0));
+        let d = i as f32 / 255.0;
+        let expected = if d <= 0.2 {
+            0
+        } else if d >= 0.6 {
+            255
+        } else {
+            128
+        };
+        assert_eq!(out, gray(expected), "{i}");
+    }
+}
+
+#[test]
+fn overlay_tints_keyed_pixels() {
+    let k = kernel(ViewMode::Overlay);
+    let tinted = |c: Rgba8, t: f32| c.lerp(OVERLAY_COLOR, t);
+    // 抜いていないピクセルは元のまま
+    assert_eq!(k.process(OPAQUE), OPAQUE);
+    // 完全に抜いたピクセルは 60%、半分抜いたピクセルは 30% 寄せる
+    assert_eq!(k.process(KEYED), tinted(KEYED, 0.6));
+    let out = k.process(PARTIAL);
+    let expected = tinted(PARTIAL, 0.3);
+    assert!(
+        out.red.abs_diff(expected.red) <= 1 && out.green.abs_diff(expected.green) <= 1,
+        "{out:?} {expected:?}"
+    );
+    // アルファは元のまま
+    let clear = Rgba8::new(0, 255, 0, 100);
+    assert_eq!(k.process(clear).alpha, 100);
+    assert_eq!(
+        k.process(Rgba8::new(0, 255, 0, 0)),
+        Rgba8::new(0, 255, 0, 0)
+    );
+}
+
+#[test]
+fn views_work_with_the_classic_key() {
+    let k = |view| ColorKey::new(0.1, ColorMetric::RgbBox, GREEN, rgb(0, 0, 0)).with_view(view);
+    assert_eq!(k(ViewMode::Matte).process(GREEN), gray(0));
+    assert_eq!(k(ViewMode::Status).process(OPAQUE), gray(255));
+    assert_eq!(
+        k(ViewMode::Overlay).process(GREEN),
+        GREEN.lerp(OVERLAY_COLOR, 0.6)
+    );
+}
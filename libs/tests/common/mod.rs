@@ -0,0 +1,40 @@
+// 結合テストで共通に使う関数です。使うテストファイルで `mod common;` と書きます。
+// ファイルごとに使わない関数があるので dead_code は許します。
+#![allow(dead_code)]
+
+use libs::kernels::colorchange::{ColorChange, MatchMode};
+use libs::{ColorMetric, Rgba8};
+
+/// 不透明な色
+pub fn rgb(r: u8, g: u8, b: u8) -> Rgba8 {
+    Rgba8::new(r, g, b, 255)
+}
+
+/// アルファを含む全チャンネルの差が `max` 以内なら true を返します。
+pub fn close_rgba(a: Rgba8, b: Rgba8, max: u8) -> bool {
+    a.red.abs_diff(b.red) <= max
+        && a.green.abs_diff(b.green) <= max
+        && a.blue.abs_diff(b.blue) <= max
+        && a.alpha.abs_diff(b.alpha) <= max
+}
+
+/// アルファが等しく、RGB の差が `max` 以内なら true を返します。
+pub fn close_rgb_same_alpha(a: Rgba8, b: Rgba8, max: u8) -> bool {
+    a.alpha == b.alpha && close_rgba(a, b, max)
+}
+
+pub const RED_A: Rgba8 = Rgba8::new(100, 0, 0, 255);
+pub const RED_B: Rgba8 = Rgba8::new(110, 0, 0, 255);
+pub const TO_A: Rgba8 = Rgba8::new(0, 0, 200, 255);
+pub const TO_B: Rgba8 = Rgba8::new(0, 200, 0, 255);
+
+/// RED_A => TO_A と RED_B => TO_B の2組です。
+/// 許容誤差 (どちらも 20) が 90 - 120 で重なっていて、どちらにも一致する色の扱いを `mode` で確かめます。
+pub fn overlapping(mode: MatchMode) -> ColorChange {
+    ColorChange::new(
+        20.0 / 255.0,
+        ColorMetric::RgbBox,
+        vec![(RED_A, TO_A), (RED_B, TO_B)],
+    )
+    .with_mode(mode)
+}
@@ -0,0 +1,177 @@
+// CreateAlpha のモードごとのテストです。
+// 出力を背景の上に合成し直すと、入力 (を背景の上に合成したもの) に戻ることを確かめます。
+
+mod common;
+
+use common::rgb;
+use libs::kernels::createalpha::{AlphaMode, CreateAlpha};
+use libs::kernels::PixelKernel;
+use libs::{Pixel, Rgba16, Rgba8, RgbaF32};
+use proptest::prelude::*;
+
+const BLACK: Rgba8 = Rgba8::new(0, 0, 0, 255);
+const WHITE: Rgba8 = Rgba8::new(255, 255, 255, 255);
+const PAPER: Rgba8 = Rgba8::new(236, 228, 205, 255);
+const GREEN: Rgba8 = Rgba8::new(0, 177, 64, 255);
+
+// 背景の上に合成した色 (0.0 - 1.0)
+fn over<P: Pixel>(pixel: P, back: Rgba8) -> [f32; 3] {
+    let p = pixel.to_normalized();
+    let b = back.to_normalized();
+    let a = p.alpha;
+    [
+        p.red * a + b.red * (1.0 - a),
+        p.green * a + b.green * (1.0 - a),
+        p.blue * a + b.blue * (1.0 - a),
+    ]
+}
+
+// 出力を背景の上に合成すると、入力を背景の上に合成した色に `max` 以内で戻る
+fn assert_round_trip<P: Pixel>(k: &CreateAlpha, pixel: P, back: Rgba8, max: f32) {
+    let out = k.process(pixel);
+    let (expected, actual) = (over(pixel, back), over(out, back));
+    for i in 0..3 {
+        assert!(
+            (expected[i] - actual[i]).abs() <= max,
+            "{pixel:?} on {back:?} => {out:?}"
+        );
+    }
+}
+
+fn unmultiply_modes() -> [(CreateAlpha, Rgba8); 5] {
+    [
+        (CreateAlpha::default(), BLACK),
+        (CreateAlpha::new(AlphaMode::White, BLACK), WHITE),
+        (CreateAlpha::new(AlphaMode::Color, PAPER), PAPER),
+        (CreateAlpha::new(AlphaMode::Color, GREEN), GREEN),
+        (CreateAlpha::new(AlphaMode::Color, BLACK), BLACK),
+    ]
+}
+
+// 8bit の 1 段分 (合成し直したときの丸めの差)
+const STEP8: f32 = 1.0 / 255.0 + 1.0e-5;
+const STEP16: f32 = 1.0 / 32768.0 + 1.0e-6;
+
+#[test]
+fn unmultiply_round_trip_8bit() {
+    for (k, back) in unmultiply_modes() {
+        for r in (0..=255u8).step_by(15) {
+            for g in (0..=255u8).step_by(15) {
+                for b in (0..=255u8).step_by(15) {
+                    assert_round_trip(&k, rgb(r, g, b), back, STEP8);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn unmultiply_round_trip_semi_transparent() {
+    for (k, back) in unmultiply_modes() {
+        for a in [0u8, 1, 64, 128, 200, 254] {
+            for c in [rgb(255, 0, 0), rgb(30, 60, 90), rgb(250, 250, 250)] {
+                assert_round_trip(&k, c.with_alpha(a), back, STEP8);
+                assert_round_trip(&k, RgbaF32::from_rgba8(c.with_alpha(a)), back, 1.0e-5);
+            }
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn unmultiply_round_trip_16bit(
+        c in any::<[u16; 3]>(),
+        back in any::<[u8; 3]>(),
+        mode in 0usize..3,
+    ) {
+        let p = Rgba16::new(c[0] % 32769, c[1] % 32769, c[2] % 32769, 32768);
+        let back = rgb(back[0], back[1], back[2]);
+        let mode = [AlphaMode::Black, AlphaMode::White, AlphaMode::Color][mode];
+        let k = CreateAlpha::new(mode, back);
+        let back = match mode {
+            AlphaMode::Black => BLACK,
+            AlphaMode::White => WHITE,
+            _ => back,
+        };
+        assert_round_trip(&k, p, back, STEP16);
+    }
+
+    #[test]
+    fn unmultiply_round_trip_32bit(c in any::<[u8; 3]>(), back in any::<[u8; 3]>()) {
+        let back = rgb(back[0], back[1], back[2]);
+        let k = CreateAlpha::new(AlphaMode::Color, back);
+        assert_round_trip(&k, RgbaF32::from_rgba8(rgb(c[0], c[1], c[2])), back, 1.0e-5);
+    }
+}
+
+#[test]
+fn black_mode_is_unchanged() {
+    let k = CreateAlpha::default();
+    assert_eq!(k.process(rgb(128, 64, 0)), Rgba8::new(255, 128, 0, 128));
+    assert_eq!(k.process(BLACK), Rgba8::new(0, 0, 0, 0));
+    assert_eq!(k.process(WHITE), WHITE);
+}
+
+#[test]
+fn black_mode_32bit_clamps_like_the_original() {
+    let k = CreateAlpha::default();
+    // 半透明は掛けた色を 0.0 - 32.0 に収めてから、RGB の最大値をアルファにして割る
+    let p = k.process(RgbaF32::new(100.0, -1.0, 0.5, 0.5));
+    assert_eq!(p, RgbaF32::new(1.0, 0.0, 0.25 / 32.0, 32.0));
+    // 不透明なら範囲外の値もそのまま使う
+    let p = k.process(RgbaF32::new(40.0, 2.0, -1.0, 1.0));
+    assert_eq!(p, RgbaF32::new(1.0, 2.0 / 40.0, -1.0 / 40.0, 40.0));
+}
+
+#[test]
+fn white_paper_becomes_transparent() {
+    let k = CreateAlpha::new(AlphaMode::White, BLACK);
+    assert_eq!(k.process(WHITE), Rgba8::new(255, 255, 255, 0));
+    assert_eq!(k.process(BLACK), BLACK);
+    // 白の上の 50% の灰色は、半透明の黒
+    assert_eq!(k.process(rgb(128, 128, 128)), Rgba8::new(0, 0, 0, 127));
+    // 白の上の薄い赤は、半透明の赤
+    assert_eq!(k.process(rgb(255, 128, 128)), Rgba8::new(255, 0, 0, 127));
+    let deep = k.process(RgbaF32::from_rgba8(rgb(255, 128, 128)));
+    assert!((deep.alpha - 127.0 / 255.0).abs() < 1.0e-6 && deep.green == 0.0);
+}
+
+#[test]
+fn background_color_becomes_transparent() {
+    let k = CreateAlpha::new(AlphaMode::Color, PAPER);
+    assert_eq!(k.process(PAPER), PAPER.with_alpha(0));
+    // 背景より暗い線は、背景色を取り除いた色になる
+    let ink = k.process(rgb(40, 30, 20));
+    assert!(ink.alpha > 200, "{ink:?}");
+    // 白の背景は White と同じ
+    let white = CreateAlpha::new(AlphaMode::Color, WHITE);
+    let by_mode = CreateAlpha::new(AlphaMode::White, BLACK);
+    for c in [rgb(10, 200, 30), rgb(250, 240, 230), rgb(128, 128, 128)] {
+        assert_eq!(white.process(c), by_mode.process(c));
+    }
+}
+
+#[test]
+fn luminance_to_alpha() {
+    let rec601 = CreateAlpha::new(AlphaMode::Luma601, BLACK);
+    let rec709 = CreateAlpha::new(AlphaMode::Luma709, BLACK);
+    // 灰色は明るさがそのままアルファになり、黒の上で元に戻る
+    for v in (0..=255u8).step_by(5) {
+        for k in [&rec601, &rec709] {
+            let out = k.process(rgb(v, v, v));
+            assert!(out.alpha.abs_diff(v) <= 1, "{v} {out:?}");
+            assert_round_trip(k, rgb(v, v, v), BLACK, STEP8);
+        }
+    }
+    // 係数の違い: 緑は 0.587 と 0.7152
+    assert_eq!(rec601.process(rgb(0, 255, 0)), Rgba8::new(0, 255, 0, 150));
+    assert_eq!(rec709.process(rgb(0, 255, 0)), Rgba8::new(0, 255, 0, 182));
+    assert_eq!(rec709.process(BLACK), Rgba8::new(0, 0, 0, 0));
+    // 明るさ以下のチャンネルは元に戻る
+    let out = rec709.process(rgb(120, 100, 110));
+    let back = over(out, BLACK);
+    assert!((back[1] - 100.0 / 255.0).abs() <= STEP8, "{out:?}");
+    // 白も 16bit で不透明な白になる
+    let white = Rgba16::from_rgba8(WHITE);
+    assert_eq!(rec601.process(white), white);
+}
@@ -0,0 +1,130 @@
+// 整数のチャンネル演算 (libs::fixed) のテストです。
+// 8bit は全部の組み合わせを、16bit は proptest で、四捨五入した実数の計算と比べます。
+
+use libs::fixed::*;
+use libs::kernels::createalpha::{AlphaMode, CreateAlpha};
+use libs::kernels::PixelKernel;
+use libs::{Pixel, Rgba16, Rgba8};
+use proptest::prelude::*;
+
+// AE SDK のサンプルの INT_MULT
+fn int_mult(a: u8, b: u8) -> u8 {
+    let t = a as u32 * b as u32 + 0x80;
+    (((t >> 8) + t) >> 8) as u8
+}
+
+// 0.5 は切り上げる
+fn round(v: f64, max: f64) -> f64 {
+    (v + 0.5).floor().min(max)
+}
+
+#[test]
+fn mul8_matches_int_mult() {
+    for a in 0..=255u8 {
+        for b in 0..=255u8 {
+            assert_eq!(mul8(a, b), int_mult(a, b), "{a} {b}");
+            assert_eq!(mul8(a, b) as f64, round(a as f64 * b as f64 / 255.0, 255.0));
+        }
+    }
+}
+
+#[test]
+fn div8_rounds() {
+    for a in 0..=255u8 {
+        assert_eq!(div8(a, 0), 0);
+        for b in 1..=255u8 {
+            let expected = round(a as f64 * 255.0 / b as f64, 255.0);
+            assert_eq!(div8(a, b) as f64, expected, "{a} {b}");
+        }
+    }
+}
+
+#[test]
+fn lerp8_rounds() {
+    for a in 0..=255u8 {
+        for b in 0..=255u8 {
+            assert_eq!(lerp8(a, b, 0), a);
+            assert_eq!(lerp8(a, b, 255), b);
+            for t in 0..=255u8 {
+                let v = a as f64 + (b as f64 - a as f64) * t as f64 / 255.0;
+                let out = lerp8(a, b, t) as f64;
+                // 差を四捨五入してから足すので、0.5 ちょうどのときだけ向きが変わる
+                assert!((out - v).abs() <= 0.5 + 1.0e-9, "{a} {b} {t}");
+            }
+        }
+    }
+}
+
+#[test]
+fn premultiply8_round_trip() {
+    for a in 0..=255u8 {
+        for c in 0..=a {
+            let p = Rgba8::new(c, a - c, c / 3, a);
+            assert_eq!(premultiply8(unpremultiply8(p)), p, "{p:?}");
+            // Pixel のメソッドも同じ計算
+            assert_eq!(p.unpremultiply(), unpremultiply8(p));
+        }
+        let p = Rgba8::new(255, 128, 1, a);
+        assert_eq!(p.premultiply(), premultiply8(p));
+    }
+}
+
+proptest! {
+    #[test]
+    fn mul16_rounds(a in 0..=32768u16, b in 0..=32768u16) {
+        let expected = round(a as f64 * b as f64 / 32768.0, 32768.0);
+        prop_assert_eq!(mul16(a, b) as f64, expected);
+    }
+
+    #[test]
+    fn div16_rounds(a in 0..=32768u16, b in 1..=32768u16) {
+        let expected = round(a as f64 * 32768.0 / b as f64, 32768.0);
+        prop_assert_eq!(div16(a, b) as f64, expected);
+    }
+
+    #[test]
+    fn lerp16_rounds(a in 0..=32768u16, b in 0..=32768u16, t in 0..=32768u16) {
+        let v = a as f64 + (b as f64 - a as f64) * t as f64 / 32768.0;
+        prop_assert!((lerp16(a, b, t) as f64 - v).abs() <= 0.5);
+        prop_assert_eq!(lerp16(a, b, 0), a);
+        prop_assert_eq!(lerp16(a, b, 32768), b);
+    }
+
+    #[test]
+    fn overflow_safe_16bit(a in any::<u16>(), b in any::<u16>(), t in any::<u16>()) {
+        // 範囲外の値でもパニックせず、MAX で止まる
+        prop_assert!(mul16(a, b) <= 32768);
+        prop_assert!(div16(a, b) <= 32768);
+        let _ = lerp16(a.min(32768), b.min(32768), t);
+        let p = Rgba16::new(a, b, t, a);
+        let _ = premultiply16(p);
+        let _ = unpremultiply16(p);
+    }
+
+    #[test]
+    fn premultiply16_round_trip(a in 0..=32768u16, r in any::<u16>(), g in any::<u16>()) {
+        let c = |v: u16| if a == 0 { 0 } else { v % (a + 1) };
+        let p = Rgba16::new(c(r), c(g), c(r / 7), a);
+        prop_assert_eq!(premultiply16(unpremultiply16(p)), p);
+        prop_assert_eq!(p.unpremultiply(), unpremultiply16(p));
+    }
+
+    #[test]
+    fn createalpha_16bit_does_not_overflow(c in any::<[u16; 4]>()) {
+        let p = Rgba16::new(c[0] % 32769, c[1] % 32769, c[2] % 32769, c[3] % 32769);
+        let out = CreateAlpha::default().process(p);
+        // アルファは (背景の黒に合成した) RGB の最大値
+        let q = premultiply16(p);
+        prop_assert_eq!(out.alpha, q.red.max(q.green).max(q.blue));
+    }
+
+    #[test]
+    fn createalpha_modes_16bit_do_not_overflow(c in any::<[u16; 4]>(), back in any::<[u8; 3]>()) {
+        // どのモードも 16bit の全部の値で桁あふれせず、MAX を超えない
+        let p = Rgba16::new(c[0] % 32769, c[1] % 32769, c[2] % 32769, c[3] % 32769);
+        for mode in AlphaMode::ALL {
+            let out = CreateAlpha::new(mode, Rgba8::new(back[0], back[1], back[2], 255)).process(p);
+            prop_assert!(out.red.max(out.green).max(out.blue).max(out.alpha) <= 32768, "{mode:?} {out:?}");
+        }
+    }
+}
@@ -0,0 +1,395 @@
+// tests/golden の参照画像と fs-rs のカーネルの出力を、8/16/32bit のそれぞれで比較するテストです。
+// 参照画像の置き場所と作り方は tests/golden/README.md を参照してください。
+// 今ある参照画像はすべて 8bit で計算式から作ったもの (source8 = formula) なので、C++ 版と同じ結果に
+// なることの確認にはなっていません。After Effects で描画した参照画像 (source16 = ae など) がそろうまで、
+// rendered_in_after_effects は失敗します。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use libs::kernels::colorchange::ColorChange;
+use libs::kernels::colorkey::ColorKey;
+use libs::kernels::createalpha::CreateAlpha;
+use libs::kernels::pixelselector::PixelSelector;
+use libs::kernels::PixelKernel;
+use libs::pixel::MAX_CHANNEL16;
+use libs::slider;
+use libs::{ColorMetric, Image, Pixel, Rgba16, Rgba8, RgbaF32};
+
+struct Case {
+    effect: String,
+    name: String,
+    dir: PathBuf,
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.effect, self.name)
+    }
+}
+
+// 参照画像のビット深度です。入力はどれも 8bit の input.png で、After Effects のプロジェクトと同じく
+// このビット深度に変換してから処理します。
+#[derive(Clone, Copy, Debug)]
+enum Depth {
+    Eight,
+    Sixteen,
+    Float,
+}
+
+impl Depth {
+    const ALL: [Depth; 3] = [Depth::Eight, Depth::Sixteen, Depth::Float];
+
+    fn bpc(self) -> u32 {
+        match self {
+            Depth::Eight => 8,
+            Depth::Sixteen => 16,
+            Depth::Float => 32,
+        }
+    }
+
+    fn file(self) -> &'static str {
+        match self {
+            Depth::Eight => "expected.png",
+            Depth::Sixteen => "expected16.png",
+            Depth::Float => "expected32.tif",
+        }
+    }
+
+    // 誤差を数える単位 (このビット深度の 1 段)。32bit は正規化した値のまま比べる
+    fn step(self) -> f32 {
+        match self {
+            Depth::Eight => 255.0,
+            Depth::Sixteen => MAX_CHANNEL16 as f32,
+            Depth::Float => 1.0,
+        }
+    }
+}
+
+// params.txt の `key = value` を読み込んだものです。同じキーは複数書けます。
+struct Params(HashMap<String, Vec<String>>);
+
+impl Params {
+    fn load(path: &Path) -> Params {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        let text = fs::read_to_string(path).unwrap_or_default();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .unwrap_or_else(|| panic!("{}: bad line `{line}`", path.display()));
+            map.entry(key.trim().to_string())
+                .or_default()
+                .push(value.trim().to_string());
+        }
+        Params(map)
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.last()).map(String::as_str)
+    }
+
+    fn all(&self, key: &str) -> &[String] {
+        self.0.get(key).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    fn f64(&self, key: &str) -> f64 {
+        self.get(key).map_or(0.0, |v| v.parse().unwrap())
+    }
+
+    fn bool(&self, key: &str) -> bool {
+        self.get(key).is_some_and(|v| v.parse().unwrap())
+    }
+
+    fn metric(&self) -> ColorMetric {
+        self.get("metric")
+            .map_or(ColorMetric::RgbBox, |v| v.parse().unwrap())
+    }
+
+    // popup の値 (mode など)。書いていなければ既定値
+    fn parse<T: FromStr + Default>(&self, key: &str) -> T
+    where
+        T::Err: fmt::Debug,
+    {
+        self.get(key).map_or(T::default(), |v| v.parse().unwrap())
+    }
+
+    fn color(&self, key: &str) -> Rgba8 {
+        Rgba8::from_hex(self.get(key).unwrap()).unwrap()
+    }
+
+    // 参照画像の出どころ (ae: After Effects で描画、formula: 計算式から作成)
+    fn source(&self, depth: Depth) -> &str {
+        self.get(&format!("source{}", depth.bpc()))
+            .unwrap_or("formula")
+    }
+
+    // C++ との丸め差を許す場合は params.txt に `tolerance16 = 2` のように、そのビット深度の段数で書く
+    fn tolerance(&self, depth: Depth) -> f32 {
+        self.f64(&format!("tolerance{}", depth.bpc())) as f32
+    }
+}
+
+#[derive(Default)]
+struct Report {
+    /// A, R, G, B ごとの最大誤差 (ビット深度の段数)
+    max_error: [f32; 4],
+    mismatched: usize,
+    total: usize,
+}
+
+impl Report {
+    fn max(&self) -> f32 {
+        self.max_error.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, r, g, b] = self.max_error;
+        write!(
+            f,
+            "max error a={a} r={r} g={g} b={b}, mismatched {}/{} pixels",
+            self.mismatched, self.total
+        )
+    }
+}
+
+// 正規化した値どうしを比べ、差を `depth` の段数で数える
+fn compare(actual: &Image<RgbaF32>, expected: &Image<RgbaF32>, depth: Depth) -> Report {
+    assert_eq!(
+        (actual.width(), actual.height()),
+        (expected.width(), expected.height()),
+        "image size differs"
+    );
+    let step = depth.step();
+    let diff = |a: f32, e: f32| {
+        let d = (a - e).abs() * step;
+        // 8/16bit は整数の段数にする (正規化したときの誤差を含めない)
+        if step > 1.0 {
+            d.round()
+        } else {
+            d
+        }
+    };
+    let mut report = Report::default();
+    for (a, e) in actual.pixels().zip(expected.pixels()) {
+        let diff = [
+            diff(a.alpha, e.alpha),
+            diff(a.red, e.red),
+            diff(a.green, e.green),
+            diff(a.blue, e.blue),
+        ];
+        for (m, d) in report.max_error.iter_mut().zip(diff) {
+            *m = m.max(d);
+        }
+        if diff.iter().any(|&d| d != 0.0) {
+            report.mismatched += 1;
+        }
+        report.total += 1;
+    }
+    report
+}
+
+fn open(path: &Path) -> image::DynamicImage {
+    image::open(path).unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+}
+
+fn load_png(path: &Path) -> Image<Rgba8> {
+    let img = open(path).to_rgba8();
+    let data = img
+        .pixels()
+        .map(|p| Rgba8::new(p[0], p[1], p[2], p[3]))
+        .collect();
+    from_vec(img.width(), img.height(), data)
+}
+
+// `depth` の参照画像を正規化した値で読み込む
+fn load_reference(path: &Path, depth: Depth) -> Image<RgbaF32> {
+    let img = open(path);
+    let data = match depth {
+        Depth::Eight => img
+            .to_rgba8()
+            .pixels()
+            .map(|p| Rgba8::new(p[0], p[1], p[2], p[3]).to_normalized())
+            .collect(),
+        Depth::Sixteen => {
+            // PNG の 16bit (0 - 65535) を After Effects の 16bpc (0 - 32768) にする
+            let c = |v: u16| ((v as u32 * MAX_CHANNEL16 + 32767) / 65535) as u16;
+            img.to_rgba16()
+                .pixels()
+                .map(|p| Rgba16::new(c(p[0]), c(p[1]), c(p[2]), c(p[3])).to_normalized())
+                .collect()
+        }
+        Depth::Float => img
+            .to_rgba32f()
+            .pixels()
+            .map(|p| RgbaF32::new(p[0], p[1], p[2], p[3]))
+            .collect(),
+    };
+    from_vec(img.width(), img.height(), data)
+}
+
+fn from_vec<P>(width: u32, height: u32, data: Vec<P>) -> Image<P> {
+    Image::from_vec(width as usize, height as usize, width as usize, data).unwrap()
+}
+
+// 8bit の入力を `P` に変換して処理し、正規化した値で返す
+fn render<P: Pixel>(effect: &str, params: &Params, input: &Image<Rgba8>) -> Image<RgbaF32> {
+    let (width, height) = (input.width() as u32, input.height() as u32);
+    let input = from_vec(
+        width,
+        height,
+        input.pixels().map(|&p| P::from_rgba8(p)).collect(),
+    );
+    let output = run(effect, params, &input);
+    from_vec(
+        width,
+        height,
+        output.pixels().map(Pixel::to_normalized).collect(),
+    )
+}
+
+// 値の単位は fs-cli のフラグと同じで、許容誤差は Pixel Selector だけ 8bit の差 (slider::level8)、
+// ほかはパーセント (slider::percent)
+fn run<P: Pixel>(effect: &str, params: &Params, input: &Image<P>) -> Image<P> {
+    let percent = |key| slider::percent(params.f64(key));
+    match effect {
+        "colorchange" => ColorChange::new(
+            percent("level"),
+            params.metric(),
+            params
+                .all("pair")
+                .iter()
+                .map(|p| {
+                    let (src, dst) = p.split_once(':').unwrap();
+                    (Rgba8::from_hex(src).unwrap(), Rgba8::from_hex(dst).unwrap())
+                })
+                .collect(),
+        )
+        .with_softness(percent("softness"))
+        .with_mode(params.parse("mode"))
+        .process_image(input),
+        "colorkey" => ColorKey::new(
+            percent("threshold"),
+            params.metric(),
+            params.color("key"),
+            params.color("back"),
+        )
+        .with_softness(percent("softness"))
+        .process_image(input),
+        "createalpha" => CreateAlpha::new(
+            params.parse("mode"),
+            params
+                .get("background")
+                .map_or(Rgba8::new(255, 255, 255, 255), |v| {
+                    Rgba8::from_hex(v).unwrap()
+                }),
+        )
+        .process_image(input),
+        "pixelselector" => PixelSelector::new(
+            params.bool("invert"),
+            Some(params.color("target")),
+            slider::level8(params.f64("threshold")),
+            params.metric(),
+        )
+        .with_softness(slider::level8(params.f64("softness")))
+        .process_image(input),
+        _ => panic!("unknown effect `{effect}`"),
+    }
+}
+
+// input.png と params.txt があるディレクトリ。参照画像がまだ無いケースも含む
+fn collect_cases() -> Vec<Case> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut cases = Vec::new();
+    for effect in fs::read_dir(&root).unwrap().flatten() {
+        if !effect.path().is_dir() {
+            continue;
+        }
+        for case in fs::read_dir(effect.path()).unwrap().flatten() {
+            let dir = case.path();
+            if !dir.join("input.png").exists() || !dir.join("params.txt").exists() {
+                continue;
+            }
+            cases.push(Case {
+                effect: effect.file_name().to_string_lossy().into_owned(),
+                name: case.file_name().to_string_lossy().into_owned(),
+                dir,
+            });
+        }
+    }
+    cases.sort_by(|a, b| (&a.effect, &a.name).cmp(&(&b.effect, &b.name)));
+    cases
+}
+
+#[test]
+fn golden_images() {
+    let cases = collect_cases();
+    assert!(!cases.is_empty(), "no golden cases found");
+
+    let mut failures = Vec::new();
+    let (mut references, mut rendered) = (0, 0);
+    for case in &cases {
+        let params = Params::load(&case.dir.join("params.txt"));
+        let input = load_png(&case.dir.join("input.png"));
+        for depth in Depth::ALL {
+            let path = case.dir.join(depth.file());
+            if !path.exists() {
+                println!("{case} {}bpc: no reference", depth.bpc());
+                continue;
+            }
+            let expected = load_reference(&path, depth);
+            let actual = match depth {
+                Depth::Eight => render::<Rgba8>(&case.effect, &params, &input),
+                Depth::Sixteen => render::<Rgba16>(&case.effect, &params, &input),
+                Depth::Float => render::<RgbaF32>(&case.effect, &params, &input),
+            };
+            let report = compare(&actual, &expected, depth);
+            let source = params.source(depth);
+            references += 1;
+            rendered += usize::from(source == "ae");
+            println!("{case} {}bpc [{source}]: {report}", depth.bpc());
+            if report.max() > params.tolerance(depth) {
+                failures.push(format!("{case} {}bpc: {report}", depth.bpc()));
+            }
+        }
+    }
+    // C++ 版との一致を確かめたのは ae の参照画像だけ
+    println!(
+        "{rendered}/{references} references rendered in After Effects ({} needed)",
+        cases.len() * Depth::ALL.len()
+    );
+    assert!(
+        failures.is_empty(),
+        "golden mismatch:\n{}",
+        failures.join("\n")
+    );
+}
+
+// C++ 版と同じ結果になることの確認です。すべてのケースとビット深度に After Effects で描画した
+// 参照画像がそろうまで失敗します: cargo test -p libs --test golden -- --ignored
+#[test]
+#[ignore = "After Effects で描画した参照画像がまだそろっていない"]
+fn rendered_in_after_effects() {
+    let mut missing = Vec::new();
+    for case in collect_cases() {
+        let params = Params::load(&case.dir.join("params.txt"));
+        for depth in Depth::ALL {
+            if !case.dir.join(depth.file()).exists() || params.source(depth) != "ae" {
+                missing.push(format!("{case} {}bpc", depth.bpc()));
+            }
+        }
+    }
+    assert!(
+        missing.is_empty(),
+        "not rendered in After Effects:\n{}",
+        missing.join("\n")
+    );
+}
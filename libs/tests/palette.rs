@@ -0,0 +1,152 @@
+// colorchange のパレット (シーケンスデータ) の保存形式と編集操作のテストです。
+
+mod common;
+
+use common::rgb;
+use libs::kernels::colorchange::PairOverride;
+use libs::list::Edited::{Emptied, Selected, Unchanged};
+use libs::palette::{Palette, PaletteEdit, PaletteEntry};
+use libs::sequence::{flatten, unflatten, SequenceError};
+use libs::ColorMetric;
+
+fn entry(v: u8) -> PaletteEntry {
+    PaletteEntry::new(rgb(v, 0, 0), rgb(0, v, 0))
+}
+
+fn palette(values: &[u8]) -> Palette {
+    Palette {
+        entries: values.iter().map(|&v| entry(v)).collect(),
+    }
+}
+
+fn values(p: &Palette) -> Vec<u8> {
+    p.entries.iter().map(|e| e.src.red).collect()
+}
+
+#[test]
+fn version1_format() {
+    let mut p = palette(&[255]);
+    p.entries.push(PaletteEntry {
+        enabled: false,
+        ..PaletteEntry::new(rgb(0, 0x80, 0xff), rgb(1, 2, 3))
+    });
+    let (version, bytes) = flatten(&p).unwrap();
+    assert_eq!(version, 1);
+    assert_eq!(
+        std::str::from_utf8(&bytes).unwrap(),
+        r#"{"version":1,"data":{"entries":[{"src":"ff0000","dst":"00ff00","enabled":true},{"src":"0080ff","dst":"010203","enabled":false}]}}"#
+    );
+    assert_eq!(unflatten::<Palette>(&bytes).unwrap(), p);
+}
+
+#[test]
+fn pair_overrides() {
+    let mut p = palette(&[255]);
+    p.entries[0].overrides = PairOverride {
+        tolerance: Some(0.25),
+        softness: None,
+        metric: Some(ColorMetric::Oklab),
+        ..PairOverride::default()
+    };
+    let (_, bytes) = flatten(&p).unwrap();
+    assert_eq!(
+        std::str::from_utf8(&bytes).unwrap(),
+        r#"{"version":1,"data":{"entries":[{"src":"ff0000","dst":"00ff00","enabled":true,"tolerance":0.25,"metric":"oklab"}]}}"#
+    );
+    assert_eq!(unflatten::<Palette>(&bytes).unwrap(), p);
+
+    // 上書きの無い古いデータは全体の設定を使う
+    let old = br#"{"version":1,"data":{"entries":[{"src":"ff0000","dst":"00ff00"}]}}"#;
+    let p = unflatten::<Palette>(old).unwrap();
+    assert!(p.entries[0].overrides.is_empty());
+    assert_eq!(p.enabled_overrides().count(), 1);
+}
+
+#[test]
+fn reads_hand_written_data() {
+    // enabled の省略と # 付きの色
+    let bytes = br##"{"version":1,"data":{"entries":[{"src":"#FF0000","dst":"000080"}]}}"##;
+    let p = unflatten::<Palette>(bytes).unwrap();
+    assert_eq!(
+        p.entries,
+        vec![PaletteEntry::new(rgb(255, 0, 0), rgb(0, 0, 128))]
+    );
+
+    let bytes = br#"{"version":1,"data":{"entries":[{"src":"red","dst":"000080"}]}}"#;
+    assert!(matches!(
+        unflatten::<Palette>(bytes),
+        Err(SequenceError::Json(_))
+    ));
+}
+
+#[test]
+fn large_palette_round_trip() {
+    let p = Palette {
+        entries: (0..=255u8)
+            .map(|v| PaletteEntry {
+                enabled: v % 3 != 0,
+                ..PaletteEntry::new(rgb(v, v / 2, 255 - v), rgb(255 - v, v, v / 3))
+            })
+            .collect(),
+    };
+    let (_, bytes) = flatten(&p).unwrap();
+    assert_eq!(unflatten::<Palette>(&bytes).unwrap(), p);
+}
+
+#[test]
+fn edits() {
+    let mut p = Palette::default();
+    assert_eq!(p.apply(PaletteEdit::Add(entry(1))), Selected(0));
+    assert_eq!(p.apply(PaletteEdit::Add(entry(2))), Selected(1));
+    assert_eq!(p.apply(PaletteEdit::Add(entry(3))), Selected(2));
+    assert_eq!(values(&p), [1, 2, 3]);
+
+    assert_eq!(p.apply(PaletteEdit::Replace(1, entry(9))), Selected(1));
+    assert_eq!(values(&p), [1, 9, 3]);
+
+    assert_eq!(p.apply(PaletteEdit::MoveUp(2)), Selected(1));
+    assert_eq!(values(&p), [1, 3, 9]);
+    assert_eq!(p.apply(PaletteEdit::MoveDown(0)), Selected(1));
+    assert_eq!(values(&p), [3, 1, 9]);
+
+    assert_eq!(p.apply(PaletteEdit::Remove(2)), Selected(1));
+    assert_eq!(values(&p), [3, 1]);
+    assert_eq!(p.apply(PaletteEdit::Remove(0)), Selected(0));
+    assert_eq!(values(&p), [1]);
+}
+
+// 最後の1つを消したら、選択できる項目は無い
+#[test]
+fn removing_the_last_entry_empties_the_selection() {
+    let mut p = palette(&[1]);
+    let edited = p.apply(PaletteEdit::Remove(0));
+    assert_eq!(edited, Emptied);
+    assert!(edited.changed());
+    assert!(p.is_empty());
+}
+
+#[test]
+fn out_of_range_edits_do_nothing() {
+    let mut p = palette(&[1, 2]);
+    assert_eq!(p.apply(PaletteEdit::Replace(2, entry(9))), Unchanged);
+    assert_eq!(p.apply(PaletteEdit::Remove(5)), Unchanged);
+    assert_eq!(p.apply(PaletteEdit::MoveUp(0)), Unchanged);
+    assert_eq!(p.apply(PaletteEdit::MoveDown(1)), Unchanged);
+    assert!(!p.apply(PaletteEdit::Remove(2)).changed());
+    assert_eq!(values(&p), [1, 2]);
+
+    let mut empty = Palette::default();
+    assert_eq!(empty.apply(PaletteEdit::Remove(0)), Unchanged);
+    assert_eq!(empty.apply(PaletteEdit::MoveDown(0)), Unchanged);
+}
+
+#[test]
+fn enabled_pairs_keep_order() {
+    let mut p = palette(&[1, 2, 3]);
+    p.entries[1].enabled = false;
+    let pairs: Vec<_> = p.enabled_pairs().collect();
+    assert_eq!(
+        pairs,
+        vec![(rgb(1, 0, 0), rgb(0, 1, 0)), (rgb(3, 0, 0), rgb(0, 3, 0))]
+    );
+}
@@ -0,0 +1,301 @@
+// パレットファイル (ASE/ACO/GPL/CSV/JSON) の読み書きのテストです。
+// 往復のテストと、壊れたファイルでパニックしないことを proptest で確かめます。
+
+mod common;
+
+use common::rgb;
+use libs::kernels::colorchange::PairOverride;
+use libs::palette::{Palette, PaletteEntry, PaletteError, PaletteFormat};
+use libs::{ColorMetric, Rgba8};
+use proptest::prelude::*;
+
+fn arb_color() -> impl Strategy<Value = Rgba8> {
+    any::<[u8; 3]>().prop_map(|[r, g, b]| rgb(r, g, b))
+}
+
+fn arb_override() -> impl Strategy<Value = PairOverride> {
+    (
+        prop::option::of(0.0f32..1.0),
+        prop::option::of(0.0f32..1.0),
+        prop::option::of(prop::sample::select(ColorMetric::ALL.to_vec())),
+        prop::option::of(any::<u8>()),
+        prop::option::of(any::<u8>()),
+    )
+        .prop_map(
+            |(tolerance, softness, metric, src_alpha, dst_alpha)| PairOverride {
+                tolerance,
+                softness,
+                metric,
+                src_alpha,
+                dst_alpha,
+            },
+        )
+}
+
+fn arb_palette() -> impl Strategy<Value = Palette> {
+    prop::collection::vec(
+        (arb_color(), arb_color(), any::<bool>(), arb_override()).prop_map(
+            |(src, dst, enabled, overrides)| PaletteEntry {
+                src,
+                dst,
+                enabled,
+                overrides,
+            },
+        ),
+        0..40,
+    )
+    .prop_map(|entries| Palette { entries })
+}
+
+// 形式ごとに保存できる内容だけを残す
+// (上書きは JSON だけ、enabled は CSV と JSON だけ)
+fn expected(p: &Palette, format: PaletteFormat) -> Palette {
+    let entries = p.entries.iter().map(|e| match format {
+        PaletteFormat::Json => *e,
+        PaletteFormat::Csv => PaletteEntry {
+            enabled: e.enabled,
+            ..PaletteEntry::new(e.src, e.dst)
+        },
+        _ => PaletteEntry::new(e.src, e.dst),
+    });
+    Palette {
+        entries: entries.collect(),
+    }
+}
+
+proptest! {
+    #[test]
+    fn round_trip(p in arb_palette()) {
+        for format in PaletteFormat::ALL {
+            let read = Palette::read(format, &p.write(format)).unwrap();
+            prop_assert_eq!(read, expected(&p, format), "{}", format);
+        }
+    }
+
+    #[test]
+    fn arbitrary_bytes_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+        for format in PaletteFormat::ALL {
+            let _ = Palette::read(format, &bytes);
+        }
+    }
+
+    #[test]
+    fn corrupted_files_do_not_panic(
+        p in arb_palette(),
+        flips in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+        cut in any::<prop::sample::Index>(),
+    ) {
+        for format in PaletteFormat::ALL {
+            let mut bytes = p.write(format);
+            for (i, v) in &flips {
+                let i = i.index(bytes.len());
+                bytes[i] ^= v;
+            }
+            let _ = Palette::read(format, &bytes);
+            let len = cut.index(bytes.len() + 1);
+            let _ = Palette::read(format, &bytes[..len]);
+        }
+    }
+
+    #[test]
+    fn arbitrary_text_does_not_panic(text in "\\PC*") {
+        for format in [PaletteFormat::Gpl, PaletteFormat::Csv, PaletteFormat::Json] {
+            let _ = Palette::read(format, text.as_bytes());
+        }
+    }
+}
+
+fn utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain([0])
+        .flat_map(|c| c.to_be_bytes())
+        .collect()
+}
+
+fn ase_color(name: &str, model: &[u8; 4], values: &[f32]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend((name.encode_utf16().count() as u16 + 1).to_be_bytes());
+    body.extend(utf16(name));
+    body.extend(model);
+    for v in values {
+        body.extend(v.to_be_bytes());
+    }
+    body.extend(2u16.to_be_bytes());
+    let mut block = vec![0x00, 0x01];
+    block.extend((body.len() as u32).to_be_bytes());
+    block.extend(body);
+    block
+}
+
+#[test]
+fn ase_color_models_and_groups() {
+    let mut group = vec![0xC0, 0x01];
+    let name = utf16("Cel");
+    group.extend((name.len() as u32 + 2).to_be_bytes());
+    group.extend((name.len() as u16 / 2).to_be_bytes());
+    group.extend(name);
+
+    let mut bytes = b"ASEF".to_vec();
+    bytes.extend([0, 1, 0, 0]);
+    bytes.extend(6u32.to_be_bytes());
+    bytes.extend(group);
+    bytes.extend(ase_color("red", b"RGB ", &[1.0, 0.0, 0.0]));
+    bytes.extend(ase_color("gray", b"Gray", &[0.5]));
+    bytes.extend(ase_color("cyan", b"CMYK", &[1.0, 0.0, 0.0, 0.0]));
+    bytes.extend(ase_color("white", b"LAB ", &[1.0, 0.0, 0.0]));
+    bytes.extend([0xC0, 0x02, 0, 0, 0, 0]);
+
+    let p = Palette::read(PaletteFormat::Ase, &bytes).unwrap();
+    assert_eq!(
+        p.entries,
+        vec![
+            PaletteEntry::new(rgb(255, 0, 0), rgb(128, 128, 128)),
+            PaletteEntry::new(rgb(0, 255, 255), rgb(255, 255, 255)),
+        ]
+    );
+}
+
+#[test]
+fn aco_color_spaces() {
+    let mut bytes = vec![0, 1, 0, 4];
+    // RGB, HSB (120°, 100%, 100%), CMYK (0 がインク 100%), RGB
+    for (space, v) in [
+        (0u16, [0xFFFF, 0x8080, 0, 0]),
+        (1, [21845, 0xFFFF, 0xFFFF, 0]),
+        (2, [0xFFFF, 0, 0xFFFF, 0xFFFF]),
+        (0, [0, 0, 0, 0]),
+    ] {
+        bytes.extend(space.to_be_bytes());
+        for x in v {
+            bytes.extend(u16::to_be_bytes(x));
+        }
+    }
+    let p = Palette::read(PaletteFormat::Aco, &bytes).unwrap();
+    assert_eq!(
+        p.entries,
+        vec![
+            PaletteEntry::new(rgb(255, 128, 0), rgb(0, 255, 0)),
+            PaletteEntry::new(rgb(255, 0, 255), rgb(0, 0, 0)),
+        ]
+    );
+
+    // Lab (L は 100 倍、a と b は 100 倍の符号付き) とグレースケール (10000 が黒)
+    let mut bytes = vec![0, 1, 0, 6];
+    for (space, v) in [
+        (7u16, [10000, 0, 0, 0]),
+        (7, [0, 0, 0, 0]),
+        (7, [5323, 8011, 6722, 0]),
+        (8, [0, 0, 0, 0]),
+        (8, [10000, 0, 0, 0]),
+        (8, [5000, 0, 0, 0]),
+    ] {
+        bytes.extend(space.to_be_bytes());
+        for x in v {
+            bytes.extend(u16::to_be_bytes(x as u16));
+        }
+    }
+    let p = Palette::read(PaletteFormat::Aco, &bytes).unwrap();
+    assert_eq!(
+        p.entries,
+        vec![
+            // sRGB の赤は L 53.23, a 80.11, b 67.22
+            PaletteEntry::new(rgb(255, 255, 255), rgb(0, 0, 0)),
+            PaletteEntry::new(rgb(255, 0, 0), rgb(255, 255, 255)),
+            PaletteEntry::new(rgb(0, 0, 0), rgb(128, 128, 128)),
+        ]
+    );
+    // 負の a, b: sRGB の緑は L 87.74, a -86.18, b 83.18
+    let mut green = vec![0, 1, 0, 2];
+    for _ in 0..2 {
+        green.extend(7u16.to_be_bytes());
+        for x in [8774i16, -8618, 8318, 0] {
+            green.extend(x.to_be_bytes());
+        }
+    }
+    let p = Palette::read(PaletteFormat::Aco, &green).unwrap();
+    let g = p.entries[0].src;
+    assert!(g.red <= 1 && g.green >= 254 && g.blue <= 1, "{g:?}");
+
+    let mut unknown = vec![0, 1, 0, 1, 0, 9];
+    unknown.extend([0; 8]);
+    assert!(matches!(
+        Palette::read(PaletteFormat::Aco, &unknown),
+        Err(PaletteError::Invalid { .. })
+    ));
+}
+
+#[test]
+fn gimp_palette() {
+    let text = "GIMP Palette\nName: Cel\nColumns: 4\n#\n255   0   0\tRed\n  0 0 128 Navy blue\n\n# comment\n10 20 30\n40 50 60 Untitled\n";
+    let p = Palette::read(PaletteFormat::Gpl, text.as_bytes()).unwrap();
+    assert_eq!(
+        p.entries,
+        vec![
+            PaletteEntry::new(rgb(255, 0, 0), rgb(0, 0, 128)),
+            PaletteEntry::new(rgb(10, 20, 30), rgb(40, 50, 60)),
+        ]
+    );
+
+    let odd = "GIMP Palette\n1 2 3\n";
+    assert!(matches!(
+        Palette::read(PaletteFormat::Gpl, odd.as_bytes()),
+        Err(PaletteError::OddColorCount(1))
+    ));
+    let bad = "GIMP Palette\n1 2 300 Too bright\n4 5 6\n";
+    assert!(matches!(
+        Palette::read(PaletteFormat::Gpl, bad.as_bytes()),
+        Err(PaletteError::Invalid { .. })
+    ));
+}
+
+#[test]
+fn csv_mapping() {
+    let text = "\u{feff}Source,Destination,Enabled\r\nff0000,800000\r\n\"#00FF00\", 008000 ,false\r\n\r\n0000ff,000080,1\r\n";
+    let p = Palette::read(PaletteFormat::Csv, text.as_bytes()).unwrap();
+    assert_eq!(
+        p.entries,
+        vec![
+            PaletteEntry::new(rgb(255, 0, 0), rgb(128, 0, 0)),
+            PaletteEntry {
+                enabled: false,
+                ..PaletteEntry::new(rgb(0, 255, 0), rgb(0, 128, 0))
+            },
+            PaletteEntry::new(rgb(0, 0, 255), rgb(0, 0, 128)),
+        ]
+    );
+
+    let err = Palette::read(PaletteFormat::Csv, b"ff0000,800000\nff0000,red\n").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid csv palette: line 2: expected RRGGBB, got `red`"
+    );
+}
+
+#[test]
+fn json_is_sequence_format() {
+    let p = Palette {
+        entries: vec![PaletteEntry::new(rgb(255, 0, 0), rgb(128, 0, 0))],
+    };
+    let bytes = p.write(PaletteFormat::Json);
+    let text = std::str::from_utf8(&bytes).unwrap();
+    assert!(text.contains("\"version\": 1"), "{text}");
+    assert_eq!(libs::sequence::unflatten::<Palette>(&bytes).unwrap(), p);
+}
+
+#[test]
+fn format_from_path() {
+    use std::path::Path;
+    assert_eq!(
+        PaletteFormat::from_path(Path::new("cel/A.ASE")),
+        Some(PaletteFormat::Ase)
+    );
+    assert_eq!(
+        PaletteFormat::from_path(Path::new("a.gpl")),
+        Some(PaletteFormat::Gpl)
+    );
+    assert_eq!(PaletteFormat::from_path(Path::new("a.txt")), None);
+    assert!(matches!(
+        Palette::load(Path::new("a.txt")),
+        Err(PaletteError::UnknownFormat(_))
+    ));
+}
@@ -0,0 +1,60 @@
+// 帯の分け方やスレッド数によって結果が変わらないことを確かめます。
+
+use libs::kernels::colorchange::ColorChange;
+use libs::{ColorMetric, Image, ImageView, ImageViewMut, Renderer, Rgba8};
+
+fn kernel() -> ColorChange {
+    ColorChange::new(
+        0.1,
+        ColorMetric::RgbEuclidean,
+        vec![(Rgba8::new(255, 0, 0, 255), Rgba8::new(0, 0, 255, 255))],
+    )
+    .with_softness(0.2)
+}
+
+#[test]
+fn threads_and_bands_do_not_change_output() {
+    // stride が width より大きいバッファ (AE の rowbytes と同じ形)
+    let (width, height, stride) = (37, 53, 40);
+    let src: Vec<Rgba8> = (0..stride * height)
+        .map(|i| Rgba8::new((i * 7 % 256) as u8, (i * 3 % 256) as u8, 0, 255))
+        .collect();
+    let view = ImageView::new(width, height, stride, &src).unwrap();
+
+    let mut expected = vec![Rgba8::default(); stride * height];
+    Renderer::new().with_threads(1).render(
+        &kernel(),
+        &view,
+        ImageViewMut::new(width, height, stride, &mut expected).unwrap(),
+    );
+
+    for (threads, band_rows) in [(2, 1), (4, 7), (8, 32), (3, 100)] {
+        let mut actual = vec![Rgba8::default(); stride * height];
+        Renderer::new()
+            .with_threads(threads)
+            .with_band_rows(band_rows)
+            .render(
+                &kernel(),
+                &view,
+                ImageViewMut::new(width, height, stride, &mut actual).unwrap(),
+            );
+        assert_eq!(actual, expected, "threads={threads} band_rows={band_rows}");
+    }
+}
+
+#[test]
+fn renders_only_the_overlapping_area() {
+    let src = Image::filled(4, 4, Rgba8::new(255, 0, 0, 255));
+    let mut dst = Image::filled(6, 2, Rgba8::default());
+    Renderer::new().render(&kernel(), &src.view(), dst.view_mut());
+    for y in 0..2 {
+        for x in 0..6 {
+            let expected = if x < 4 {
+                Rgba8::new(0, 0, 255, 255)
+            } else {
+                Rgba8::default()
+            };
+            assert_eq!(dst.get(x, y), Some(&expected), "({x}, {y})");
+        }
+    }
+}
@@ -0,0 +1,122 @@
+// シーケンスデータの flatten/unflatten の往復と、バージョンの扱いのテストです。
+
+use libs::sequence::{flatten, unflatten, unflatten_or_default, SequenceError, SequenceState};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+struct State {
+    palette: Vec<[u8; 3]>,
+    note: String,
+    cached: Option<f32>,
+}
+
+impl SequenceState for State {
+    const VERSION: u16 = 2;
+
+    fn migrate(version: u16, data: serde_json::Value) -> Result<Self, SequenceError> {
+        // バージョン 1 は色の配列だけを保存していた
+        match version {
+            1 => Ok(State {
+                palette: serde_json::from_value(data)?,
+                ..State::default()
+            }),
+            _ => Err(SequenceError::Unsupported(version)),
+        }
+    }
+}
+
+fn sample() -> State {
+    State {
+        palette: vec![[255, 0, 0], [0, 128, 255], [0, 0, 0]],
+        note: "セル 01".to_string(),
+        cached: Some(0.25),
+    }
+}
+
+#[test]
+fn round_trip() {
+    let state = sample();
+    let (version, bytes) = flatten(&state).unwrap();
+    assert_eq!(version, State::VERSION);
+    assert_eq!(unflatten::<State>(&bytes).unwrap(), state);
+
+    let (_, bytes) = flatten(&State::default()).unwrap();
+    assert_eq!(unflatten::<State>(&bytes).unwrap(), State::default());
+}
+
+#[test]
+fn unit_state_round_trip() {
+    let (version, bytes) = flatten(&()).unwrap();
+    assert_eq!(version, 0);
+    unflatten::<()>(&bytes).unwrap();
+}
+
+#[test]
+fn empty_bytes_are_default() {
+    assert_eq!(unflatten::<State>(&[]).unwrap(), State::default());
+}
+
+#[test]
+fn migrates_older_version() {
+    let bytes = br#"{"version":1,"data":[[1,2,3],[4,5,6]]}"#;
+    let state = unflatten::<State>(bytes).unwrap();
+    assert_eq!(state.palette, vec![[1, 2, 3], [4, 5, 6]]);
+    assert_eq!(state.note, "");
+
+    let bytes = br#"{"version":0,"data":null}"#;
+    assert!(matches!(
+        unflatten::<State>(bytes),
+        Err(SequenceError::Unsupported(0))
+    ));
+}
+
+#[test]
+fn rejects_newer_version() {
+    let bytes = br#"{"version":3,"data":{}}"#;
+    assert!(matches!(
+        unflatten::<State>(bytes),
+        Err(SequenceError::Newer {
+            found: 3,
+            current: 2
+        })
+    ));
+    // ホストでは既定値で開く
+    assert_eq!(unflatten_or_default::<State>(3, bytes), State::default());
+}
+
+#[test]
+fn rejects_broken_data() {
+    let (_, bytes) = flatten(&sample()).unwrap();
+    assert!(matches!(
+        unflatten::<State>(&bytes[..bytes.len() / 2]),
+        Err(SequenceError::Json(_))
+    ));
+    assert!(matches!(
+        unflatten::<State>(br#"{"version":2,"data":{"palette":"red"}}"#),
+        Err(SequenceError::Json(_))
+    ));
+}
+
+#[test]
+fn host_falls_back_to_default() {
+    let (version, bytes) = flatten(&sample()).unwrap();
+    assert_eq!(unflatten_or_default::<State>(version, &bytes), sample());
+    // AE のバージョンと違っても、中に書かれたバージョンで読む
+    assert_eq!(unflatten_or_default::<State>(1, &bytes), sample());
+    let old = br#"{"version":1,"data":[[1,2,3]]}"#;
+    assert_eq!(
+        unflatten_or_default::<State>(1, old).palette,
+        vec![[1, 2, 3]]
+    );
+    // 壊れたデータ、読み替えられない古いデータ、空のデータは既定値
+    for bytes in [
+        &bytes[..bytes.len() / 2],
+        br#"{"version":0,"data":null}"#,
+        b"",
+    ] {
+        assert_eq!(
+            unflatten_or_default::<State>(version, bytes),
+            State::default()
+        );
+    }
+}
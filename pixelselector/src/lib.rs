@@ -1,6 +1,12 @@
+// After Effects SDK が無い環境(Linux)ではプラグイン本体はビルドしない
+#![cfg(any(windows, target_os = "macos"))]
+
 use after_effects::{self as ae};
 
-use libs::utils::{comp_pix8_lv, conv_16_to_8, conv_32_to_8};
+use libs::host::FsEffect;
+use libs::kernels::pixelselector::PixelSelector;
+use libs::slider::{self, percent_to_alpha8};
+use libs::ColorMetric;
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
 enum Params {
@@ -12,24 +18,27 @@ enum Params {
     Target1Color,
     Target1End,
     Threshold,
+    Metric,
+    Softness,
+    Unpremultiply,
+    // Target 1 のグループの後に追加したので、グループの外に並ぶ
+    MatchAlpha,
+    AlphaToMatch,
 }
 
 #[derive(Default)]
 struct Plugin {}
 
-ae::define_effect!(Plugin, (), Params);
+libs::fs_effect!(Plugin, Params);
 
-impl AdobePluginGlobal for Plugin {
-    fn can_load(_host_name: &str, _host_version: &str) -> bool {
-        true
-    }
+impl FsEffect for Plugin {
+    type Params = Params;
+    type Kernel = PixelSelector;
+    type Sequence = ();
 
-    fn params_setup(
-        &self,
-        params: &mut ae::Parameters<Params>,
-        in_data: InData,
-        _: OutData,
-    ) -> Result<(), Error> {
+    const ABOUT: &'static str = "fs-rs pixelselector";
+
+    fn params_setup(&self, params: &mut ae::Parameters<Params>) -> Result<(), Error> {
         params.add_group(
             Params::OptionsStart,
             Params::OptionsEnd,
@@ -92,196 +101,87 @@ impl AdobePluginGlobal for Plugin {
             }),
         )?;
 
-        Ok(())
-    }
-
-    fn handle_command(
-        &mut self,
-        cmd: ae::Command,
-        in_data: InData,
-        mut out_data: OutData,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        match cmd {
-            ae::Command::About => {
-                self.about(&mut out_data);
-            }
-            ae::Command::GlobalSetup => {
-                self.global_setup(&in_data)?;
-            }
-            ae::Command::Render {
-                in_layer,
-                out_layer,
-            } => {
-                self.legacy_render(&in_data, in_layer, out_layer, params)?;
-            }
-            ae::Command::SmartPreRender { extra } => {
-                self.smart_pre_render(&in_data, extra, params)?;
-            }
-            ae::Command::SmartRender { extra } => {
-                self.smart_render(&in_data, extra, params)?;
-            }
-            _ => {}
-        }
-        Ok(())
-    }
-}
-
-impl Plugin {
-    fn about(&mut self, out_data: &mut OutData) {
-        out_data
-            .set_return_msg("fs-rs pixelselector");
-    }
-
-    fn global_setup(&mut self, in_data: &InData) -> Result<(), ae::Error> {
-        win_dbg_logger::DEBUGGER_LOGGER.set_force_log_without_debugger(true);
-        log::info!("GlobalSetup");
-        // For Premiere - declare supported pixel formats
-        if in_data.is_premiere() {
-            let suite = ae::pf::suites::PixelFormat::new()?;
-
-            // Add the pixel formats we support in order of preference.
-            suite.clear_supported_pixel_formats(in_data.effect_ref())?;
-            let formats = [
-                ae::pr::PixelFormat::Bgra4444_8u,
-                ae::pr::PixelFormat::Bgra4444_16u,
-                ae::pr::PixelFormat::Bgra4444_32f,
-            ];
-            for x in formats {
-                suite.add_supported_pixel_format(in_data.effect_ref(), x)?;
-            }
-        }
-        Ok(())
-    }
-
-    fn legacy_render(
-        &mut self,
-        in_data: &InData,
-        in_layer: ae::Layer,
-        out_layer: ae::Layer,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        if !in_data.is_premiere() {
-            // We don't support non-SmartFX unless it's Premiere
-            return Err(Error::BadCallbackParameter);
-        }
-
-        self.do_render(in_data, in_layer, out_layer, params)?;
-
-        Ok(())
-    }
-
-    fn smart_pre_render(
-        &mut self,
-        in_data: &InData,
-        mut extra: ae::PreRenderExtra,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        let req = extra.output_request();
+        // 色の距離の測り方
+        params.add(
+            Params::Metric,
+            "Color Metric",
+            ae::PopupDef::setup(|f| {
+                f.set_options(&ColorMetric::LABELS);
+                f.set_default(1);
+                f.set_value(f.default());
+            }),
+        )?;
 
-        if let Ok(in_result) = extra.callbacks().checkout_layer(
-            0,
-            0,
-            &req,
-            in_data.current_time(),
-            in_data.time_step(),
-            in_data.time_scale(),
-        ) {
-            let _ = extra.union_result_rect(in_result.result_rect.into());
-            let _ = extra.union_max_result_rect(in_result.max_result_rect.into());
-        }
-        Ok(())
-    }
+        // Threshold の外側で少しずつ選択を弱める幅 (Threshold と同じ単位)
+        params.add(
+            Params::Softness,
+            "Softness",
+            ae::FloatSliderDef::setup(|f| {
+                f.set_default(0.0);
+                f.set_precision(1);
+                f.set_valid_min(0.0);
+                f.set_valid_max(100.0);
+                f.set_slider_min(0.0);
+                f.set_slider_max(100.0);
+                f.set_value(f.default());
+            }),
+        )?;
 
-    fn smart_render(
-        &mut self,
-        in_data: &InData,
-        extra: ae::SmartRenderExtra,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), ae::Error> {
-        let cb = extra.callbacks();
-        let Some(input_world) = cb.checkout_layer_pixels(0)? else {
-            return Ok(());
-        };
+        // 半透明の縁も不透明な部分と同じ色として比べる
+        params.add(
+            Params::Unpremultiply,
+            "Unpremultiply",
+            ae::CheckBoxDef::setup(|f| {
+                f.set_default(false);
+                f.set_value(f.default());
+            }),
+        )?;
 
-        if let Ok(Some(output_world)) = cb.checkout_output() {
-            self.do_render(in_data, input_world, output_world, params)?;
-        }
+        // 色に加えてアルファも比べる
+        params.add(
+            Params::MatchAlpha,
+            "Match Alpha",
+            ae::CheckBoxDef::setup(|f| {
+                f.set_default(false);
+                f.set_value(f.default());
+            }),
+        )?;
+        params.add(
+            Params::AlphaToMatch,
+            "Alpha To Match",
+            ae::FloatSliderDef::setup(|f| {
+                f.set_default(100.0);
+                f.set_precision(1);
+                f.set_valid_min(0.0);
+                f.set_valid_max(100.0);
+                f.set_slider_min(0.0);
+                f.set_slider_max(100.0);
+                f.set_value(f.default());
+            }),
+        )?;
 
-        cb.checkin_layer_pixels(0)?;
         Ok(())
     }
 
-    fn do_render(
-        &self,
-        in_data: &ae::InData,
-        in_layer: ae::Layer,
-        mut out_layer: ae::Layer,
-        params: &mut ae::Parameters<Params>,
-    ) -> Result<(), Error> {
+    fn kernel(params: &ae::Parameters<Params>, _sequence: &()) -> Result<PixelSelector, Error> {
         let target1_enabled = params.get(Params::Target1Enabled)?.as_checkbox()?.value();
         let target1_color = params.get(Params::Target1Color)?.as_color()?.value();
         let invert = params.get(Params::OptionInvert)?.as_checkbox()?.value();
         let threshold = params.get(Params::Threshold)?.as_float_slider()?.value();
-
-        let progress_final = out_layer.height() as _;
-        ae::pf::suites::WorldTransform::new()?.copy_hq(in_data.effect_ref(), &in_layer, &mut out_layer, None, None)?;
-        in_layer.iterate_with(
-            &mut out_layer,
-            0,
-            progress_final,
-            None,
-            |_x: i32,
-             _y: i32,
-             pixel: ae::GenericPixel,
-             out_pixel: ae::GenericPixelMut|
-             -> Result<(), Error> {
-                match (pixel, out_pixel) {
-                    (ae::GenericPixel::Pixel8(pixel), ae::GenericPixelMut::Pixel8(out_pixel)) => {
-                        if !target1_enabled {
-                            return Ok(());
-                        }
-                        let b = comp_pix8_lv(&target1_color, pixel, threshold as u8);
-                        if b == invert {
-                            out_pixel.alpha = 0;
-                            out_pixel.red = MAX_CHANNEL8 as u8;
-                            out_pixel.green = MAX_CHANNEL8 as u8;
-                            out_pixel.blue = MAX_CHANNEL8 as u8;
-                        }
-                    }
-                    (ae::GenericPixel::Pixel16(pixel), ae::GenericPixelMut::Pixel16(out_pixel)) => {
-                        if !target1_enabled {
-                            return Ok(());
-                        }
-                        let b = comp_pix8_lv(&target1_color, &conv_16_to_8(pixel), threshold as u8);
-                        if b == invert {
-                            out_pixel.alpha = 0;
-                            out_pixel.red = MAX_CHANNEL16 as u16;
-                            out_pixel.green = MAX_CHANNEL16 as u16;
-                            out_pixel.blue = MAX_CHANNEL16 as u16;
-                        }
-                    }
-                    (
-                        ae::GenericPixel::PixelF32(pixel),
-                        ae::GenericPixelMut::PixelF32(out_pixel),
-                    ) => {
-                        if !target1_enabled {
-                            return Ok(());
-                        }
-                        let b = comp_pix8_lv(&target1_color, &conv_32_to_8(pixel), threshold as u8);
-                        if b == invert {
-                            out_pixel.alpha = 0.0;
-                            out_pixel.red = 1.0;
-                            out_pixel.green = 1.0;
-                            out_pixel.blue = 1.0;
-                        }
-                    }
-                    _ => return Err(Error::BadCallbackParameter),
-                }
-
-                Ok(())
-            },
-        )?;
-        Ok(())
+        let metric = ColorMetric::from_popup(params.get(Params::Metric)?.as_popup()?.value());
+        let softness = params.get(Params::Softness)?.as_float_slider()?.value();
+        let unpremultiply = params.get(Params::Unpremultiply)?.as_checkbox()?.value();
+        let match_alpha = params.get(Params::MatchAlpha)?.as_checkbox()?.value();
+        let alpha = params.get(Params::AlphaToMatch)?.as_float_slider()?.value();
+        Ok(PixelSelector::new(
+            invert,
+            target1_enabled.then_some(target1_color.into()),
+            // スライダーの値は 8bit での差として扱う(元の実装と同じ)
+            slider::level8(threshold),
+            metric,
+        )
+        .with_softness(slider::level8(softness))
+        .with_target_alpha(match_alpha.then(|| percent_to_alpha8(alpha)))
+        .with_unpremultiply(unpremultiply))
     }
 }